@@ -1,8 +1,10 @@
 use crate::domains::auth_service::AuthService;
-use crate::domains::dto::auth::{LoginRequestDto, LogoutRequestDto, RegisterRequestDto};
+use crate::domains::dto::auth::{
+    LoginRequestDto, LogoutRequestDto, PromoteToDispatcherRequestDto, RegisterRequestDto,
+};
 use crate::errors::AppError;
 use crate::repositories::auth_repository::AuthRepositoryImpl;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
@@ -82,3 +84,83 @@ pub async fn user_profile_image_handler(
         .content_type("image/png")
         .body(profile_image_byte))
 }
+
+#[derive(Deserialize, Debug)]
+pub struct UsersQueryParams {
+    // カンマ区切りのユーザーID
+    ids: String,
+}
+
+pub async fn get_users_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    query: web::Query<UsersQueryParams>,
+) -> Result<HttpResponse, AppError> {
+    let ids = query
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse::<i32>().map_err(|_| AppError::BadRequest))
+        .collect::<Result<Vec<i32>, AppError>>()?;
+    match service.get_users_by_ids(&ids).await {
+        Ok(users) => Ok(HttpResponse::Ok().json(users)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UserSearchQueryParams {
+    prefix: String,
+    limit: Option<i64>,
+}
+
+pub async fn search_users_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    http_req: HttpRequest,
+    query: web::Query<UserSearchQueryParams>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    match service
+        .search_users_by_username(&query.prefix, query.limit, session_token)
+        .await
+    {
+        Ok(users) => Ok(HttpResponse::Ok().json(users)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_session_count_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = path.into_inner();
+    match service.count_valid_sessions(user_id).await {
+        Ok(count) => Ok(HttpResponse::Ok().json(count)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn promote_to_dispatcher_handler(
+    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    http_req: HttpRequest,
+    path: web::Path<i32>,
+    req: web::Json<PromoteToDispatcherRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    let user_id = path.into_inner();
+    match service
+        .promote_to_dispatcher(user_id, req.area_id, session_token)
+        .await
+    {
+        Ok(dispatcher) => Ok(HttpResponse::Ok().json(dispatcher)),
+        Err(err) => Err(err),
+    }
+}
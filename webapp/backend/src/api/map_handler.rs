@@ -1,9 +1,16 @@
 use crate::{
-    domains::{dto::map::UpdateEdgeRequestDto, map_service::MapService},
+    domains::{
+        auth_service::AuthService,
+        dto::map::{EccentricityDto, UpdateEdgeRequestDto},
+        map_service::{decode_map_import, MapService, MAX_MAP_IMPORT_BYTES},
+    },
     errors::AppError,
-    repositories::map_repository::MapRepositoryImpl,
+    models::area::AreaId,
+    repositories::{auth_repository::AuthRepositoryImpl, map_repository::MapRepositoryImpl},
 };
-use actix_web::{web, HttpResponse};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use serde::Deserialize;
 
 pub async fn update_edge_handler(
     service: web::Data<MapService<MapRepositoryImpl>>,
@@ -17,3 +24,131 @@ pub async fn update_edge_handler(
         Err(err) => Err(err),
     }
 }
+
+#[derive(Deserialize, Debug)]
+pub struct NeighborhoodQuery {
+    node_id: i32,
+    hops: Option<usize>,
+}
+
+pub async fn get_neighborhood_handler(
+    service: web::Data<MapService<MapRepositoryImpl>>,
+    query: web::Query<NeighborhoodQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .get_neighborhood(query.node_id, query.hops.unwrap_or(1))
+        .await
+    {
+        Ok(graph) => Ok(HttpResponse::Ok().json(graph)),
+        Err(err) => Err(err),
+    }
+}
+
+// gzip で圧縮したエクスポートをそのまま取り込めるよう、本文を Content-Encoding に応じて展開する
+// actix-web の抽出器は本文を自動で展開するため、Payload から圧縮されたままの本文を読む
+// 地図全体を書き換えるため管理者のみ
+pub async fn import_map_handler(
+    service: web::Data<MapService<MapRepositoryImpl>>,
+    auth_service: web::Data<AuthService<AuthRepositoryImpl>>,
+    http_req: HttpRequest,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    auth_service.ensure_admin(session_token).await?;
+    let content_encoding = http_req
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|h| h.to_str().map_err(|_| AppError::BadRequest))
+        .transpose()?;
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|_| AppError::BadRequest)?;
+        if body.len() + chunk.len() > MAX_MAP_IMPORT_BYTES {
+            return Err(AppError::BadRequest);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let req = decode_map_import(&body, content_encoding)?;
+
+    match service.import_map(&req.nodes, &req.edges).await {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RouteQuery {
+    from: i32,
+    to: i32,
+}
+
+pub async fn get_route_handler(
+    service: web::Data<MapService<MapRepositoryImpl>>,
+    query: web::Query<RouteQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service.get_route(query.from, query.to).await {
+        Ok(Some(route)) => Ok(HttpResponse::Ok().json(route)),
+        Ok(None) => Ok(HttpResponse::NotFound().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EccentricityQuery {
+    node_id: i32,
+}
+
+pub async fn get_eccentricity_handler(
+    service: web::Data<MapService<MapRepositoryImpl>>,
+    query: web::Query<EccentricityQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service.get_eccentricity(query.node_id).await {
+        Ok(Some(eccentricity)) => Ok(HttpResponse::Ok().json(EccentricityDto {
+            node_id: query.node_id,
+            eccentricity,
+        })),
+        Ok(None) => Ok(HttpResponse::NotFound().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NetworkSizeQuery {
+    area: i32,
+}
+
+pub async fn get_network_size_handler(
+    service: web::Data<MapService<MapRepositoryImpl>>,
+    query: web::Query<NetworkSizeQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service.get_network_size(AreaId::from(query.area)).await {
+        Ok(network_size) => Ok(HttpResponse::Ok().json(network_size)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExportGraphQuery {
+    area: i32,
+    compress: Option<bool>,
+}
+
+pub async fn export_area_graph_handler(
+    service: web::Data<MapService<MapRepositoryImpl>>,
+    query: web::Query<ExportGraphQuery>,
+) -> Result<HttpResponse, AppError> {
+    let exported = service
+        .export_area_graph(AreaId::from(query.area), query.compress.unwrap_or(false))
+        .await?;
+
+    let mut response = HttpResponse::Ok();
+    response.content_type("application/json");
+    if let Some(content_encoding) = exported.content_encoding {
+        response.insert_header((header::CONTENT_ENCODING, content_encoding));
+    }
+    Ok(response.body(exported.body))
+}
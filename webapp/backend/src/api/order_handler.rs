@@ -1,15 +1,30 @@
 use crate::domains::dto::order::{
-    ClientOrderRequestDto, DispatcherOrderRequestDto, UpdateOrderStatusRequestDto,
+    AutoDispatchRequestDto, BatchDispatchRequestDto, BulkCompleteRequestDto,
+    CancelAreaPendingRequestDto, CancelOrderRequestDto, ClientOrderRequestDto,
+    DedupePendingRequestDto, DispatcherOrderRequestDto, MultiTruckDispatchRequestDto,
+    MultiTruckDispatchResultDto, PreferredDispatchRequestDto, RelocateOrderRequestDto,
+    ReopenOrderRequestDto, UpdateOrderStatusRequestDto,
 };
 use crate::domains::order_service::OrderService;
 use crate::errors::AppError;
+use crate::models::area::AreaId;
+use crate::models::order::{OrderFilter, OrderStatus};
 use crate::repositories::auth_repository::AuthRepositoryImpl;
 use crate::repositories::map_repository::MapRepositoryImpl;
 use crate::repositories::order_repository::OrderRepositoryImpl;
 use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
-use actix_web::{web, HttpResponse};
+use crate::utils::bounded_duration;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 
+// 二重送信とみなす間隔の上限 (1日)
+const MAX_DEDUPE_WINDOW_SECONDS: i64 = 24 * 60 * 60;
+// 配車からの経過時間として指定できる上限 (30日)
+const MAX_OVERDUE_AGE_SECONDS: i64 = 30 * 24 * 60 * 60;
+// 最近の注文として遡れる期間の上限 (7日)
+const MAX_RECENT_WINDOW_MINUTES: i64 = 7 * 24 * 60;
+
 pub async fn update_order_status_handler(
     service: web::Data<
         OrderService<
@@ -36,9 +51,19 @@ pub async fn get_order_handler(
             MapRepositoryImpl,
         >,
     >,
+    http_req: HttpRequest,
     path: web::Path<i32>,
 ) -> Result<HttpResponse, AppError> {
-    match service.get_order_by_id(path.into_inner()).await {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    let viewer_role = service.viewer_role(session_token).await?;
+    match service
+        .get_order_by_id(path.into_inner(), viewer_role)
+        .await
+    {
         Ok(order) => Ok(HttpResponse::Ok().json(order)),
         Err(err) => Err(err),
     }
@@ -52,6 +77,7 @@ pub struct PaginatedOrderQuery {
     sort_order: Option<String>,
     status: Option<String>,
     area: Option<i32>,
+    assigned: Option<bool>,
 }
 
 pub async fn get_paginated_orders_handler(
@@ -63,16 +89,31 @@ pub async fn get_paginated_orders_handler(
             MapRepositoryImpl,
         >,
     >,
+    http_req: HttpRequest,
     query: web::Query<PaginatedOrderQuery>,
 ) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    let viewer_role = service.viewer_role(session_token).await?;
+    let filter = OrderFilter {
+        statuses: match query.status.as_deref() {
+            Some(status) => OrderStatus::parse_list(status)?,
+            None => vec![],
+        },
+        area: query.area.map(AreaId::from),
+        assigned: query.assigned,
+    };
     match service
         .get_paginated_orders(
             query.page.unwrap_or(0),
             query.page_size.unwrap_or(10),
             query.sort_by.clone(),
             query.sort_order.clone(),
-            query.status.clone(),
-            query.area,
+            filter,
+            viewer_role,
         )
         .await
     {
@@ -93,10 +134,17 @@ pub async fn create_client_order_handler(
     req: web::Json<ClientOrderRequestDto>,
 ) -> Result<HttpResponse, AppError> {
     match service
-        .create_client_order(req.client_id, req.node_id, req.car_value)
+        .create_client_order(
+            req.client_id,
+            req.node_id,
+            req.destination_node_id,
+            req.car_value,
+            req.contact_phone.as_deref(),
+            req.trucks_required.unwrap_or(1),
+        )
         .await
     {
-        Ok(_) => Ok(HttpResponse::Created().finish()),
+        Ok(order) => Ok(HttpResponse::Created().json(order)),
         Err(err) => Err(err),
     }
 }
@@ -125,3 +173,549 @@ pub async fn create_dispatcher_order_handler(
         Err(err) => Err(err),
     }
 }
+
+pub async fn create_dispatcher_order_preferred_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<PreferredDispatchRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    service.ensure_dispatcher(session_token).await?;
+    match service
+        .create_dispatcher_order_preferred(
+            req.order_id,
+            req.dispatcher_id,
+            req.preferred_tow_truck_id,
+        )
+        .await
+    {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn dispatch_required_tow_trucks_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<MultiTruckDispatchRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    service.ensure_dispatcher(session_token).await?;
+    match service
+        .dispatch_required_tow_trucks(req.order_id, req.dispatcher_id)
+        .await
+    {
+        Ok(tow_truck_ids) => {
+            Ok(HttpResponse::Ok().json(MultiTruckDispatchResultDto { tow_truck_ids }))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn dispatch_and_describe_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<AutoDispatchRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    let viewer_role = service.ensure_dispatcher(session_token).await?;
+    match service
+        .dispatch_and_describe(
+            req.order_id,
+            req.dispatcher_id,
+            req.allow_adjacent_areas.unwrap_or(false),
+            viewer_role,
+        )
+        .await
+    {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn batch_dispatch_minmax_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<BatchDispatchRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    service.ensure_dispatcher(session_token).await?;
+    match service.batch_dispatch_minmax(req.dispatcher_id).await {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn bulk_complete_orders_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<BulkCompleteRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    service.ensure_dispatcher(session_token).await?;
+    match service
+        .bulk_complete(&req.order_ids, req.completed_time)
+        .await
+    {
+        Ok(results) => Ok(HttpResponse::Ok().json(results)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn dedupe_pending_orders_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<DedupePendingRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    service.ensure_dispatcher(session_token).await?;
+    let window = bounded_duration(
+        "window_seconds",
+        req.window_seconds,
+        MAX_DEDUPE_WINDOW_SECONDS,
+        chrono::Duration::seconds,
+    )?;
+    match service.dedupe_pending(req.client_id, window).await {
+        Ok(results) => Ok(HttpResponse::Ok().json(results)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn cancel_order_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<CancelOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    service.ensure_dispatcher(session_token).await?;
+    match service.cancel_order(req.order_id, &req.reason).await {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn cancel_area_pending_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<CancelAreaPendingRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    match service
+        .cancel_area_pending(AreaId::from(req.area_id), &req.reason, session_token)
+        .await
+    {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn relocate_order_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<RelocateOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    service.ensure_dispatcher(session_token).await?;
+    match service.relocate_order(req.order_id, req.node_id).await {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CompletionSlaReportQuery {
+    area: i32,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+pub async fn get_completion_sla_report_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    query: web::Query<CompletionSlaReportQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .get_completion_sla_report(AreaId::from(query.area), query.from, query.to)
+        .await
+    {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_dispatch_fairness_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    query: web::Query<CompletionSlaReportQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .get_dispatch_fairness(AreaId::from(query.area), query.from, query.to)
+        .await
+    {
+        Ok(fairness) => Ok(HttpResponse::Ok().json(fairness)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_area_stats_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+) -> Result<HttpResponse, AppError> {
+    let stats = service.get_area_stats().await?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+pub async fn reopen_order_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    req: web::Json<ReopenOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    match service.reopen_order(req.order_id, session_token).await {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_order_dispatcher_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    match service.dispatcher_for_order(path.into_inner()).await {
+        Ok(Some(dispatcher)) => Ok(HttpResponse::Ok().json(dispatcher)),
+        Ok(None) => Ok(HttpResponse::NotFound().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PaginatedCompletedOrderQuery {
+    page: Option<i32>,
+    page_size: Option<i32>,
+}
+
+pub async fn get_completed_orders_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    query: web::Query<PaginatedCompletedOrderQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .get_completed_orders(query.page.unwrap_or(0), query.page_size.unwrap_or(10))
+        .await
+    {
+        Ok(completed_orders) => Ok(HttpResponse::Ok().json(completed_orders)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OverdueOrderQuery {
+    area: i32,
+    max_age_seconds: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DispatcherQueueQuery {
+    dispatcher_id: i32,
+    after_time: Option<DateTime<Utc>>,
+    after_id: Option<i32>,
+    limit: Option<i32>,
+}
+
+pub async fn get_dispatcher_queue_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    query: web::Query<DispatcherQueueQuery>,
+) -> Result<HttpResponse, AppError> {
+    // after_time と after_id は揃って指定された場合のみ続きから取得する
+    let after = match (query.after_time, query.after_id) {
+        (Some(after_time), Some(after_id)) => Some((after_time, after_id)),
+        (None, None) => None,
+        _ => return Err(AppError::BadRequest),
+    };
+    match service
+        .get_dispatcher_queue(query.dispatcher_id, after, query.limit.unwrap_or(20))
+        .await
+    {
+        Ok(queue) => Ok(HttpResponse::Ok().json(queue)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RecentOrderQuery {
+    area: i32,
+    window_minutes: i64,
+}
+
+pub async fn get_recent_orders_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    query: web::Query<RecentOrderQuery>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    let viewer_role = service.viewer_role(session_token).await?;
+    let window = bounded_duration(
+        "window_minutes",
+        query.window_minutes,
+        MAX_RECENT_WINDOW_MINUTES,
+        chrono::Duration::minutes,
+    )?;
+    match service
+        .get_recent_orders(AreaId::from(query.area), window, viewer_role)
+        .await
+    {
+        Ok(orders) => Ok(HttpResponse::Ok().json(orders)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LatestOrderQuery {
+    // カンマ区切りのクライアントID
+    client_ids: String,
+}
+
+pub async fn get_latest_orders_by_client_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    http_req: HttpRequest,
+    query: web::Query<LatestOrderQuery>,
+) -> Result<HttpResponse, AppError> {
+    let session_token = http_req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+    let viewer_role = service.viewer_role(session_token).await?;
+    let client_ids = query
+        .client_ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse::<i32>().map_err(|_| AppError::BadRequest))
+        .collect::<Result<Vec<i32>, AppError>>()?;
+    match service
+        .get_latest_orders_by_client(&client_ids, viewer_role)
+        .await
+    {
+        Ok(orders) => Ok(HttpResponse::Ok().json(orders)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NearbyOrderQuery {
+    node_id: i32,
+    radius: i32,
+}
+
+pub async fn get_pending_orders_within_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    query: web::Query<NearbyOrderQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .get_pending_orders_within(query.node_id, query.radius)
+        .await
+    {
+        Ok(orders) => Ok(HttpResponse::Ok().json(orders)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_overdue_dispatched_orders_handler(
+    service: web::Data<
+        OrderService<
+            OrderRepositoryImpl,
+            TowTruckRepositoryImpl,
+            AuthRepositoryImpl,
+            MapRepositoryImpl,
+        >,
+    >,
+    query: web::Query<OverdueOrderQuery>,
+) -> Result<HttpResponse, AppError> {
+    let max_age = bounded_duration(
+        "max_age_seconds",
+        query.max_age_seconds,
+        MAX_OVERDUE_AGE_SECONDS,
+        chrono::Duration::seconds,
+    )?;
+    match service
+        .get_overdue_dispatched_orders(AreaId::from(query.area), max_age)
+        .await
+    {
+        Ok(orders) => Ok(HttpResponse::Ok().json(orders)),
+        Err(err) => Err(err),
+    }
+}
@@ -1,13 +1,16 @@
-use crate::domains::tow_truck_service::TowTruckService;
+use crate::domains::dto::tow_truck::{TowTruckStatusChangeDto, UpdateTowTruckStatusRequestDto};
+use crate::domains::tow_truck_service::{NearestTowTruck, TowTruckService};
 use crate::errors::AppError;
+use crate::models::area::AreaId;
 use crate::repositories::order_repository::OrderRepositoryImpl;
 use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
 use crate::{
-    domains::dto::tow_truck::UpdateLocationRequestDto,
+    domains::dto::tow_truck::{HeartbeatRequestDto, UpdateLocationRequestDto},
     repositories::map_repository::MapRepositoryImpl,
 };
 use actix_web::{web, HttpResponse};
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
 pub struct PaginatedTowTruckQuery {
@@ -15,6 +18,8 @@ pub struct PaginatedTowTruckQuery {
     page_size: Option<i32>,
     status: Option<String>,
     area: Option<i32>,
+    include_deleted: Option<bool>,
+    model: Option<String>,
 }
 
 pub async fn get_paginated_tow_trucks_handler(
@@ -26,9 +31,11 @@ pub async fn get_paginated_tow_trucks_handler(
     let tow_trucks = service
         .get_all_tow_trucks(
             query.page.unwrap_or(0),
-            query.page_size.unwrap_or(-1),
+            query.page_size,
             query.status.clone(),
-            query.area,
+            query.area.map(AreaId::from),
+            query.include_deleted.unwrap_or(false),
+            query.model.clone(),
         )
         .await?;
 
@@ -56,16 +63,47 @@ pub async fn update_location_handler(
     req: web::Json<UpdateLocationRequestDto>,
 ) -> Result<HttpResponse, AppError> {
     service
-        .update_location(req.tow_truck_id, req.node_id)
+        .update_location(req.tow_truck_id, req.node_id, req.timestamp)
         .await?;
     Ok(HttpResponse::Ok().finish())
 }
 
+pub async fn heartbeat_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    req: web::Json<HeartbeatRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    service.heartbeat(req.tow_truck_id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn update_tow_truck_status_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    req: web::Json<UpdateTowTruckStatusRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let changed = service
+        .update_tow_truck_status(req.tow_truck_id, &req.status)
+        .await?;
+    Ok(HttpResponse::Ok().json(TowTruckStatusChangeDto {
+        tow_truck_id: req.tow_truck_id,
+        changed,
+    }))
+}
+
 #[derive(Deserialize, Debug)]
 pub struct TowTruckQuery {
     order_id: i32,
 }
 
+#[derive(Serialize, Debug)]
+pub struct UnreachableResponse {
+    message: String,
+    available_count: usize,
+}
+
 pub async fn get_nearest_available_tow_trucks_handler(
     service: web::Data<
         TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
@@ -76,7 +114,211 @@ pub async fn get_nearest_available_tow_trucks_handler(
         .get_nearest_available_tow_trucks(query.order_id)
         .await
     {
-        Ok(Some(tow_truck)) => Ok(HttpResponse::Ok().json(tow_truck)),
+        Ok(NearestTowTruck::Found(tow_truck)) => Ok(HttpResponse::Ok().json(tow_truck)),
+        Ok(NearestTowTruck::NoAvailable) => Ok(HttpResponse::NotFound().finish()),
+        Ok(NearestTowTruck::Unreachable { available_count }) => {
+            Ok(HttpResponse::NotFound().json(UnreachableResponse {
+                message: "No available tow truck can reach the order".to_string(),
+                available_count,
+            }))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReclaimableTowTruckQuery {
+    area: Option<i32>,
+}
+
+pub async fn get_reclaimable_tow_trucks_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<ReclaimableTowTruckQuery>,
+) -> Result<HttpResponse, AppError> {
+    let tow_trucks = service
+        .get_reclaimable_tow_trucks(query.area.map(AreaId::from))
+        .await?;
+    Ok(HttpResponse::Ok().json(tow_trucks))
+}
+
+pub async fn get_nearest_depot_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    match service.nearest_depot(path.into_inner()).await {
+        Ok(Some(depot)) => Ok(HttpResponse::Ok().json(depot)),
+        Ok(None) => Ok(HttpResponse::NotFound().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FleetCentroidQuery {
+    area: i32,
+}
+
+pub async fn get_fleet_availability_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<FleetCentroidQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .get_fleet_availability(AreaId::from(query.area))
+        .await
+    {
+        Ok(availability) => Ok(HttpResponse::Ok().json(availability)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_fleet_centroid_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<FleetCentroidQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service.get_fleet_centroid(AreaId::from(query.area)).await {
+        Ok(Some(centroid)) => Ok(HttpResponse::Ok().json(centroid)),
+        Ok(None) => Ok(HttpResponse::NotFound().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn assign_pending_in_area_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<FleetCentroidQuery>,
+) -> Result<HttpResponse, AppError> {
+    let result = service
+        .assign_pending_in_area(AreaId::from(query.area))
+        .await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn reconcile_busy_tow_trucks_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<FleetCentroidQuery>,
+) -> Result<HttpResponse, AppError> {
+    let result = service
+        .reconcile_busy_without_active_order(AreaId::from(query.area))
+        .await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LocationHistoryQuery {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+pub async fn get_location_history_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    path: web::Path<i32>,
+    query: web::Query<LocationHistoryQuery>,
+) -> Result<HttpResponse, AppError> {
+    let history = service
+        .get_location_history(path.into_inner(), query.from, query.to)
+        .await?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UtilizationQuery {
+    area: i32,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+pub async fn get_utilization_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<UtilizationQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .get_utilization(AreaId::from(query.area), query.from, query.to)
+        .await
+    {
+        Ok(utilization) => Ok(HttpResponse::Ok().json(utilization)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_candidates_for_order_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<TowTruckQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service.candidates_for_order(query.order_id).await {
+        Ok(candidates) => Ok(HttpResponse::Ok().json(candidates)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DriverQuery {
+    username: String,
+}
+
+pub async fn get_tow_trucks_by_driver_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<DriverQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .get_tow_trucks_by_driver_username(&query.username)
+        .await
+    {
+        Ok(tow_trucks) => Ok(HttpResponse::Ok().json(tow_trucks)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TowTruckIdsQuery {
+    // カンマ区切りのトラックID
+    ids: String,
+}
+
+pub async fn get_tow_trucks_by_ids_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<TowTruckIdsQuery>,
+) -> Result<HttpResponse, AppError> {
+    let ids = query
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse::<i32>().map_err(|_| AppError::BadRequest))
+        .collect::<Result<Vec<i32>, AppError>>()?;
+    match service.get_tow_trucks_by_ids(&ids).await {
+        Ok(tow_trucks) => Ok(HttpResponse::Ok().json(tow_trucks)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_tow_truck_coverage_handler(
+    service: web::Data<
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
+    >,
+    query: web::Query<TowTruckQuery>,
+) -> Result<HttpResponse, AppError> {
+    match service.get_tow_truck_coverage(query.order_id).await {
+        Ok(Some(coverage)) => Ok(HttpResponse::Ok().json(coverage)),
         Ok(None) => Ok(HttpResponse::NotFound().finish()),
         Err(err) => Err(err),
     }
@@ -5,22 +5,55 @@ use actix_web::web::Bytes;
 use log::error;
 
 use crate::errors::AppError;
-use crate::models::user::{Dispatcher, Session, User};
+use crate::models::user::{Dispatcher, DispatcherProfile, Role, Session, User};
 use crate::utils::{generate_session_token, hash_password, verify_password};
 
-use super::dto::auth::LoginResponseDto;
+use super::dto::auth::{DispatcherDto, LoginResponseDto, SessionCountDto, UserDto};
+
+const MAX_USERNAME_LENGTH: usize = 64;
+// ハッシュ化の前に弾くパスワードの最大長 (バイト数)
+const MAX_PASSWORD_LENGTH: usize = 128;
+// ユーザー名の前方一致検索で返す最大件数
+const MAX_USER_SEARCH_LIMIT: i64 = 50;
+
+// ユーザー名は英数字と "_", "-", "." のみ許可する
+fn validate_username(username: &str) -> Result<(), AppError> {
+    let is_valid_length = !username.is_empty() && username.len() <= MAX_USERNAME_LENGTH;
+    let is_valid_charset = username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+    match is_valid_length && is_valid_charset {
+        true => Ok(()),
+        false => Err(AppError::BadRequest),
+    }
+}
+
+fn validate_password(password: &str) -> Result<(), AppError> {
+    match !password.is_empty() && password.len() <= MAX_PASSWORD_LENGTH {
+        true => Ok(()),
+        false => Err(AppError::BadRequest),
+    }
+}
 
 pub trait AuthRepository {
     async fn create_user(&self, username: &str, password: &str, role: &str)
         -> Result<(), AppError>;
     async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError>;
     async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError>;
+    async fn search_users_by_username(
+        &self,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<User>, AppError>;
     async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError>;
+    async fn promote_to_dispatcher(&self, user_id: i32, area_id: i32) -> Result<i32, AppError>;
     async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError>;
     async fn find_dispatcher_by_user_id(
         &self,
         user_id: i32,
     ) -> Result<Option<Dispatcher>, AppError>;
+    #[allow(dead_code)]
     async fn find_user_by_ids(&self, ids: &[i32]) -> Result<Vec<User>, AppError>;
     async fn find_users_by_ids(&self, ids: &[i32]) -> Result<Vec<User>, AppError>;
     async fn find_profile_image_name_by_user_id(
@@ -29,9 +62,35 @@ pub trait AuthRepository {
     ) -> Result<Option<String>, AppError>;
     async fn create_session(&self, user_id: i32, session_token: &str) -> Result<(), AppError>;
     async fn delete_session(&self, session_token: &str) -> Result<(), AppError>;
+    async fn count_valid_sessions(&self, user_id: i32) -> Result<i64, AppError>;
     async fn find_session_by_session_token(&self, session_token: &str)
         -> Result<Session, AppError>;
     async fn find_dispatchers_by_ids(&self, ids: &[i32]) -> Result<Vec<Dispatcher>, AppError>;
+    async fn find_users_by_ids_in_order(&self, ids: &[i32]) -> Result<Vec<Option<User>>, AppError>;
+    async fn find_dispatcher_profile_by_id(
+        &self,
+        id: i32,
+    ) -> Result<Option<DispatcherProfile>, AppError>;
+}
+
+// セッションの持ち主のロールを取得する (セッションやユーザーが見つからない場合は Unauthorized)
+// 権限の確認が必要な各サービスから共通で使う
+pub async fn session_role<T: AuthRepository>(
+    repository: &T,
+    session_token: &str,
+) -> Result<Role, AppError> {
+    let session = repository
+        .find_session_by_session_token(session_token)
+        .await
+        .map_err(|_| AppError::Unauthorized)?;
+    let user = repository
+        .find_user_by_id(session.user_id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    user.role
+        .parse::<Role>()
+        .map_err(|_| AppError::InternalServerError)
 }
 
 #[derive(Debug)]
@@ -51,7 +110,15 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
         role: &str,
         area: Option<i32>,
     ) -> Result<LoginResponseDto, AppError> {
-        if role == "dispatcher" && area.is_none() {
+        validate_username(username)?;
+        validate_password(password)?;
+        let role = role.parse::<Role>()?;
+        // 管理者は登録APIからは作成できない
+        if role == Role::Admin {
+            return Err(AppError::BadRequest);
+        }
+
+        if role == Role::Dispatcher && area.is_none() {
             return Err(AppError::BadRequest);
         }
 
@@ -62,7 +129,7 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
         let hashed_password = hash_password(password).unwrap();
 
         self.repository
-            .create_user(username, &hashed_password, role)
+            .create_user(username, &hashed_password, role.as_str())
             .await?;
 
         let session_token = generate_session_token();
@@ -197,6 +264,105 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
         }
     }
 
+    // 指定したIDと同じ順にユーザーを返す (存在しないIDの位置は null)
+    pub async fn get_users_by_ids(&self, ids: &[i32]) -> Result<Vec<Option<UserDto>>, AppError> {
+        let users = self.repository.find_users_by_ids_in_order(ids).await?;
+
+        Ok(users
+            .into_iter()
+            .map(|user| {
+                user.map(|user| UserDto {
+                    id: user.id,
+                    username: user.username,
+                    role: user.role,
+                })
+            })
+            .collect())
+    }
+
+    // 「他の端末でもログイン中」の表示用に、有効期限内のセッション数を返す
+    pub async fn count_valid_sessions(&self, user_id: i32) -> Result<SessionCountDto, AppError> {
+        let count = self.repository.count_valid_sessions(user_id).await?;
+
+        Ok(SessionCountDto { user_id, count })
+    }
+
+    // サポート対応用に、ユーザー名の前方一致でユーザーを検索する (ディスパッチャー・管理者のみ)
+    // インデックスを使えるよう前方一致に限り、空の文字列での検索は受け付けない
+    pub async fn search_users_by_username(
+        &self,
+        prefix: &str,
+        limit: Option<i64>,
+        session_token: &str,
+    ) -> Result<Vec<UserDto>, AppError> {
+        match self.viewer_role(session_token).await? {
+            Role::Dispatcher | Role::Admin => {}
+            _ => return Err(AppError::Forbidden),
+        }
+        if prefix.is_empty() || prefix.len() > MAX_USERNAME_LENGTH {
+            return Err(AppError::BadRequest);
+        }
+        let limit = limit.unwrap_or(MAX_USER_SEARCH_LIMIT);
+        if limit <= 0 {
+            return Err(AppError::BadRequest);
+        }
+
+        let users = self
+            .repository
+            .search_users_by_username(prefix, limit.min(MAX_USER_SEARCH_LIMIT))
+            .await?;
+
+        Ok(users
+            .into_iter()
+            .map(|user| UserDto {
+                id: user.id,
+                username: user.username,
+                role: user.role,
+            })
+            .collect())
+    }
+
+    // セッションからリクエストしたユーザーのロールを取得する
+    async fn viewer_role(&self, session_token: &str) -> Result<Role, AppError> {
+        session_role(&self.repository, session_token).await
+    }
+
+    // セッションの持ち主が管理者であることを確認する
+    pub async fn ensure_admin(&self, session_token: &str) -> Result<(), AppError> {
+        match self.viewer_role(session_token).await? {
+            Role::Admin => Ok(()),
+            _ => Err(AppError::Forbidden),
+        }
+    }
+
+    // 既存のユーザーを指定エリアのディスパッチャーにする (管理者のみ)
+    // ロールの変更とディスパッチャーの登録は同じトランザクションで行う
+    pub async fn promote_to_dispatcher(
+        &self,
+        user_id: i32,
+        area_id: i32,
+        session_token: &str,
+    ) -> Result<DispatcherDto, AppError> {
+        self.ensure_admin(session_token).await?;
+
+        let dispatcher_id = self
+            .repository
+            .promote_to_dispatcher(user_id, area_id)
+            .await?;
+        let dispatcher = self
+            .repository
+            .find_dispatcher_profile_by_id(dispatcher_id)
+            .await?
+            .ok_or(AppError::InternalServerError)?;
+
+        Ok(DispatcherDto {
+            id: dispatcher.id,
+            user_id: dispatcher.user_id,
+            username: dispatcher.username,
+            area_id: dispatcher.area_id,
+        })
+    }
+
     pub async fn validate_session(&self, session_token: &str) -> Result<bool, AppError> {
         let session = self
             .repository
@@ -206,3 +372,215 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
         Ok(session.is_valid)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::auth_repository::AuthRepositoryImpl;
+    use crate::testsupport::{circuit_breaker, seed_area, seed_session, seed_user, test_pool};
+
+    #[test]
+    fn accepts_usernames_of_allowed_characters_up_to_the_limit() {
+        for username in [
+            "a",
+            "driver_01",
+            "first.last-2",
+            &"x".repeat(MAX_USERNAME_LENGTH),
+        ] {
+            assert!(validate_username(username).is_ok(), "{}", username);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_long_or_unusual_usernames() {
+        let too_long = "x".repeat(MAX_USERNAME_LENGTH + 1);
+        for username in [
+            "",
+            &too_long,
+            "with space",
+            "semi;colon",
+            "ユーザー",
+            "tab\t",
+        ] {
+            assert!(
+                matches!(validate_username(username), Err(AppError::BadRequest)),
+                "{}",
+                username
+            );
+        }
+    }
+
+    #[test]
+    fn password_length_is_checked_in_bytes() {
+        assert!(validate_password("p").is_ok());
+        assert!(validate_password(&"p".repeat(MAX_PASSWORD_LENGTH)).is_ok());
+        assert!(validate_password("").is_err());
+        assert!(validate_password(&"p".repeat(MAX_PASSWORD_LENGTH + 1)).is_err());
+        // 多バイト文字は文字数ではなくバイト数で数える
+        assert!(validate_password(&"あ".repeat(MAX_PASSWORD_LENGTH / 3 + 1)).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn admin_promotes_a_client_to_dispatcher_of_the_area() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let service = AuthService::new(AuthRepositoryImpl::new(pool.clone(), circuit_breaker()));
+        let area_id = seed_area(&pool).await;
+        let admin_id = seed_user(&pool, "admin").await;
+        let admin_token = seed_session(&pool, admin_id).await;
+        let client_id = seed_user(&pool, "client").await;
+
+        let dispatcher = service
+            .promote_to_dispatcher(client_id, area_id, &admin_token)
+            .await
+            .unwrap();
+
+        assert_eq!(dispatcher.user_id, client_id);
+        assert_eq!(dispatcher.area_id, area_id);
+        let role: String = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+            .bind(client_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(role, "dispatcher");
+    }
+
+    #[actix_rt::test]
+    async fn promoting_a_dispatcher_again_is_rejected_without_a_second_row() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let service = AuthService::new(AuthRepositoryImpl::new(pool.clone(), circuit_breaker()));
+        let area_id = seed_area(&pool).await;
+        let admin_id = seed_user(&pool, "admin").await;
+        let admin_token = seed_session(&pool, admin_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        service
+            .promote_to_dispatcher(client_id, area_id, &admin_token)
+            .await
+            .unwrap();
+
+        let result = service
+            .promote_to_dispatcher(client_id, area_id, &admin_token)
+            .await;
+
+        assert!(matches!(result, Err(AppError::Conflict)));
+        let dispatcher_rows: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM dispatchers WHERE user_id = ?")
+                .bind(client_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(dispatcher_rows, 1);
+    }
+
+    #[actix_rt::test]
+    async fn only_admins_can_promote_to_dispatcher() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let service = AuthService::new(AuthRepositoryImpl::new(pool.clone(), circuit_breaker()));
+        let area_id = seed_area(&pool).await;
+        let dispatcher_user_id = seed_user(&pool, "dispatcher").await;
+        let token = seed_session(&pool, dispatcher_user_id).await;
+        let client_id = seed_user(&pool, "client").await;
+
+        let result = service
+            .promote_to_dispatcher(client_id, area_id, &token)
+            .await;
+
+        // 権限がない場合はロールも変えない
+        assert!(matches!(result, Err(AppError::Forbidden)));
+        let role: String = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+            .bind(client_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(role, "client");
+    }
+
+    // 検索のテスト用に、前方一致を確かめやすいユーザー名のユーザーを作る
+    async fn seed_named_user(pool: &sqlx::MySqlPool, username: &str) -> i32 {
+        let user_id = seed_user(pool, "client").await;
+        sqlx::query("UPDATE users SET username = ? WHERE id = ?")
+            .bind(username)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        user_id
+    }
+
+    #[actix_rt::test]
+    async fn dispatchers_search_users_by_username_prefix() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let service = AuthService::new(AuthRepositoryImpl::new(pool.clone(), circuit_breaker()));
+        let dispatcher_user_id = seed_user(&pool, "dispatcher").await;
+        let token = seed_session(&pool, dispatcher_user_id).await;
+        let prefix = format!("search{}", rand::random::<u32>());
+        let first_id = seed_named_user(&pool, &format!("{}_a", prefix)).await;
+        let second_id = seed_named_user(&pool, &format!("{}_b", prefix)).await;
+        let wildcard_id = seed_named_user(&pool, &format!("{}xc", prefix)).await;
+        // 途中に含むだけのユーザーは一致しない
+        seed_named_user(&pool, &format!("x{}_d", prefix)).await;
+
+        let found: Vec<i32> = service
+            .search_users_by_username(&prefix, None, &token)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|user| user.id)
+            .collect();
+        assert_eq!(found, vec![first_id, second_id, wildcard_id]);
+
+        // _ は任意の1文字ではなく文字そのものとして扱う
+        let found: Vec<i32> = service
+            .search_users_by_username(&format!("{}_", prefix), None, &token)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|user| user.id)
+            .collect();
+        assert_eq!(found, vec![first_id, second_id]);
+
+        let limited = service
+            .search_users_by_username(&prefix, Some(2), &token)
+            .await
+            .unwrap();
+        assert_eq!(limited.len(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn user_search_rejects_an_empty_prefix_and_non_dispatchers() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let service = AuthService::new(AuthRepositoryImpl::new(pool.clone(), circuit_breaker()));
+        let dispatcher_user_id = seed_user(&pool, "dispatcher").await;
+        let dispatcher_token = seed_session(&pool, dispatcher_user_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let client_token = seed_session(&pool, client_id).await;
+
+        assert!(matches!(
+            service
+                .search_users_by_username("", None, &dispatcher_token)
+                .await,
+            Err(AppError::BadRequest)
+        ));
+        assert!(matches!(
+            service
+                .search_users_by_username("test", Some(0), &dispatcher_token)
+                .await,
+            Err(AppError::BadRequest)
+        ));
+        assert!(matches!(
+            service
+                .search_users_by_username("test", None, &client_token)
+                .await,
+            Err(AppError::Forbidden)
+        ));
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::errors::AppError;
+
+// 記録しているクライアント数がこれを超えたら、期間の過ぎた記録を捨てる
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+// 1人のクライアントが注文を連投して待ち行列を埋めないよう、一定期間あたりの注文数を制限する
+// 期間は各クライアントの最初の注文から数え、期間が過ぎると件数を数え直す (固定ウィンドウ)
+#[derive(Debug)]
+pub struct ClientOrderLimiter {
+    max_orders: u32,
+    window: Duration,
+    // クライアントIDごとの (期間の開始時刻, 期間内の注文数)
+    windows: Mutex<HashMap<i32, (Instant, u32)>>,
+}
+
+impl ClientOrderLimiter {
+    pub fn new(max_orders: u32, window: Duration) -> Self {
+        ClientOrderLimiter {
+            // 0 だとどの注文も受け付けなくなるため、最低1件は許可する
+            max_orders: max_orders.max(1),
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // 環境変数から期間あたりの注文数の上限と期間(秒)を取得し、デフォルトをそれぞれ10件・60秒に設定
+    pub fn from_env() -> Self {
+        let max_orders: u32 = env::var("CLIENT_ORDER_RATE_LIMIT")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .expect("CLIENT_ORDER_RATE_LIMIT must be a valid number");
+        let window_seconds: u64 = env::var("CLIENT_ORDER_RATE_WINDOW_SECONDS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .expect("CLIENT_ORDER_RATE_WINDOW_SECONDS must be a valid number");
+
+        ClientOrderLimiter::new(max_orders, Duration::from_secs(window_seconds))
+    }
+
+    // 注文を1件数える。期間内の上限を超える場合は数えずに TooManyRequests を返す
+    pub fn try_acquire(&self, client_id: i32) -> Result<(), AppError> {
+        self.try_acquire_at(client_id, Instant::now())
+    }
+
+    fn try_acquire_at(&self, client_id: i32, now: Instant) -> Result<(), AppError> {
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= MAX_TRACKED_CLIENTS && !windows.contains_key(&client_id) {
+            windows.retain(|_, (started_at, _)| now.duration_since(*started_at) < self.window);
+        }
+
+        let (started_at, count) = windows.entry(client_id).or_insert((now, 0));
+        if now.duration_since(*started_at) >= self.window {
+            *started_at = now;
+            *count = 0;
+        }
+        if *count >= self.max_orders {
+            return Err(AppError::TooManyRequests);
+        }
+        *count += 1;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_orders_over_the_limit_within_the_window() {
+        let limiter = ClientOrderLimiter::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at(1, start).is_ok());
+        assert!(limiter
+            .try_acquire_at(1, start + Duration::from_secs(1))
+            .is_ok());
+        assert!(matches!(
+            limiter.try_acquire_at(1, start + Duration::from_secs(59)),
+            Err(AppError::TooManyRequests)
+        ));
+        // 他のクライアントの注文は別に数える
+        assert!(limiter.try_acquire_at(2, start).is_ok());
+    }
+
+    #[test]
+    fn counts_again_once_the_window_has_passed() {
+        let limiter = ClientOrderLimiter::new(1, Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(limiter.try_acquire_at(1, start).is_ok());
+        assert!(limiter
+            .try_acquire_at(1, start + Duration::from_secs(30))
+            .is_err());
+
+        // 期間は最初の注文から数えるため、拒否された注文では延びない
+        let next_window = start + Duration::from_secs(60);
+        assert!(limiter.try_acquire_at(1, next_window).is_ok());
+        assert!(limiter
+            .try_acquire_at(1, next_window + Duration::from_secs(1))
+            .is_err());
+    }
+
+    #[test]
+    fn zero_limit_still_allows_one_order() {
+        let limiter = ClientOrderLimiter::new(0, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at(1, start).is_ok());
+        assert!(limiter.try_acquire_at(1, start).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::models::area::AreaId;
+
+// 少ないトラックに配車が殺到しないよう、エリアごとに同時に進行できる配車の数を制限する
+// 上限を超えた配車は、同じエリアの先行する配車が終わるまで待たされる (別エリアの配車は待たない)
+#[derive(Debug)]
+pub struct DispatchLimiter {
+    max_concurrent: usize,
+    semaphores: Mutex<HashMap<AreaId, Arc<Semaphore>>>,
+}
+
+impl DispatchLimiter {
+    pub fn new(max_concurrent: usize) -> Self {
+        DispatchLimiter {
+            // 0 だとどの配車も進めなくなるため、最低1件は許可する
+            max_concurrent: max_concurrent.max(1),
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // 環境変数からエリアごとの同時配車数の上限を取得し、デフォルトを2件に設定
+    pub fn from_env() -> Self {
+        let max_concurrent: usize = env::var("MAX_CONCURRENT_DISPATCHES_PER_AREA")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse()
+            .expect("MAX_CONCURRENT_DISPATCHES_PER_AREA must be a valid number");
+
+        DispatchLimiter::new(max_concurrent)
+    }
+
+    // 返り値の許可を保持している間、そのエリアの枠を1つ使う
+    pub async fn acquire(&self, area_id: AreaId) -> OwnedSemaphorePermit {
+        let semaphore = self
+            .semaphores
+            .lock()
+            .unwrap()
+            .entry(area_id)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_concurrent)))
+            .clone();
+
+        semaphore
+            .acquire_owned()
+            .await
+            .expect("dispatch semaphore is never closed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[actix_rt::test]
+    async fn limits_concurrent_dispatches_per_area_only() {
+        let limiter = DispatchLimiter::new(2);
+        let first = limiter.acquire(AreaId(1)).await;
+        let _second = limiter.acquire(AreaId(1)).await;
+
+        // エリア 1 の枠は埋まっているが、エリア 2 の配車は待たない
+        assert!(limiter.acquire(AreaId(1)).now_or_never().is_none());
+        assert!(limiter.acquire(AreaId(2)).now_or_never().is_some());
+
+        drop(first);
+        assert!(limiter.acquire(AreaId(1)).now_or_never().is_some());
+    }
+
+    #[actix_rt::test]
+    async fn zero_limit_still_allows_one_dispatch() {
+        let limiter = DispatchLimiter::new(0);
+        let _permit = limiter.acquire(AreaId(1)).await;
+
+        assert!(limiter.acquire(AreaId(1)).now_or_never().is_none());
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::models::area::AreaId;
+
+type Distances = Arc<HashMap<i32, i32>>;
+
+// (エリアID, 起点ノードID) ごとにダイクストラ法の結果を短時間だけ保持するキャッシュ
+// 同じ注文に対する配車候補の問い合わせが続いた場合に再計算を避ける
+#[derive(Debug)]
+pub struct DistanceCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<(AreaId, i32), (Instant, Distances)>>,
+}
+
+impl DistanceCache {
+    pub fn new(ttl: Duration) -> Self {
+        DistanceCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // 環境変数からキャッシュの有効期間(ミリ秒)を取得し、デフォルトを1000ミリ秒に設定
+    pub fn from_env() -> Self {
+        let ttl_millis: u64 = env::var("DISTANCE_CACHE_TTL_MILLIS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .expect("DISTANCE_CACHE_TTL_MILLIS must be a valid number");
+
+        DistanceCache::new(Duration::from_millis(ttl_millis))
+    }
+
+    pub fn get(&self, area_id: AreaId, start_node_id: i32) -> Option<Distances> {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(&(area_id, start_node_id)) {
+            Some((cached_at, distances)) if cached_at.elapsed() < self.ttl => {
+                Some(distances.clone())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn insert(&self, area_id: AreaId, start_node_id: i32, distances: Distances) {
+        let mut entries = self.entries.lock().unwrap();
+        // 期限切れのエントリはここでまとめて捨てる
+        entries.retain(|_, (cached_at, _)| cached_at.elapsed() < self.ttl);
+        entries.insert((area_id, start_node_id), (Instant::now(), distances));
+    }
+
+    // 辺の重みが変わったエリアのキャッシュを破棄する
+    pub fn invalidate_area(&self, area_id: AreaId) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|(cached_area_id, _), _| *cached_area_id != area_id);
+    }
+
+    // 地図の取り込みなど、影響するエリアを特定しにくい変更の後に全て破棄する
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distances() -> Distances {
+        Arc::new(HashMap::from([(1, 0), (2, 5)]))
+    }
+
+    #[test]
+    fn returns_cached_distances_within_the_ttl() {
+        let cache = DistanceCache::new(Duration::from_secs(600));
+        cache.insert(AreaId(1), 1, distances());
+
+        assert_eq!(cache.get(AreaId(1), 1), Some(distances()));
+        assert_eq!(cache.get(AreaId(1), 2), None);
+        assert_eq!(cache.get(AreaId(2), 1), None);
+    }
+
+    #[test]
+    fn expired_entries_are_not_returned() {
+        let cache = DistanceCache::new(Duration::ZERO);
+        cache.insert(AreaId(1), 1, distances());
+
+        assert_eq!(cache.get(AreaId(1), 1), None);
+    }
+
+    #[test]
+    fn invalidating_an_area_keeps_other_areas() {
+        let cache = DistanceCache::new(Duration::from_secs(600));
+        cache.insert(AreaId(1), 1, distances());
+        cache.insert(AreaId(2), 1, distances());
+
+        cache.invalidate_area(AreaId(1));
+
+        assert_eq!(cache.get(AreaId(1), 1), None);
+        assert_eq!(cache.get(AreaId(2), 1), Some(distances()));
+    }
+}
@@ -21,6 +21,11 @@ pub struct LogoutRequestDto {
     pub session_token: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct PromoteToDispatcherRequestDto {
+    pub area_id: i32,
+}
+
 // Output Data Structure
 
 #[derive(Serialize)]
@@ -32,3 +37,24 @@ pub struct LoginResponseDto {
     pub dispatcher_id: Option<i32>,
     pub area_id: Option<i32>,
 }
+
+#[derive(Serialize, Debug)]
+pub struct DispatcherDto {
+    pub id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub area_id: i32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserDto {
+    pub id: i32,
+    pub username: String,
+    pub role: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SessionCountDto {
+    pub user_id: i32,
+    pub count: i64,
+}
@@ -0,0 +1,42 @@
+use serde::Serialize;
+
+// 一括処理の項目ごとの結果
+#[derive(Serialize, Debug)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum BatchItemResult<T> {
+    Ok { id: i32, value: T },
+    // error には失敗の理由 ("not_found" など) を入れる
+    Err { id: i32, error: String },
+}
+
+// 一括処理の結果
+// 失敗した項目があっても残りの項目の処理は続け、項目ごとの成否を要求と同じ順に返す
+#[derive(Serialize, Debug)]
+pub struct BatchResult<T> {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub items: Vec<BatchItemResult<T>>,
+}
+
+impl<T> BatchResult<T> {
+    pub fn new() -> Self {
+        BatchResult {
+            succeeded: 0,
+            failed: 0,
+            items: Vec::new(),
+        }
+    }
+
+    pub fn push_ok(&mut self, id: i32, value: T) {
+        self.succeeded += 1;
+        self.items.push(BatchItemResult::Ok { id, value });
+    }
+
+    pub fn push_err(&mut self, id: i32, error: &str) {
+        self.failed += 1;
+        self.items.push(BatchItemResult::Err {
+            id,
+            error: error.to_string(),
+        });
+    }
+}
@@ -1,6 +1,8 @@
 // Input Data Structure
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::models::graph::{Edge, Graph, Node};
 
 #[derive(Deserialize, Debug)]
 pub struct UpdateEdgeRequestDto {
@@ -8,3 +10,69 @@ pub struct UpdateEdgeRequestDto {
     pub node_b_id: i32,
     pub weight: i32,
 }
+
+#[derive(Deserialize, Debug)]
+pub struct ImportMapRequestDto {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct ImportMapResultDto {
+    pub nodes_inserted: u64,
+    pub edges_inserted: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GraphDto {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl GraphDto {
+    // 双方向に保持している辺は node_a_id < node_b_id の向きだけを出力する
+    pub fn from_graph(graph: &Graph) -> Self {
+        let mut nodes: Vec<Node> = graph.nodes.values().cloned().collect();
+        nodes.sort_by_key(|node| node.id);
+
+        let mut edges: Vec<Edge> = graph
+            .edges
+            .values()
+            .flatten()
+            .filter(|edge| edge.node_a_id < edge.node_b_id)
+            .cloned()
+            .collect();
+        edges.sort_by_key(|edge| (edge.node_a_id, edge.node_b_id));
+
+        GraphDto { nodes, edges }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RouteDto {
+    pub node_ids: Vec<i32>,
+    pub edges: Vec<Edge>,
+    pub total_weight: i32,
+}
+
+// エクスポートした地図の本文 (JSON)。content_encoding が Some の場合はその形式で圧縮している
+#[derive(Debug)]
+pub struct ExportedGraph {
+    pub body: Vec<u8>,
+    pub content_encoding: Option<&'static str>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NetworkSizeDto {
+    pub area_id: i32,
+    pub node_count: usize,
+    pub total_weight: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EccentricityDto {
+    pub node_id: i32,
+    pub eccentricity: i32,
+}
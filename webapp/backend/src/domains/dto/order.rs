@@ -1,5 +1,8 @@
+use crate::domains::dto::tow_truck::TowTruckDto;
+use crate::models::order::{CancelReason, OrderStatus};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 // Input Data Structure
 
@@ -7,7 +10,11 @@ use serde::{Deserialize, Serialize};
 pub struct ClientOrderRequestDto {
     pub client_id: i32,
     pub node_id: i32,
+    pub destination_node_id: Option<i32>,
     pub car_value: f64,
+    pub contact_phone: Option<String>,
+    // 省略時は1台
+    pub trucks_required: Option<i32>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -24,6 +31,67 @@ pub struct UpdateOrderStatusRequestDto {
     pub status: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct BulkCompleteRequestDto {
+    pub order_ids: Vec<i32>,
+    pub completed_time: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReopenOrderRequestDto {
+    pub order_id: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CancelOrderRequestDto {
+    pub order_id: i32,
+    pub reason: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CancelAreaPendingRequestDto {
+    pub area_id: i32,
+    pub reason: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RelocateOrderRequestDto {
+    pub order_id: i32,
+    pub node_id: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DedupePendingRequestDto {
+    pub client_id: i32,
+    pub window_seconds: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PreferredDispatchRequestDto {
+    pub order_id: i32,
+    pub dispatcher_id: i32,
+    pub preferred_tow_truck_id: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AutoDispatchRequestDto {
+    pub order_id: i32,
+    pub dispatcher_id: i32,
+    // true の場合、注文のエリアに配車できるトラックがいなければ隣接するエリアのトラックも探す
+    pub allow_adjacent_areas: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BatchDispatchRequestDto {
+    pub dispatcher_id: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MultiTruckDispatchRequestDto {
+    pub order_id: i32,
+    pub dispatcher_id: i32,
+}
+
 // Output Data Structure
 
 #[derive(Serialize, Debug)]
@@ -37,14 +105,26 @@ pub struct OrderDto {
     pub tow_truck_id: Option<i32>,
     pub driver_user_id: Option<i32>,
     pub driver_username: Option<String>,
-    pub status: String,
+    pub status: OrderStatus,
     pub node_id: i32,
+    pub destination_node_id: Option<i32>,
     pub area_id: i32,
     pub car_value: f64,
+    // ディスパッチャー・管理者以外には下4桁以外を伏せて返す
+    pub contact_phone: Option<String>,
+    pub trucks_required: i32,
+    pub cancel_reason: Option<CancelReason>,
+    // 未配車の注文や、配車時に経路が見つからなかった注文は None
+    pub eta_seconds: Option<i32>,
     pub order_time: DateTime<Utc>,
     pub completed_time: Option<DateTime<Utc>>,
 }
 
+#[derive(Serialize, Debug)]
+pub struct CreatedOrderDto {
+    pub id: i32,
+}
+
 #[derive(Serialize, Debug)]
 pub struct CompletedOrderDto {
     pub id: i32,
@@ -54,3 +134,118 @@ pub struct CompletedOrderDto {
     pub completed_time: DateTime<Utc>,
     pub car_value: f64,
 }
+
+#[derive(Serialize, Debug)]
+pub struct CompletedOrderDetailDto {
+    pub id: i32,
+    pub order_id: i32,
+    pub tow_truck_id: i32,
+    pub client_id: i32,
+    pub client_username: Option<String>,
+    pub driver_user_id: Option<i32>,
+    pub driver_username: Option<String>,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CompletionSlaReportDto {
+    pub count: usize,
+    pub average_seconds: Option<f64>,
+    pub p95_seconds: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DispatchFairnessDto {
+    // ディスパッチャーIDごとの配車件数
+    pub counts: HashMap<i32, i64>,
+    // 最多件数 / 最少件数 (配車実績のあるディスパッチャーが1人もいない場合は None)
+    pub imbalance: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DedupedOrderDto {
+    pub kept_order_id: i32,
+    pub canceled_order_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CanceledOrderCountDto {
+    pub area_id: i32,
+    pub canceled_count: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PreferredDispatchResultDto {
+    pub tow_truck_id: i32,
+    // false の場合は指定トラックが使えず、最寄りの空きトラックに配車した
+    pub used_preferred: bool,
+}
+
+#[derive(Serialize)]
+pub struct DispatchResultDto {
+    pub order: OrderDto,
+    pub truck: TowTruckDto,
+    // トラックの現在地から注文地点までのノードIDの列
+    pub route: Vec<i32>,
+    pub distance: i32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MultiTruckDispatchResultDto {
+    pub tow_truck_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QueuedOrderDto {
+    pub id: i32,
+    pub client_id: i32,
+    pub node_id: i32,
+    pub destination_node_id: Option<i32>,
+    pub car_value: f64,
+    pub trucks_required: i32,
+    pub order_time: DateTime<Utc>,
+}
+
+// 次のページを取得するときに after_time / after_id としてそのまま渡す
+#[derive(Serialize, Debug)]
+pub struct QueueCursorDto {
+    pub after_time: DateTime<Utc>,
+    pub after_id: i32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DispatcherQueueDto {
+    pub orders: Vec<QueuedOrderDto>,
+    // 最後のページでは None
+    pub next_cursor: Option<QueueCursorDto>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NearbyOrderDto {
+    pub id: i32,
+    pub node_id: i32,
+    // 指定ノードからの経路の距離
+    pub distance: i32,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AreaStatsDto {
+    pub area_id: i32,
+    pub pending_order_count: i64,
+    pub available_tow_truck_count: i64,
+    // 完了済みの注文が1件もないエリアは None
+    pub average_completion_seconds: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OverdueOrderDto {
+    pub id: i32,
+    pub dispatcher_id: Option<i32>,
+    pub tow_truck_id: Option<i32>,
+    pub order_time: DateTime<Utc>,
+    pub age_seconds: i64,
+}
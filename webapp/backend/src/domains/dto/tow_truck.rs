@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 // Input Data Structure
@@ -6,10 +7,30 @@ use serde::{Deserialize, Serialize};
 pub struct UpdateLocationRequestDto {
     pub tow_truck_id: i32,
     pub node_id: i32,
+    // 端末が位置情報を送信した時刻 (省略時は受信時刻)
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HeartbeatRequestDto {
+    pub tow_truck_id: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateTowTruckStatusRequestDto {
+    pub tow_truck_id: i32,
+    pub status: String,
 }
 
 // Output Data Structure
 
+#[derive(Serialize)]
+pub struct TowTruckStatusChangeDto {
+    pub tow_truck_id: i32,
+    // 既に同じステータスだった場合は false
+    pub changed: bool,
+}
+
 #[derive(Serialize, Clone)]
 pub struct TowTruckDto {
     pub id: i32,
@@ -18,6 +39,87 @@ pub struct TowTruckDto {
     pub status: String,
     pub node_id: i32,
     pub area_id: i32,
+    pub order_id: Option<i32>,
+    pub model: Option<String>,
+    pub year: Option<i32>,
+    // 論理削除 (運用から除外) 済みのトラック
+    pub deleted: bool,
+}
+
+#[derive(Serialize)]
+pub struct TowTruckDistanceDto {
+    pub tow_truck: TowTruckDto,
+    pub distance: i32,
+}
+
+#[derive(Serialize)]
+pub struct TowTruckCandidateDto {
+    pub tow_truck: TowTruckDto,
+    // 注文地点に到達できないトラックは None
+    pub distance: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct TowTruckCoverageDto {
+    pub nearest: TowTruckDistanceDto,
+    pub furthest: TowTruckDistanceDto,
+}
+
+#[derive(Serialize)]
+pub struct DepotDistanceDto {
+    pub node_id: i32,
+    pub distance: i32,
+}
+
+#[derive(Serialize)]
+pub struct FleetAvailabilityDto {
+    pub area_id: i32,
+    pub available_count: i64,
+    pub total_count: i64,
+    // 空きトラックの割合 (%)
+    pub availability: f64,
+}
+
+#[derive(Serialize)]
+pub struct LocationHistoryDto {
+    pub node_id: i32,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct ReconciledTowTruckCountDto {
+    pub area_id: i32,
+    pub freed_count: usize,
+}
+
+#[derive(Serialize)]
+pub struct FleetCentroidDto {
+    pub node_id: i32,
+    pub total_distance: i32,
+    // node_id から到達できた空きトラックの台数
+    pub tow_truck_count: usize,
+}
+
+#[derive(Serialize)]
+pub struct PendingAssignmentDto {
+    pub order_id: i32,
+    pub tow_truck_id: i32,
+    pub distance: i32,
+}
+
+#[derive(Serialize)]
+pub struct PendingAssignmentResultDto {
+    pub assignments: Vec<PendingAssignmentDto>,
+    // 到達できるトラックがいない、または最寄りのトラックを他の注文に割り当て済みの注文
+    pub unassigned_order_ids: Vec<i32>,
+}
+
+#[derive(Serialize)]
+pub struct TowTruckUtilizationDto {
+    pub tow_truck_id: i32,
+    pub busy_seconds: i64,
+    // 期間の長さに対する busy だった時間の割合 (%)
+    pub utilization: f64,
 }
 
 impl TowTruckDto {
@@ -29,6 +131,10 @@ impl TowTruckDto {
             status: entity.status,
             node_id: entity.node_id,
             area_id: entity.area_id,
+            order_id: None,
+            model: entity.model,
+            year: entity.year,
+            deleted: entity.deleted_at.is_some(),
         }
     }
 }
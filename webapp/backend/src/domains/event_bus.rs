@@ -0,0 +1,116 @@
+use log::info;
+use std::fmt;
+use std::sync::RwLock;
+
+// 注文の状態変化を、通知・監査・メトリクスなどの後続処理に伝えるためのイベント
+#[derive(Clone, Debug)]
+pub enum DomainEvent {
+    OrderDispatched {
+        order_id: i32,
+        tow_truck_ids: Vec<i32>,
+    },
+    OrderCompleted {
+        order_id: i32,
+    },
+    OrderCanceled {
+        order_id: i32,
+    },
+    TowTruckStatusChanged {
+        tow_truck_id: i32,
+        status: String,
+    },
+}
+
+// 通知や監査の購読者を追加するまでの、イベントをログに残すだけの購読者
+pub fn log_event(event: &DomainEvent) {
+    match event {
+        DomainEvent::OrderDispatched {
+            order_id,
+            tow_truck_ids,
+        } => info!(
+            "注文 {} をトラック {:?} に配車しました",
+            order_id, tow_truck_ids
+        ),
+        DomainEvent::OrderCompleted { order_id } => info!("注文 {} が完了しました", order_id),
+        DomainEvent::OrderCanceled { order_id } => {
+            info!("注文 {} がキャンセルされました", order_id)
+        }
+        DomainEvent::TowTruckStatusChanged {
+            tow_truck_id,
+            status,
+        } => info!(
+            "トラック {} のステータスを {} に変更しました",
+            tow_truck_id, status
+        ),
+    }
+}
+
+type Subscriber = Box<dyn Fn(&DomainEvent) + Send + Sync>;
+
+// プロセス内の簡易なイベントバス
+// 購読者は publish の中で登録順に同期的に呼ばれるため、重い処理は購読者側で別タスクに逃がすこと
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: RwLock<Vec<Subscriber>>,
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscribers.read().unwrap().len())
+            .finish()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus::default()
+    }
+
+    pub fn subscribe<F>(&self, subscriber: F)
+    where
+        F: Fn(&DomainEvent) + Send + Sync + 'static,
+    {
+        self.subscribers.write().unwrap().push(Box::new(subscriber));
+    }
+
+    pub fn publish(&self, event: DomainEvent) {
+        for subscriber in self.subscribers.read().unwrap().iter() {
+            subscriber(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn publish_calls_every_subscriber_in_subscription_order() {
+        let bus = EventBus::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        for name in ["first", "second"] {
+            let received = received.clone();
+            bus.subscribe(move |event| {
+                if let DomainEvent::OrderCompleted { order_id } = event {
+                    received.lock().unwrap().push((name, *order_id));
+                }
+            });
+        }
+
+        bus.publish(DomainEvent::OrderCompleted { order_id: 7 });
+        bus.publish(DomainEvent::OrderCanceled { order_id: 8 });
+
+        assert_eq!(*received.lock().unwrap(), vec![("first", 7), ("second", 7)]);
+    }
+
+    #[test]
+    fn publish_without_subscribers_does_nothing() {
+        let bus = EventBus::new();
+
+        bus.publish(DomainEvent::OrderCompleted { order_id: 1 });
+
+        assert_eq!(format!("{:?}", bus), "EventBus { subscribers: 0 }");
+    }
+}
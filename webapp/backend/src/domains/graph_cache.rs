@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+use super::map_service::MapRepository;
+use crate::errors::AppError;
+use crate::models::area::AreaId;
+use crate::models::graph::Graph;
+
+// 環境変数から辺の重みに掛ける倍率を取得する (未設定の場合は倍率をかけない)
+// 経路探索の感度を調べるためのもので、経路の長さの大小関係が変わらないよう正の整数のみ受け付ける
+pub fn weight_scale_from_env() -> Option<i32> {
+    let factor: i32 = env::var("GRAPH_WEIGHT_SCALE")
+        .ok()?
+        .parse()
+        .expect("GRAPH_WEIGHT_SCALE must be a valid integer");
+    assert!(factor > 0, "GRAPH_WEIGHT_SCALE must be a positive integer");
+
+    Some(factor)
+}
+
+// 重みに倍率をかけず、キャッシュも使わずにエリアのグラフを組み立てる
+pub async fn load_raw_graph<R: MapRepository>(
+    repository: &R,
+    area_id: AreaId,
+) -> Result<Graph, AppError> {
+    let mut graph = Graph::new();
+    for node in repository.get_all_nodes(Some(area_id)).await? {
+        graph.add_node(node);
+    }
+    for edge in repository.get_all_edges(Some(area_id)).await? {
+        graph.add_edge(edge);
+    }
+
+    Ok(graph)
+}
+
+// エリアごとの地図グラフを保持するキャッシュ
+// 地図が変わるのは辺の更新や取り込みのときだけなので、有効期限は設けずに変更時に破棄する
+// 辺の重みに掛ける倍率は起動時に一度だけ読み込み、グラフを読み込むたびに使う
+#[derive(Debug)]
+pub struct GraphCache {
+    graphs: Mutex<HashMap<AreaId, Arc<Graph>>>,
+    weight_scale: Option<i32>,
+}
+
+impl GraphCache {
+    pub fn new(weight_scale: Option<i32>) -> Self {
+        GraphCache {
+            graphs: Mutex::new(HashMap::new()),
+            weight_scale,
+        }
+    }
+
+    // キャッシュにあればそれを返し、無ければ地図から組み立てて倍率をかけてから保持する
+    pub async fn load<R: MapRepository>(
+        &self,
+        repository: &R,
+        area_id: AreaId,
+    ) -> Result<Arc<Graph>, AppError> {
+        if let Some(graph) = self.graphs.lock().unwrap().get(&area_id).cloned() {
+            return Ok(graph);
+        }
+
+        let mut graph = load_raw_graph(repository, area_id).await?;
+        if let Some(factor) = self.weight_scale {
+            graph.scale_weights(factor);
+        }
+        let graph = Arc::new(graph);
+        self.graphs.lock().unwrap().insert(area_id, graph.clone());
+
+        Ok(graph)
+    }
+
+    // 複数のエリアのグラフを1つにまとめる (エリアをまたぐ辺も両端のエリアを含めればつながる)
+    pub async fn load_combined<R: MapRepository>(
+        &self,
+        repository: &R,
+        area_ids: &[AreaId],
+    ) -> Result<Graph, AppError> {
+        let mut graph = Graph::new();
+        for &area_id in area_ids {
+            graph.merge(&*self.load(repository, area_id).await?);
+        }
+
+        Ok(graph)
+    }
+
+    // 辺の重みが変わったエリアのグラフを破棄する
+    pub fn invalidate(&self, area_id: AreaId) {
+        self.graphs.lock().unwrap().remove(&area_id);
+    }
+
+    // 地図の取り込みなど、影響するエリアを特定しにくい変更の後に全て破棄する
+    pub fn clear(&self) {
+        self.graphs.lock().unwrap().clear();
+    }
+}
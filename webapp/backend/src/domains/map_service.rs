@@ -1,28 +1,109 @@
 use crate::{
     errors::AppError,
-    models::graph::{Edge, Node},
+    models::area::AreaId,
+    models::graph::{Edge, Graph, Node},
 };
 
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use super::distance_cache::DistanceCache;
+use super::dto::map::{
+    ExportedGraph, GraphDto, ImportMapRequestDto, ImportMapResultDto, NetworkSizeDto, RouteDto,
+};
+use super::graph_cache::{load_raw_graph, GraphCache};
+
+// 取り込む地図データ (展開後) の最大サイズ
+pub const MAX_MAP_IMPORT_BYTES: usize = 32 * 1024 * 1024;
+pub const GZIP_ENCODING: &str = "gzip";
+
+// 取り込みの本文を Content-Encoding に応じて展開し、地図データとして解釈する
+// 未対応の圧縮形式や壊れたデータは BadRequest とする
+pub fn decode_map_import(
+    body: &[u8],
+    content_encoding: Option<&str>,
+) -> Result<ImportMapRequestDto, AppError> {
+    let json = match content_encoding {
+        None | Some("identity") => body.to_vec(),
+        Some(GZIP_ENCODING) => {
+            let mut json = Vec::new();
+            // 展開後のサイズが上限を超えるデータ (圧縮爆弾など) は途中で打ち切る
+            GzDecoder::new(body)
+                .take(MAX_MAP_IMPORT_BYTES as u64 + 1)
+                .read_to_end(&mut json)
+                .map_err(|_| AppError::BadRequest)?;
+            if json.len() > MAX_MAP_IMPORT_BYTES {
+                return Err(AppError::BadRequest);
+            }
+            json
+        }
+        Some(_) => return Err(AppError::BadRequest),
+    };
+
+    serde_json::from_slice(&json).map_err(|e| AppError::ValidationError(e.to_string()))
+}
+
+// グラフを取り込みと同じ形式の JSON にし、compress が true の場合は gzip で圧縮する
+fn encode_graph_export(graph: &Graph, compress: bool) -> Result<ExportedGraph, AppError> {
+    let json = serde_json::to_vec(&GraphDto::from_graph(graph))
+        .map_err(|_| AppError::InternalServerError)?;
+    if !compress {
+        return Ok(ExportedGraph {
+            body: json,
+            content_encoding: None,
+        });
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&json)
+        .map_err(|_| AppError::InternalServerError)?;
+    let body = encoder
+        .finish()
+        .map_err(|_| AppError::InternalServerError)?;
+
+    Ok(ExportedGraph {
+        body,
+        content_encoding: Some(GZIP_ENCODING),
+    })
+}
+
 pub trait MapRepository {
-    async fn get_all_nodes(&self, area_id: Option<i32>) -> Result<Vec<Node>, sqlx::Error>;
-    async fn get_all_edges(&self, area_id: Option<i32>) -> Result<Vec<Edge>, sqlx::Error>;
-    async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<i32, sqlx::Error>;
+    async fn get_all_nodes(&self, area_id: Option<AreaId>) -> Result<Vec<Node>, sqlx::Error>;
+    async fn get_all_edges(&self, area_id: Option<AreaId>) -> Result<Vec<Edge>, sqlx::Error>;
+    async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<AreaId, sqlx::Error>;
     async fn update_edge(
         &self,
         node_a_id: i32,
         node_b_id: i32,
         weight: i32,
     ) -> Result<(), sqlx::Error>;
+    async fn get_depot_node_ids(&self, area_id: AreaId) -> Result<Vec<i32>, sqlx::Error>;
+    async fn get_adjacent_area_ids(&self, area_id: AreaId) -> Result<Vec<AreaId>, sqlx::Error>;
+    async fn insert_map(&self, nodes: &[Node], edges: &[Edge]) -> Result<(u64, u64), sqlx::Error>;
 }
 
 #[derive(Debug)]
 pub struct MapService<T: MapRepository + std::fmt::Debug> {
     repository: T,
+    distance_cache: Arc<DistanceCache>,
+    graph_cache: Arc<GraphCache>,
 }
 
 impl<T: MapRepository + std::fmt::Debug> MapService<T> {
-    pub fn new(repository: T) -> Self {
-        MapService { repository }
+    pub fn new(
+        repository: T,
+        distance_cache: Arc<DistanceCache>,
+        graph_cache: Arc<GraphCache>,
+    ) -> Self {
+        MapService {
+            repository,
+            distance_cache,
+            graph_cache,
+        }
     }
 
     pub async fn update_edge(
@@ -31,10 +112,192 @@ impl<T: MapRepository + std::fmt::Debug> MapService<T> {
         node_b_id: i32,
         weight: i32,
     ) -> Result<(), AppError> {
+        let area_id = self.repository.get_area_id_by_node_id(node_a_id).await?;
+        if !self
+            .load_area_graph(area_id)
+            .await?
+            .contains_edge(node_a_id, node_b_id)
+        {
+            return Err(AppError::NotFound);
+        }
+
         self.repository
             .update_edge(node_a_id, node_b_id, weight)
             .await?;
 
+        // 重みが変わったエリアのグラフと距離のキャッシュを破棄する
+        self.graph_cache.invalidate(area_id);
+        self.distance_cache.invalidate_area(area_id);
+
         Ok(())
     }
+
+    async fn load_area_graph(&self, area_id: AreaId) -> Result<Arc<Graph>, AppError> {
+        self.graph_cache.load(&self.repository, area_id).await
+    }
+
+    pub async fn get_neighborhood(&self, node_id: i32, hops: usize) -> Result<GraphDto, AppError> {
+        let area_id = self.repository.get_area_id_by_node_id(node_id).await?;
+        let graph = self.load_area_graph(area_id).await?;
+
+        Ok(GraphDto::from_graph(
+            &graph.subgraph_within_hops(node_id, hops),
+        ))
+    }
+
+    // ノードと辺はまとめて取り込み、途中で失敗した場合はどちらも取り込まない
+    pub async fn import_map(
+        &self,
+        nodes: &[Node],
+        edges: &[Edge],
+    ) -> Result<ImportMapResultDto, AppError> {
+        Graph::validate_parts(nodes, edges).map_err(AppError::ValidationError)?;
+
+        let (nodes_inserted, edges_inserted) = self.repository.insert_map(nodes, edges).await?;
+
+        self.graph_cache.clear();
+        self.distance_cache.clear();
+
+        Ok(ImportMapResultDto {
+            nodes_inserted,
+            edges_inserted,
+        })
+    }
+
+    // 取り込んだ地図が小さすぎたり大きすぎたりしないかを確認するための、エリアのノード数と辺の重みの合計
+    // 取り込んだデータそのものを確認するため、キャッシュ (GRAPH_WEIGHT_SCALE の倍率をかけたもの) は使わない
+    pub async fn get_network_size(&self, area_id: AreaId) -> Result<NetworkSizeDto, AppError> {
+        let graph = load_raw_graph(&self.repository, area_id).await?;
+
+        Ok(NetworkSizeDto {
+            area_id: area_id.0,
+            node_count: graph.nodes.len(),
+            total_weight: graph.total_weight(),
+        })
+    }
+
+    // エリアの地図を取り込みと同じ形式の JSON で返す。compress が true の場合は gzip で圧縮する
+    // 取り込み直せるよう、倍率 (GRAPH_WEIGHT_SCALE) をかける前の重みで出力する
+    pub async fn export_area_graph(
+        &self,
+        area_id: AreaId,
+        compress: bool,
+    ) -> Result<ExportedGraph, AppError> {
+        let graph = load_raw_graph(&self.repository, area_id).await?;
+
+        encode_graph_export(&graph, compress)
+    }
+
+    // 指定ノードの同じエリア内での離心率を返す
+    pub async fn get_eccentricity(&self, node_id: i32) -> Result<Option<i32>, AppError> {
+        let area_id = self.repository.get_area_id_by_node_id(node_id).await?;
+        let graph = self.load_area_graph(area_id).await?;
+        if !graph.contains_node(node_id) {
+            return Err(AppError::NotFound);
+        }
+
+        Ok(graph.eccentricity(node_id))
+    }
+
+    // 同じエリア内の2ノード間の最短経路を、区間ごとの辺と重みの合計付きで返す
+    pub async fn get_route(
+        &self,
+        from_node_id: i32,
+        to_node_id: i32,
+    ) -> Result<Option<RouteDto>, AppError> {
+        let area_id = self.repository.get_area_id_by_node_id(from_node_id).await?;
+        let graph = self.load_area_graph(area_id).await?;
+
+        let route = graph
+            .shortest_path_nodes(from_node_id, to_node_id)
+            .and_then(|node_ids| {
+                graph.path_edges(&node_ids).map(|edges| RouteDto {
+                    total_weight: edges.iter().map(|edge| edge.weight).sum(),
+                    node_ids,
+                    edges,
+                })
+            });
+
+        Ok(route)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_graph() -> Graph {
+        let mut graph = Graph::new();
+        for id in 1..=3 {
+            graph.add_node(Node {
+                id,
+                name: format!("node{id}"),
+                area_id: 1,
+                x: id * 10,
+                y: -id,
+            });
+        }
+        graph.add_edge(Edge {
+            node_a_id: 1,
+            node_b_id: 2,
+            weight: 5,
+        });
+        graph.add_edge(Edge {
+            node_a_id: 2,
+            node_b_id: 3,
+            weight: 7,
+        });
+        graph
+    }
+
+    fn node_keys(nodes: &[Node]) -> Vec<(i32, String, i32, i32, i32)> {
+        nodes
+            .iter()
+            .map(|node| (node.id, node.name.clone(), node.area_id, node.x, node.y))
+            .collect()
+    }
+
+    fn edge_keys(edges: &[Edge]) -> Vec<(i32, i32, i32)> {
+        edges
+            .iter()
+            .map(|edge| (edge.node_a_id, edge.node_b_id, edge.weight))
+            .collect()
+    }
+
+    #[test]
+    fn compressed_export_round_trips_through_import() {
+        let graph = sample_graph();
+        let expected = GraphDto::from_graph(&graph);
+
+        let exported = encode_graph_export(&graph, true).unwrap();
+        assert_eq!(exported.content_encoding, Some(GZIP_ENCODING));
+        assert_eq!(&exported.body[..2], &[0x1f, 0x8b]);
+
+        let imported = decode_map_import(&exported.body, exported.content_encoding).unwrap();
+        assert_eq!(node_keys(&imported.nodes), node_keys(&expected.nodes));
+        assert_eq!(edge_keys(&imported.edges), edge_keys(&expected.edges));
+    }
+
+    #[test]
+    fn uncompressed_export_is_plain_json() {
+        let exported = encode_graph_export(&sample_graph(), false).unwrap();
+        assert_eq!(exported.content_encoding, None);
+
+        let imported = decode_map_import(&exported.body, None).unwrap();
+        assert_eq!(imported.nodes.len(), 3);
+        assert_eq!(edge_keys(&imported.edges), vec![(1, 2, 5), (2, 3, 7)]);
+    }
+
+    #[test]
+    fn rejects_unknown_encoding_and_corrupt_gzip() {
+        let exported = encode_graph_export(&sample_graph(), false).unwrap();
+        assert!(matches!(
+            decode_map_import(&exported.body, Some("br")),
+            Err(AppError::BadRequest)
+        ));
+        assert!(matches!(
+            decode_map_import(&exported.body, Some(GZIP_ENCODING)),
+            Err(AppError::BadRequest)
+        ));
+    }
 }
@@ -1,5 +1,12 @@
 pub mod auth_service;
+pub mod client_order_limiter;
+pub mod dispatch_limiter;
+pub mod distance_cache;
 pub mod dto;
+pub mod event_bus;
+pub mod graph_cache;
 pub mod map_service;
 pub mod order_service;
+pub mod router;
+pub mod shutdown;
 pub mod tow_truck_service;
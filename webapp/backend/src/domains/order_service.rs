@@ -1,43 +1,226 @@
 use super::{
-    auth_service::AuthRepository, dto::order::OrderDto, map_service::MapRepository,
-    tow_truck_service::TowTruckRepository,
+    auth_service::{session_role, AuthRepository},
+    client_order_limiter::ClientOrderLimiter,
+    dispatch_limiter::DispatchLimiter,
+    dto::auth::DispatcherDto,
+    dto::batch::BatchResult,
+    dto::order::{
+        AreaStatsDto, CanceledOrderCountDto, CompletedOrderDetailDto, CompletedOrderDto,
+        CompletionSlaReportDto, CreatedOrderDto, DedupedOrderDto, DispatchFairnessDto,
+        DispatchResultDto, DispatcherQueueDto, NearbyOrderDto, OrderDto, OverdueOrderDto,
+        PreferredDispatchResultDto, QueueCursorDto, QueuedOrderDto,
+    },
+    dto::tow_truck::{PendingAssignmentDto, TowTruckDto},
+    event_bus::{DomainEvent, EventBus},
+    graph_cache::GraphCache,
+    map_service::MapRepository,
+    router::Router,
+    shutdown::ShutdownCoordinator,
+    tow_truck_service::{ensure_start_node, TowTruckRepository},
 };
+use crate::models::area::{AreaId, AreaStats};
+use crate::models::graph::Graph;
 use crate::models::tow_truck::TowTruck;
 use crate::models::user::Dispatcher;
-use crate::models::user::User;
-use crate::{errors::AppError, models::order::Order};
+use crate::models::user::{Role, User};
+use crate::utils::fetch_in_chunks;
+use crate::{
+    errors::AppError,
+    models::order::{
+        CancelReason, CompletedOrderDetail, CompletionOutcome, Order, OrderEvent, OrderFilter,
+        OrderStatus,
+    },
+};
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const MIN_CONTACT_PHONE_DIGITS: usize = 7;
+const MAX_CONTACT_PHONE_DIGITS: usize = 15;
+const VISIBLE_CONTACT_PHONE_DIGITS: usize = 4;
+// 注文一覧にユーザー名などを付ける際の、1回の問い合わせで IN 句に渡すIDの最大数
+const ENRICH_CHUNK_SIZE: usize = 100;
+
+// 環境変数からトラックの移動速度 (1分あたりに進む辺の重み) を取得し、デフォルトを1に設定
+pub fn tow_truck_speed_from_env() -> f64 {
+    let speed: f64 = env::var("TOW_TRUCK_SPEED_PER_MINUTE")
+        .unwrap_or_else(|_| "1.0".to_string())
+        .parse()
+        .expect("TOW_TRUCK_SPEED_PER_MINUTE must be a valid number");
+    assert!(
+        speed.is_finite() && speed > 0.0,
+        "TOW_TRUCK_SPEED_PER_MINUTE must be a positive number"
+    );
+
+    speed
+}
+
+// 経路の距離を、移動速度 (1分あたりに進む辺の重み) をもとに到着予定時間(秒)に換算する
+fn eta_seconds_for_distance(distance: i32, speed_per_minute: f64) -> i32 {
+    (distance as f64 / speed_per_minute * 60.0).round() as i32
+}
+
+// 完了までの秒数から件数・平均・95 パーセンタイルを求める (完了した注文がなければ平均と p95 は None)
+fn completion_sla_report(mut durations: Vec<i64>) -> CompletionSlaReportDto {
+    durations.sort_unstable();
+
+    let count = durations.len();
+    if count == 0 {
+        return CompletionSlaReportDto {
+            count: 0,
+            average_seconds: None,
+            p95_seconds: None,
+        };
+    }
+
+    let average_seconds = durations.iter().sum::<i64>() as f64 / count as f64;
+    // nearest-rank 法で 95 パーセンタイルを求める
+    let p95_index = ((count as f64 * 0.95).ceil() as usize).max(1) - 1;
+
+    CompletionSlaReportDto {
+        count,
+        average_seconds: Some(average_seconds),
+        p95_seconds: Some(durations[p95_index]),
+    }
+}
+
+// E.164 に近い形式 ("+" は先頭のみ任意、その後に数字が7〜15桁) のみ許可する
+fn validate_contact_phone(contact_phone: &str) -> Result<(), AppError> {
+    let digits = contact_phone.strip_prefix('+').unwrap_or(contact_phone);
+    let is_valid_length =
+        (MIN_CONTACT_PHONE_DIGITS..=MAX_CONTACT_PHONE_DIGITS).contains(&digits.len());
+    let is_valid_charset = digits.chars().all(|c| c.is_ascii_digit());
+
+    match is_valid_length && is_valid_charset {
+        true => Ok(()),
+        false => Err(AppError::BadRequest),
+    }
+}
+
+// 連絡先を閲覧できるのは配車を行うディスパッチャーと管理者のみ
+fn visible_contact_phone(contact_phone: Option<String>, viewer_role: Role) -> Option<String> {
+    match viewer_role {
+        Role::Dispatcher | Role::Admin => contact_phone,
+        _ => contact_phone.map(|contact_phone| {
+            let hidden = contact_phone
+                .len()
+                .saturating_sub(VISIBLE_CONTACT_PHONE_DIGITS);
+            contact_phone
+                .chars()
+                .enumerate()
+                .map(|(i, c)| if i < hidden && c != '+' { '*' } else { c })
+                .collect()
+        }),
+    }
+}
+
 pub trait OrderRepository {
     async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError>;
-    async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError>;
+    async fn update_order_status(&self, order_id: i32, status: OrderStatus)
+        -> Result<(), AppError>;
     async fn get_paginated_orders(
         &self,
         page: i32,
         page_size: i32,
         sort_by: Option<String>,
         sort_order: Option<String>,
-        status: Option<String>,
-        area: Option<i32>,
+        filter: OrderFilter,
     ) -> Result<Vec<Order>, AppError>;
-    async fn create_order(
+    async fn create_order_tx(
         &self,
         customer_id: i32,
         node_id: i32,
+        destination_node_id: Option<i32>,
         car_value: f64,
-    ) -> Result<(), AppError>;
-    async fn update_order_dispatched(
+        contact_phone: Option<&str>,
+        trucks_required: i32,
+    ) -> Result<i32, AppError>;
+    async fn pending_in_area(&self, area: AreaId) -> Result<Vec<Order>, AppError>;
+    async fn recent_orders(
+        &self,
+        area: AreaId,
+        window: chrono::Duration,
+    ) -> Result<Vec<Order>, AppError>;
+    async fn latest_order_per_client(
+        &self,
+        client_ids: &[i32],
+    ) -> Result<HashMap<i32, Order>, AppError>;
+    async fn pending_at_nodes(&self, node_ids: &[i32]) -> Result<Vec<Order>, AppError>;
+    async fn pending_in_area_after(
+        &self,
+        area: AreaId,
+        after: Option<(DateTime<Utc>, i32)>,
+        limit: i32,
+    ) -> Result<Vec<Order>, AppError>;
+    async fn order_events_before(
+        &self,
+        area: AreaId,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<OrderEvent>, AppError>;
+    async fn overdue_dispatched(
+        &self,
+        area: AreaId,
+        max_age: chrono::Duration,
+    ) -> Result<Vec<Order>, AppError>;
+    async fn dispatch_order_with_tow_trucks(
         &self,
-        id: i32,
+        order_id: i32,
         dispatcher_id: i32,
-        tow_truck_id: i32,
+        tow_truck_ids: &[i32],
+        order_time: DateTime<Utc>,
+        eta_seconds: Option<i32>,
     ) -> Result<(), AppError>;
-    async fn create_completed_order(
+    async fn dispatch_order(
         &self,
         order_id: i32,
+        dispatcher_id: i32,
         tow_truck_id: i32,
-        completed_time: DateTime<Utc>,
+        order_time: DateTime<Utc>,
+        eta_seconds: Option<i32>,
     ) -> Result<(), AppError>;
+    async fn bulk_complete_orders(
+        &self,
+        order_ids: &[i32],
+        completed_time: DateTime<Utc>,
+    ) -> Result<Vec<(i32, CompletionOutcome)>, AppError>;
+    async fn completion_durations(
+        &self,
+        area: AreaId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<i64>, AppError>;
+    async fn area_stats(&self) -> Result<Vec<AreaStats>, AppError>;
+    async fn reopen_order(&self, order_id: i32) -> Result<(), AppError>;
+    async fn relocate_order(&self, order_id: i32, node_id: i32) -> Result<(), AppError>;
+    async fn cancel_order(&self, order_id: i32, reason: CancelReason) -> Result<(), AppError>;
+    async fn cancel_duplicate_pending_orders(
+        &self,
+        client_id: i32,
+        window: chrono::Duration,
+    ) -> Result<Vec<(i32, Vec<i32>)>, AppError>;
+    async fn cancel_area_pending(
+        &self,
+        area: AreaId,
+        reason: CancelReason,
+    ) -> Result<Vec<i32>, AppError>;
+    async fn dispatch_counts_by_dispatcher(
+        &self,
+        area: AreaId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<HashMap<i32, i64>, AppError>;
+    async fn get_paginated_completed_orders(
+        &self,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<CompletedOrderDetail>, AppError>;
+    // (tow_truck_id, order_id) の組を返す
+    async fn find_active_order_ids_by_tow_truck_ids(
+        &self,
+        tow_truck_ids: &[i32],
+    ) -> Result<Vec<(i32, i32)>, AppError>;
 }
 
 #[derive(Debug)]
@@ -51,6 +234,13 @@ pub struct OrderService<
     tow_truck_repository: U,
     auth_repository: V,
     map_repository: W,
+    graph_cache: Arc<GraphCache>,
+    router: Arc<dyn Router>,
+    dispatch_limiter: DispatchLimiter,
+    client_order_limiter: ClientOrderLimiter,
+    event_bus: Arc<EventBus>,
+    shutdown: Arc<ShutdownCoordinator>,
+    tow_truck_speed: f64,
 }
 
 impl<
@@ -60,81 +250,105 @@ impl<
         W: MapRepository + std::fmt::Debug,
     > OrderService<T, U, V, W>
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         order_repository: T,
         tow_truck_repository: U,
         auth_repository: V,
         map_repository: W,
+        graph_cache: Arc<GraphCache>,
+        router: Arc<dyn Router>,
+        dispatch_limiter: DispatchLimiter,
+        client_order_limiter: ClientOrderLimiter,
+        event_bus: Arc<EventBus>,
+        shutdown: Arc<ShutdownCoordinator>,
+        tow_truck_speed: f64,
     ) -> Self {
         OrderService {
             order_repository,
             tow_truck_repository,
             auth_repository,
             map_repository,
+            graph_cache,
+            router,
+            dispatch_limiter,
+            client_order_limiter,
+            event_bus,
+            shutdown,
+            tow_truck_speed,
         }
     }
 
     pub async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError> {
+        let status = status.parse::<OrderStatus>()?;
         self.order_repository
             .update_order_status(order_id, status)
-            .await
+            .await?;
+
+        let event = match status {
+            OrderStatus::Dispatched => {
+                let order = self.order_repository.find_order_by_id(order_id).await?;
+                DomainEvent::OrderDispatched {
+                    order_id,
+                    tow_truck_ids: order.tow_truck_id.into_iter().collect(),
+                }
+            }
+            OrderStatus::Completed => DomainEvent::OrderCompleted { order_id },
+            OrderStatus::Canceled => DomainEvent::OrderCanceled { order_id },
+            // pending への遷移は許可していない
+            OrderStatus::Pending => return Ok(()),
+        };
+        self.event_bus.publish(event);
+
+        Ok(())
     }
 
-    pub async fn get_order_by_id(&self, id: i32) -> Result<OrderDto, AppError> {
+    pub async fn get_order_by_id(&self, id: i32, viewer_role: Role) -> Result<OrderDto, AppError> {
         let order = self.order_repository.find_order_by_id(id).await?;
 
+        // ユーザーが見つからない場合はユーザー名を空のまま返す
         let client_username = self
             .auth_repository
             .find_user_by_id(order.client_id)
-            .await
-            .unwrap()
-            .unwrap()
-            .username;
+            .await?
+            .map(|user| user.username);
 
         let dispatcher = match order.dispatcher_id {
-            Some(dispatcher_id) => self
-                .auth_repository
-                .find_dispatcher_by_id(dispatcher_id)
-                .await
-                .unwrap(),
+            Some(dispatcher_id) => {
+                self.auth_repository
+                    .find_dispatcher_by_id(dispatcher_id)
+                    .await?
+            }
             None => None,
         };
 
         let (dispatcher_user_id, dispatcher_username) = match dispatcher {
             Some(dispatcher) => (
                 Some(dispatcher.user_id),
-                Some(
-                    self.auth_repository
-                        .find_user_by_id(dispatcher.user_id)
-                        .await
-                        .unwrap()
-                        .unwrap()
-                        .username,
-                ),
+                self.auth_repository
+                    .find_user_by_id(dispatcher.user_id)
+                    .await?
+                    .map(|user| user.username),
             ),
             None => (None, None),
         };
 
         let tow_truck = match order.tow_truck_id {
-            Some(tow_truck_id) => self
-                .tow_truck_repository
-                .find_tow_truck_by_id(tow_truck_id)
-                .await
-                .unwrap(),
+            Some(tow_truck_id) => {
+                self.tow_truck_repository
+                    .find_tow_truck_by_id(tow_truck_id)
+                    .await?
+            }
             None => None,
         };
 
         let (driver_user_id, driver_username) = match tow_truck {
             Some(tow_truck) => (
                 Some(tow_truck.driver_id),
-                Some(
-                    self.auth_repository
-                        .find_user_by_id(tow_truck.driver_id)
-                        .await
-                        .unwrap()
-                        .unwrap()
-                        .username,
-                ),
+                self.auth_repository
+                    .find_user_by_id(tow_truck.driver_id)
+                    .await?
+                    .map(|user| user.username),
             ),
             None => (None, None),
         };
@@ -144,7 +358,7 @@ impl<
         Ok(OrderDto {
             id: order.id,
             client_id: order.client_id,
-            client_username: Some(client_username),
+            client_username,
             dispatcher_user_id,
             dispatcher_username,
             driver_user_id,
@@ -152,9 +366,22 @@ impl<
             area_id,
             dispatcher_id: order.dispatcher_id,
             tow_truck_id: order.tow_truck_id,
-            status: order.status,
+            status: order
+                .status
+                .parse::<OrderStatus>()
+                .map_err(|_| AppError::InternalServerError)?,
             node_id: order.node_id,
+            destination_node_id: order.destination_node_id,
             car_value: order.car_value,
+            contact_phone: visible_contact_phone(order.contact_phone, viewer_role),
+            trucks_required: order.trucks_required,
+            cancel_reason: order
+                .cancel_reason
+                .as_deref()
+                .map(CancelReason::from_str)
+                .transpose()
+                .map_err(|_| AppError::InternalServerError)?,
+            eta_seconds: order.eta_seconds,
             order_time: order.order_time,
             completed_time: order.completed_time,
         })
@@ -166,15 +393,59 @@ impl<
         page_size: i32,
         sort_by: Option<String>,
         sort_order: Option<String>,
-        status: Option<String>,
-        area: Option<i32>,
+        filter: OrderFilter,
+        viewer_role: Role,
     ) -> Result<Vec<OrderDto>, AppError> {
         let orders = self
             .order_repository
-            .get_paginated_orders(page, page_size, sort_by, sort_order, status, area)
+            .get_paginated_orders(page, page_size, sort_by, sort_order, filter)
+            .await?;
+
+        self.enrich_orders(orders, viewer_role).await
+    }
+
+    // ダッシュボード用に、エリアの直近 window の注文を状態を問わず新しい順に返す
+    pub async fn get_recent_orders(
+        &self,
+        area: AreaId,
+        window: chrono::Duration,
+        viewer_role: Role,
+    ) -> Result<Vec<OrderDto>, AppError> {
+        if window < chrono::Duration::zero() {
+            return Err(AppError::BadRequest);
+        }
+        let orders = self.order_repository.recent_orders(area, window).await?;
+
+        self.enrich_orders(orders, viewer_role).await
+    }
+
+    // 「最新の依頼」表示用に、クライアントごとに最も新しい注文を返す
+    // 注文が1件もないクライアントはキーに含めない
+    pub async fn get_latest_orders_by_client(
+        &self,
+        client_ids: &[i32],
+        viewer_role: Role,
+    ) -> Result<HashMap<i32, OrderDto>, AppError> {
+        let latest = self
+            .order_repository
+            .latest_order_per_client(client_ids)
             .await?;
+        let orders: Vec<Order> = latest.into_values().collect();
+        let order_dtos = self.enrich_orders(orders, viewer_role).await?;
+
+        Ok(order_dtos
+            .into_iter()
+            .map(|order| (order.client_id, order))
+            .collect())
+    }
+
+    // 注文一覧にクライアント・ディスパッチャー・ドライバーのユーザー名を付けて DTO にする
+    async fn enrich_orders(
+        &self,
+        orders: Vec<Order>,
+        viewer_role: Role,
+    ) -> Result<Vec<OrderDto>, AppError> {
         // すべてのIDを収集
-        let client_ids: Vec<i32> = orders.iter().map(|order| order.client_id).collect();
         let dispatcher_ids: Vec<i32> = orders
             .iter()
             .filter_map(|order| order.dispatcher_id)
@@ -183,22 +454,33 @@ impl<
             .iter()
             .filter_map(|order| order.tow_truck_id)
             .collect();
-        // バルクでクライアント、ディスパッチャー、トウトラックを取得
-        let clients = self.auth_repository.find_users_by_ids(&client_ids).await?;
-        let dispatchers = self
-            .auth_repository
-            .find_dispatchers_by_ids(&dispatcher_ids)
-            .await?;
-        let tow_trucks = self
-            .tow_truck_repository
-            .find_tow_truck_by_ids(&tow_truck_ids)
-            .await?;
+        // バルクでディスパッチャー、トウトラックを取得
+        // ページサイズが大きくても IN 句が一定の大きさに収まるよう、ENRICH_CHUNK_SIZE 件ずつに分けて問い合わせる
+        // 未割り当ての注文しかない場合は ID が空になり、問い合わせ自体を行わない
+        let dispatchers = fetch_in_chunks(&dispatcher_ids, ENRICH_CHUNK_SIZE, |ids| async move {
+            self.auth_repository.find_dispatchers_by_ids(&ids).await
+        })
+        .await?;
+        let tow_trucks = fetch_in_chunks(&tow_truck_ids, ENRICH_CHUNK_SIZE, |ids| async move {
+            self.tow_truck_repository.find_tow_truck_by_ids(&ids).await
+        })
+        .await?;
+        // クライアントとディスパッチャーのユーザーはまとめて取得する
+        // ドライバーのユーザー名はトラックと一緒に取得済み
+        let user_ids: Vec<i32> = orders
+            .iter()
+            .map(|order| order.client_id)
+            .chain(dispatchers.iter().map(|dispatcher| dispatcher.user_id))
+            .collect();
+        let users = fetch_in_chunks(&user_ids, ENRICH_CHUNK_SIZE, |ids| async move {
+            self.auth_repository.find_users_by_ids(&ids).await
+        })
+        .await?;
         // IDをキーにしたHashMapを作成
-        let client_map: HashMap<i32, User> =
-            clients.into_iter().map(|user| (user.id, user)).collect();
+        let user_map: HashMap<i32, User> = users.into_iter().map(|user| (user.id, user)).collect();
         let dispatcher_map: HashMap<i32, Dispatcher> = dispatchers
             .into_iter()
-            .map(|dispatcher| (dispatcher.user_id, dispatcher))
+            .map(|dispatcher| (dispatcher.id, dispatcher))
             .collect();
         let tow_truck_map: HashMap<i32, TowTruck> = tow_trucks
             .into_iter()
@@ -207,14 +489,14 @@ impl<
         let mut results = Vec::new();
         for order in orders {
             // クライアント情報を取得
-            let client_username = client_map
+            let client_username = user_map
                 .get(&order.client_id)
                 .map(|client| client.username.clone());
             // ディスパッチャー情報を取得
             let (dispatcher_user_id, dispatcher_username) = match order.dispatcher_id {
                 Some(dispatcher_id) => {
                     if let Some(dispatcher) = dispatcher_map.get(&dispatcher_id) {
-                        let dispatcher_user = client_map.get(&dispatcher.user_id);
+                        let dispatcher_user = user_map.get(&dispatcher.user_id);
                         (
                             Some(dispatcher.user_id),
                             dispatcher_user.map(|user| user.username.clone()),
@@ -229,11 +511,7 @@ impl<
             let (driver_user_id, driver_username) = match order.tow_truck_id {
                 Some(tow_truck_id) => {
                     if let Some(tow_truck) = tow_truck_map.get(&tow_truck_id) {
-                        let driver_user = client_map.get(&tow_truck.driver_id);
-                        (
-                            Some(tow_truck.driver_id),
-                            driver_user.map(|user| user.username.clone()),
-                        )
+                        (Some(tow_truck.driver_id), tow_truck.driver_username.clone())
                     } else {
                         (None, None)
                     }
@@ -251,9 +529,22 @@ impl<
                 driver_user_id,
                 driver_username,
                 area_id: order.area_id,
-                status: order.status,
+                status: order
+                    .status
+                    .parse::<OrderStatus>()
+                    .map_err(|_| AppError::InternalServerError)?,
                 node_id: order.node_id,
+                destination_node_id: order.destination_node_id,
                 car_value: order.car_value,
+                contact_phone: visible_contact_phone(order.contact_phone, viewer_role),
+                trucks_required: order.trucks_required,
+                cancel_reason: order
+                    .cancel_reason
+                    .as_deref()
+                    .map(CancelReason::from_str)
+                    .transpose()
+                    .map_err(|_| AppError::InternalServerError)?,
+                eta_seconds: order.eta_seconds,
                 order_time: order.order_time,
                 completed_time: order.completed_time,
             });
@@ -265,14 +556,33 @@ impl<
         &self,
         client_id: i32,
         node_id: i32,
+        destination_node_id: Option<i32>,
         car_value: f64,
-    ) -> Result<(), AppError> {
+        contact_phone: Option<&str>,
+        trucks_required: i32,
+    ) -> Result<CreatedOrderDto, AppError> {
+        if let Some(contact_phone) = contact_phone {
+            validate_contact_phone(contact_phone)?;
+        }
+        if trucks_required < 1 {
+            return Err(AppError::BadRequest);
+        }
+        // 入力が不正な注文は数えず、受け付ける直前に上限を確認する
+        self.client_order_limiter.try_acquire(client_id)?;
+
         match self
             .order_repository
-            .create_order(client_id, node_id, car_value)
+            .create_order_tx(
+                client_id,
+                node_id,
+                destination_node_id,
+                car_value,
+                contact_phone,
+                trucks_required,
+            )
             .await
         {
-            Ok(_) => Ok(()),
+            Ok(id) => Ok(CreatedOrderDto { id }),
             Err(_) => Err(AppError::BadRequest),
         }
     }
@@ -284,23 +594,2311 @@ impl<
         tow_truck_id: i32,
         order_time: DateTime<Utc>,
     ) -> Result<(), AppError> {
-        if (self
+        // 停止処理中は新しい配車を受け付けない
+        let _in_flight = self.shutdown.begin()?;
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let _permit = self
+            .dispatch_limiter
+            .acquire(AreaId::from(order.area_id))
+            .await;
+
+        let graph = self.load_area_graph(AreaId::from(order.area_id)).await?;
+        let eta_seconds = self.eta_seconds(&order, &graph, tow_truck_id).await?;
+        self.order_repository
+            .dispatch_order(
+                order_id,
+                dispatcher_id,
+                tow_truck_id,
+                order_time,
+                eta_seconds,
+            )
+            .await?;
+        self.event_bus.publish(DomainEvent::OrderDispatched {
+            order_id,
+            tow_truck_ids: vec![tow_truck_id],
+        });
+
+        Ok(())
+    }
+
+    pub async fn bulk_complete(
+        &self,
+        order_ids: &[i32],
+        completed_time: DateTime<Utc>,
+    ) -> Result<BatchResult<CompletedOrderDto>, AppError> {
+        let outcomes = self
             .order_repository
-            .create_completed_order(order_id, tow_truck_id, order_time)
-            .await)
-            .is_err()
-        {
+            .bulk_complete_orders(order_ids, completed_time)
+            .await?;
+        for (order_id, outcome) in &outcomes {
+            if let CompletionOutcome::Completed(..) = outcome {
+                self.event_bus.publish(DomainEvent::OrderCompleted {
+                    order_id: *order_id,
+                });
+            }
+        }
+
+        let mut results = BatchResult::new();
+        for (order_id, outcome) in outcomes {
+            match outcome {
+                CompletionOutcome::Completed(completed_order, order) => results.push_ok(
+                    order_id,
+                    CompletedOrderDto {
+                        id: completed_order.id,
+                        order_id: completed_order.order_id,
+                        tow_truck_id: completed_order.tow_truck_id,
+                        order_time: Some(order.order_time),
+                        completed_time: completed_order.completed_time,
+                        car_value: order.car_value,
+                    },
+                ),
+                CompletionOutcome::AlreadyCompleted => {
+                    results.push_err(order_id, "already_completed")
+                }
+                CompletionOutcome::NotDispatched => results.push_err(order_id, "not_dispatched"),
+                CompletionOutcome::AreaMismatch => results.push_err(order_id, "area_mismatch"),
+                CompletionOutcome::NotFound => results.push_err(order_id, "not_found"),
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub async fn get_completion_sla_report(
+        &self,
+        area: AreaId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<CompletionSlaReportDto, AppError> {
+        if from >= to {
             return Err(AppError::BadRequest);
         }
 
+        let durations = self
+            .order_repository
+            .completion_durations(area, from, to)
+            .await?;
+
+        Ok(completion_sla_report(durations))
+    }
+
+    // 一覧画面向けに、全エリアの集計値をまとめて返す
+    pub async fn get_area_stats(&self) -> Result<Vec<AreaStatsDto>, AppError> {
+        let stats = self.order_repository.area_stats().await?;
+        Ok(stats
+            .into_iter()
+            .map(|s| AreaStatsDto {
+                area_id: s.area_id.into(),
+                pending_order_count: s.pending_order_count,
+                available_tow_truck_count: s.available_tow_truck_count,
+                average_completion_seconds: s.average_completion_seconds,
+            })
+            .collect())
+    }
+
+    // セッションからリクエストしたユーザーのロールを取得する
+    pub async fn viewer_role(&self, session_token: &str) -> Result<Role, AppError> {
+        session_role(&self.auth_repository, session_token).await
+    }
+
+    // セッションの持ち主が管理者であることを確認する
+    async fn ensure_admin(&self, session_token: &str) -> Result<(), AppError> {
+        match self.viewer_role(session_token).await? {
+            Role::Admin => Ok(()),
+            _ => Err(AppError::Forbidden),
+        }
+    }
+
+    // 配車や注文の整理など、ディスパッチャーと管理者だけに許す操作の前に確認する
+    pub async fn ensure_dispatcher(&self, session_token: &str) -> Result<Role, AppError> {
+        match self.viewer_role(session_token).await? {
+            role @ (Role::Dispatcher | Role::Admin) => Ok(role),
+            _ => Err(AppError::Forbidden),
+        }
+    }
+
+    // 誤って完了にした注文を配車済みに戻す (管理者のみ)
+    pub async fn reopen_order(&self, order_id: i32, session_token: &str) -> Result<(), AppError> {
+        self.ensure_admin(session_token).await?;
+        self.order_repository.reopen_order(order_id).await
+    }
+
+    // pending の注文を理由付きでキャンセルする (理由は CancelReason のいずれか)
+    pub async fn cancel_order(&self, order_id: i32, reason: &str) -> Result<(), AppError> {
+        let reason = reason.parse::<CancelReason>()?;
+        self.order_repository.cancel_order(order_id, reason).await?;
+        self.event_bus
+            .publish(DomainEvent::OrderCanceled { order_id });
+
+        Ok(())
+    }
+
+    // エリア全体の障害時に、エリア内の pending の注文をすべて理由付きでキャンセルする (管理者のみ)
+    // キャンセルした件数を返す
+    pub async fn cancel_area_pending(
+        &self,
+        area: AreaId,
+        reason: &str,
+        session_token: &str,
+    ) -> Result<CanceledOrderCountDto, AppError> {
+        self.ensure_admin(session_token).await?;
+        let reason = reason.parse::<CancelReason>()?;
+
+        let canceled_order_ids = self
+            .order_repository
+            .cancel_area_pending(area, reason)
+            .await?;
+        for &order_id in &canceled_order_ids {
+            self.event_bus
+                .publish(DomainEvent::OrderCanceled { order_id });
+        }
+
+        Ok(CanceledOrderCountDto {
+            area_id: area.into(),
+            canceled_count: canceled_order_ids.len(),
+        })
+    }
+
+    // 依頼者が誤った地点を伝えた場合に、未配車の注文の引き取り地点を付け替える
+    // エリアは新しいノードから求め直す
+    pub async fn relocate_order(&self, order_id: i32, node_id: i32) -> Result<(), AppError> {
         self.order_repository
-            .update_order_dispatched(order_id, dispatcher_id, tow_truck_id)
+            .relocate_order(order_id, node_id)
+            .await
+    }
+
+    // 注文を担当したディスパッチャーをユーザー名・エリア付きで返す
+    pub async fn dispatcher_for_order(
+        &self,
+        order_id: i32,
+    ) -> Result<Option<DispatcherDto>, AppError> {
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let dispatcher_id = match order.dispatcher_id {
+            Some(dispatcher_id) => dispatcher_id,
+            None => return Ok(None),
+        };
+
+        let dispatcher = self
+            .auth_repository
+            .find_dispatcher_profile_by_id(dispatcher_id)
+            .await?;
+
+        Ok(dispatcher.map(|dispatcher| DispatcherDto {
+            id: dispatcher.id,
+            user_id: dispatcher.user_id,
+            username: dispatcher.username,
+            area_id: dispatcher.area_id,
+        }))
+    }
+
+    // 完了済みの注文を完了時刻の新しい順に返す
+    // クライアントとドライバーのユーザー名は、ページ内の全件分をまとめて取得して埋める
+    pub async fn get_completed_orders(
+        &self,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<CompletedOrderDetailDto>, AppError> {
+        let completed_orders = self
+            .order_repository
+            .get_paginated_completed_orders(page, page_size)
             .await?;
 
-        self.tow_truck_repository
-            .update_status(tow_truck_id, "busy")
+        let tow_truck_ids: Vec<i32> = completed_orders
+            .iter()
+            .map(|completed_order| completed_order.tow_truck_id)
+            .collect();
+        // 注文一覧と同じく、ページサイズが大きくても ENRICH_CHUNK_SIZE 件ずつに分けて問い合わせる
+        let tow_trucks = fetch_in_chunks(&tow_truck_ids, ENRICH_CHUNK_SIZE, |ids| async move {
+            self.tow_truck_repository.find_tow_truck_by_ids(&ids).await
+        })
+        .await?;
+        let driver_id_map: HashMap<i32, i32> = tow_trucks
+            .into_iter()
+            .map(|tow_truck| (tow_truck.id, tow_truck.driver_id))
+            .collect();
+
+        // クライアントとドライバーはまとめて取得する (ENRICH_CHUNK_SIZE 件までなら1回のクエリ)
+        let user_ids: Vec<i32> = completed_orders
+            .iter()
+            .map(|completed_order| completed_order.client_id)
+            .chain(driver_id_map.values().cloned())
+            .collect();
+        let username_map: HashMap<i32, String> =
+            fetch_in_chunks(&user_ids, ENRICH_CHUNK_SIZE, |ids| async move {
+                self.auth_repository.find_users_by_ids(&ids).await
+            })
+            .await?
+            .into_iter()
+            .map(|user| (user.id, user.username))
+            .collect();
+
+        Ok(completed_orders
+            .into_iter()
+            .map(|completed_order| {
+                let driver_user_id = driver_id_map.get(&completed_order.tow_truck_id).cloned();
+                CompletedOrderDetailDto {
+                    id: completed_order.id,
+                    order_id: completed_order.order_id,
+                    tow_truck_id: completed_order.tow_truck_id,
+                    client_id: completed_order.client_id,
+                    client_username: username_map.get(&completed_order.client_id).cloned(),
+                    driver_user_id,
+                    driver_username: driver_user_id
+                        .and_then(|driver_user_id| username_map.get(&driver_user_id).cloned()),
+                    car_value: completed_order.car_value,
+                    order_time: completed_order.order_time,
+                    completed_time: completed_order.completed_time,
+                }
+            })
+            .collect())
+    }
+
+    // ディスパッチャー間の配車件数の偏りを集計する
+    // 期間内に1件も配車していないディスパッチャーは集計に含めない
+    pub async fn get_dispatch_fairness(
+        &self,
+        area: AreaId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<DispatchFairnessDto, AppError> {
+        if from >= to {
+            return Err(AppError::BadRequest);
+        }
+
+        let counts = self
+            .order_repository
+            .dispatch_counts_by_dispatcher(area, from, to)
             .await?;
+        let imbalance = match (counts.values().max(), counts.values().min()) {
+            (Some(&max), Some(&min)) if min > 0 => Some(max as f64 / min as f64),
+            _ => None,
+        };
 
-        Ok(())
+        Ok(DispatchFairnessDto { counts, imbalance })
+    }
+
+    // 二重送信された pending の注文を、地点ごとに最も早いものへまとめる
+    pub async fn dedupe_pending(
+        &self,
+        client_id: i32,
+        window: chrono::Duration,
+    ) -> Result<Vec<DedupedOrderDto>, AppError> {
+        if window < chrono::Duration::zero() {
+            return Err(AppError::BadRequest);
+        }
+
+        let groups = self
+            .order_repository
+            .cancel_duplicate_pending_orders(client_id, window)
+            .await?;
+        for (_, canceled_order_ids) in &groups {
+            for &order_id in canceled_order_ids {
+                self.event_bus
+                    .publish(DomainEvent::OrderCanceled { order_id });
+            }
+        }
+
+        Ok(groups
+            .into_iter()
+            .map(|(kept_order_id, canceled_order_ids)| DedupedOrderDto {
+                kept_order_id,
+                canceled_order_ids,
+            })
+            .collect())
+    }
+
+    async fn load_area_graph(&self, area_id: AreaId) -> Result<Arc<Graph>, AppError> {
+        self.graph_cache.load(&self.map_repository, area_id).await
+    }
+
+    async fn load_combined_graph(&self, area_ids: &[AreaId]) -> Result<Arc<Graph>, AppError> {
+        let graph = self
+            .graph_cache
+            .load_combined(&self.map_repository, area_ids)
+            .await?;
+
+        Ok(Arc::new(graph))
+    }
+
+    // 拠点などのノードから経路の距離が radius 以内にある未配車の注文を、近い順 (同じ距離なら注文IDが小さい順) に返す
+    pub async fn get_pending_orders_within(
+        &self,
+        node_id: i32,
+        radius: i32,
+    ) -> Result<Vec<NearbyOrderDto>, AppError> {
+        if radius < 0 {
+            return Err(AppError::BadRequest);
+        }
+        let area_id = match self.map_repository.get_area_id_by_node_id(node_id).await {
+            Ok(area_id) => area_id,
+            Err(sqlx::Error::RowNotFound) => return Err(AppError::NotFound),
+            Err(e) => return Err(e.into()),
+        };
+
+        let graph = self.load_area_graph(area_id).await?;
+        let distances = graph.nodes_within(node_id, radius);
+        let node_ids: Vec<i32> = distances.keys().cloned().collect();
+        let orders = self.order_repository.pending_at_nodes(&node_ids).await?;
+
+        let mut nearby_orders: Vec<NearbyOrderDto> = orders
+            .into_iter()
+            .filter_map(|order| {
+                distances
+                    .get(&order.node_id)
+                    .map(|&distance| NearbyOrderDto {
+                        id: order.id,
+                        node_id: order.node_id,
+                        distance,
+                        car_value: order.car_value,
+                        order_time: order.order_time,
+                    })
+            })
+            .collect();
+        nearby_orders.sort_by_key(|order| (order.distance, order.id));
+
+        Ok(nearby_orders)
+    }
+
+    // トラックの現在地から注文地点までの距離をもとに到着予定時間(秒)を求める
+    // トラックから注文地点に到達できない場合は None
+    async fn eta_seconds(
+        &self,
+        order: &Order,
+        graph: &Graph,
+        tow_truck_id: i32,
+    ) -> Result<Option<i32>, AppError> {
+        let tow_truck = match self
+            .tow_truck_repository
+            .find_tow_truck_by_id(tow_truck_id)
+            .await?
+        {
+            Some(tow_truck) => tow_truck,
+            None => return Err(AppError::NotFound),
+        };
+
+        Ok(graph
+            .distance_to(tow_truck.node_id, order.node_id)
+            .map(|distance| eta_seconds_for_distance(distance, self.tow_truck_speed)))
+    }
+
+    // 注文地点に到達できる空きトラックのIDを近い順 (同じ距離ならトラックIDが小さい順) に返す
+    // graph には注文のエリアのグラフを渡す
+    async fn nearest_available_tow_truck_ids(
+        &self,
+        order: &Order,
+        graph: &Graph,
+    ) -> Result<Vec<i32>, AppError> {
+        let area_id = AreaId::from(order.area_id);
+        let tow_trucks = self.tow_truck_repository.available_in_area(area_id).await?;
+
+        rank_tow_truck_ids_by_distance(self.router.as_ref(), order, graph, tow_trucks)
+    }
+
+    // 注文のエリアに配車できるトラックがいない場合に、隣接するエリアの空きトラックを近い順に返す
+    // 注文のエリアと隣接エリアをまとめたグラフで距離を求め、そのグラフも併せて返す
+    async fn nearest_adjacent_tow_truck_ids(
+        &self,
+        order: &Order,
+    ) -> Result<(Arc<Graph>, Vec<i32>), AppError> {
+        let area_id = AreaId::from(order.area_id);
+        let adjacent_area_ids = self.map_repository.get_adjacent_area_ids(area_id).await?;
+
+        let mut tow_trucks = Vec::new();
+        for &adjacent_area_id in &adjacent_area_ids {
+            tow_trucks.extend(
+                self.tow_truck_repository
+                    .available_in_area(adjacent_area_id)
+                    .await?,
+            );
+        }
+        let mut area_ids = vec![area_id];
+        area_ids.extend(adjacent_area_ids);
+        let graph = self.load_combined_graph(&area_ids).await?;
+        let tow_truck_ids =
+            rank_tow_truck_ids_by_distance(self.router.as_ref(), order, &graph, tow_trucks)?;
+
+        Ok((graph, tow_truck_ids))
+    }
+
+    // 注文に必要な台数 (trucks_required) だけ、近い順に空きトラックを確保して配車する
+    // 到達できる空きトラックが足りない場合は1台も確保せずに Conflict を返す
+    pub async fn dispatch_required_tow_trucks(
+        &self,
+        order_id: i32,
+        dispatcher_id: i32,
+    ) -> Result<Vec<i32>, AppError> {
+        let _in_flight = self.shutdown.begin()?;
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let _permit = self
+            .dispatch_limiter
+            .acquire(AreaId::from(order.area_id))
+            .await;
+        let trucks_required = order.trucks_required.max(1) as usize;
+
+        let graph = self.load_area_graph(AreaId::from(order.area_id)).await?;
+        let mut tow_truck_ids = self.nearest_available_tow_truck_ids(&order, &graph).await?;
+        if tow_truck_ids.len() < trucks_required {
+            return Err(AppError::Conflict);
+        }
+        tow_truck_ids.truncate(trucks_required);
+
+        // 全台がそろうまでの時間として、最も遠いトラック (近い順に並べた末尾) の到着予定時間を記録する
+        let eta_seconds = match tow_truck_ids.last() {
+            Some(&farthest_tow_truck_id) => {
+                self.eta_seconds(&order, &graph, farthest_tow_truck_id)
+                    .await?
+            }
+            None => None,
+        };
+        self.order_repository
+            .dispatch_order_with_tow_trucks(
+                order_id,
+                dispatcher_id,
+                &tow_truck_ids,
+                Utc::now(),
+                eta_seconds,
+            )
+            .await?;
+        self.event_bus.publish(DomainEvent::OrderDispatched {
+            order_id,
+            tow_truck_ids: tow_truck_ids.clone(),
+        });
+
+        Ok(tow_truck_ids)
+    }
+
+    // 注文地点から最も近い空きトラックに配車し、配車後の注文・トラックと、トラックから注文地点までの経路をまとめて返す
+    // allow_adjacent_areas が true の場合、注文のエリアに配車できるトラックがいなければ隣接するエリアから探す
+    pub async fn dispatch_and_describe(
+        &self,
+        order_id: i32,
+        dispatcher_id: i32,
+        allow_adjacent_areas: bool,
+        viewer_role: Role,
+    ) -> Result<DispatchResultDto, AppError> {
+        let _in_flight = self.shutdown.begin()?;
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let area_id = AreaId::from(order.area_id);
+        let permit = self.dispatch_limiter.acquire(area_id).await;
+
+        let mut graph = self.load_area_graph(area_id).await?;
+        let mut tow_truck_ids = self.nearest_available_tow_truck_ids(&order, &graph).await?;
+        if tow_truck_ids.is_empty() && allow_adjacent_areas {
+            log::info!(
+                "注文 {} のエリア {} に配車できるトラックがいないため、隣接エリアから探します",
+                order_id,
+                area_id.0
+            );
+            (graph, tow_truck_ids) = self.nearest_adjacent_tow_truck_ids(&order).await?;
+        }
+        let tow_truck_id = match tow_truck_ids.first() {
+            Some(&tow_truck_id) => tow_truck_id,
+            None => return Err(AppError::NotFound),
+        };
+        let eta_seconds = self.eta_seconds(&order, &graph, tow_truck_id).await?;
+        self.order_repository
+            .dispatch_order(
+                order_id,
+                dispatcher_id,
+                tow_truck_id,
+                Utc::now(),
+                eta_seconds,
+            )
+            .await?;
+        drop(permit);
+        self.event_bus.publish(DomainEvent::OrderDispatched {
+            order_id,
+            tow_truck_ids: vec![tow_truck_id],
+        });
+
+        let tow_truck = match self
+            .tow_truck_repository
+            .find_tow_truck_by_id(tow_truck_id)
+            .await?
+        {
+            Some(tow_truck) => tow_truck,
+            None => return Err(AppError::NotFound),
+        };
+        // 到達できるトラックだけを候補にしているため、経路は必ず見つかる
+        let route = graph
+            .shortest_path_nodes(tow_truck.node_id, order.node_id)
+            .ok_or(AppError::InternalServerError)?;
+        let distance = graph
+            .path_edges(&route)
+            .map(|edges| edges.iter().map(|edge| edge.weight).sum())
+            .ok_or(AppError::InternalServerError)?;
+
+        Ok(DispatchResultDto {
+            order: self.get_order_by_id(order_id, viewer_role).await?,
+            truck: TowTruckDto::from_entity(tow_truck),
+            route,
+            distance,
+        })
+    }
+
+    // 指定されたトラックが空いていて注文と同じエリアにいればそれを使い、
+    // そうでなければ注文地点から最も近い空きトラック (同じ距離ならトラックIDが最小) に配車する
+    pub async fn create_dispatcher_order_preferred(
+        &self,
+        order_id: i32,
+        dispatcher_id: i32,
+        preferred_tow_truck_id: i32,
+    ) -> Result<PreferredDispatchResultDto, AppError> {
+        let _in_flight = self.shutdown.begin()?;
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let _permit = self
+            .dispatch_limiter
+            .acquire(AreaId::from(order.area_id))
+            .await;
+
+        let graph = self.load_area_graph(AreaId::from(order.area_id)).await?;
+        // 指定トラックが注文地点と往復できない場合も、使えないものとして最寄りのトラックに切り替える
+        let preferred = self
+            .tow_truck_repository
+            .find_tow_truck_by_id(preferred_tow_truck_id)
+            .await?
+            .filter(|tow_truck| {
+                tow_truck.status == "available"
+                    && tow_truck.area_id == order.area_id
+                    && graph.mutually_reachable(tow_truck.node_id, order.node_id)
+            });
+
+        let (tow_truck_id, used_preferred) = match preferred {
+            Some(tow_truck) => (tow_truck.id, true),
+            None => {
+                match self
+                    .nearest_available_tow_truck_ids(&order, &graph)
+                    .await?
+                    .first()
+                {
+                    Some(&tow_truck_id) => (tow_truck_id, false),
+                    None => return Err(AppError::NotFound),
+                }
+            }
+        };
+
+        // 枠は取得済みのため、create_dispatcher_order を経由せずに配車する
+        let eta_seconds = self.eta_seconds(&order, &graph, tow_truck_id).await?;
+        self.order_repository
+            .dispatch_order(
+                order_id,
+                dispatcher_id,
+                tow_truck_id,
+                Utc::now(),
+                eta_seconds,
+            )
+            .await?;
+        self.event_bus.publish(DomainEvent::OrderDispatched {
+            order_id,
+            tow_truck_ids: vec![tow_truck_id],
+        });
+
+        Ok(PreferredDispatchResultDto {
+            tow_truck_id,
+            used_preferred,
+        })
+    }
+
+    // ディスパッチャーの担当エリアの未配車の注文をまとめて配車する
+    // 最寄り順の貪欲法では遠い注文ほど後回しにされやすいため、待ち時間の最大値を抑えるよう
+    // 「残っている最寄りのトラックまでが最も遠い注文」から順に、その最寄りのトラックを割り当てる
+    pub async fn batch_dispatch_minmax(
+        &self,
+        dispatcher_id: i32,
+    ) -> Result<BatchResult<PendingAssignmentDto>, AppError> {
+        let _in_flight = self.shutdown.begin()?;
+        let dispatcher = match self
+            .auth_repository
+            .find_dispatcher_by_id(dispatcher_id)
+            .await?
+        {
+            Some(dispatcher) => dispatcher,
+            None => return Err(AppError::NotFound),
+        };
+        let area_id = AreaId::from(dispatcher.area_id);
+        let _permit = self.dispatch_limiter.acquire(area_id).await;
+
+        let orders = self.order_repository.pending_in_area(area_id).await?;
+        let tow_trucks = self.tow_truck_repository.available_in_area(area_id).await?;
+        let graph = self.load_area_graph(area_id).await?;
+
+        // 注文ごとに、到達できるトラックを (距離, トラックID) の昇順で並べる
+        let mut reachable_by_order: HashMap<i32, Vec<(i32, i32)>> = HashMap::new();
+        for order in orders.iter().filter(|o| graph.contains_node(o.node_id)) {
+            let distances = graph.dijkstra(order.node_id);
+            let mut reachable: Vec<(i32, i32)> = tow_trucks
+                .iter()
+                .filter_map(|tow_truck| {
+                    distances
+                        .get(&tow_truck.node_id)
+                        .map(|&distance| (distance, tow_truck.id))
+                })
+                .collect();
+            reachable.sort_unstable();
+            reachable_by_order.insert(order.id, reachable);
+        }
+
+        let order_ids: Vec<i32> = orders.iter().map(|order| order.id).collect();
+        let assignments = minmax_assignments(&order_ids, &reachable_by_order);
+
+        let mut results = BatchResult::new();
+        for order in &orders {
+            let (tow_truck_id, distance) = match assignments.get(&order.id) {
+                Some(&assignment) => assignment,
+                None if !graph.contains_node(order.node_id) => {
+                    results.push_err(order.id, "order_node_missing");
+                    continue;
+                }
+                None => {
+                    results.push_err(order.id, "no_reachable_tow_truck");
+                    continue;
+                }
+            };
+            match self
+                .order_repository
+                .dispatch_order(
+                    order.id,
+                    dispatcher_id,
+                    tow_truck_id,
+                    Utc::now(),
+                    Some(eta_seconds_for_distance(distance, self.tow_truck_speed)),
+                )
+                .await
+            {
+                Ok(()) => {
+                    self.event_bus.publish(DomainEvent::OrderDispatched {
+                        order_id: order.id,
+                        tow_truck_ids: vec![tow_truck_id],
+                    });
+                    results.push_ok(
+                        order.id,
+                        PendingAssignmentDto {
+                            order_id: order.id,
+                            tow_truck_id,
+                            distance,
+                        },
+                    );
+                }
+                Err(AppError::NotFound) => results.push_err(order.id, "not_found"),
+                Err(AppError::Conflict) => results.push_err(order.id, "conflict"),
+                Err(err) => {
+                    log::error!("注文 {} の一括配車に失敗しました: {:?}", order.id, err);
+                    results.push_err(order.id, "dispatch_failed");
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    // ディスパッチャーの作業キュー: 担当エリアの未配車の注文を受付の古い順に limit 件返す
+    // after には前のページの最後の注文の (受付時刻, 注文ID) を渡す (キーセットページング)
+    pub async fn get_dispatcher_queue(
+        &self,
+        dispatcher_id: i32,
+        after: Option<(DateTime<Utc>, i32)>,
+        limit: i32,
+    ) -> Result<DispatcherQueueDto, AppError> {
+        if limit <= 0 {
+            return Err(AppError::BadRequest);
+        }
+        let dispatcher = match self
+            .auth_repository
+            .find_dispatcher_by_id(dispatcher_id)
+            .await?
+        {
+            Some(dispatcher) => dispatcher,
+            None => return Err(AppError::NotFound),
+        };
+
+        let orders = self
+            .order_repository
+            .pending_in_area_after(AreaId::from(dispatcher.area_id), after, limit)
+            .await?;
+
+        // 件数が limit に満たなければ最後のページ
+        let next_cursor = match orders.len() == limit as usize {
+            true => orders.last().map(|order| QueueCursorDto {
+                after_time: order.order_time,
+                after_id: order.id,
+            }),
+            false => None,
+        };
+        let orders = orders
+            .into_iter()
+            .map(|order| QueuedOrderDto {
+                id: order.id,
+                client_id: order.client_id,
+                node_id: order.node_id,
+                destination_node_id: order.destination_node_id,
+                car_value: order.car_value,
+                trucks_required: order.trucks_required,
+                order_time: order.order_time,
+            })
+            .collect();
+
+        Ok(DispatcherQueueDto {
+            orders,
+            next_cursor,
+        })
+    }
+
+    // 配車済みのまま max_age を超えて完了していない注文を、古い順に返す (エスカレーション用)
+    pub async fn get_overdue_dispatched_orders(
+        &self,
+        area: AreaId,
+        max_age: chrono::Duration,
+    ) -> Result<Vec<OverdueOrderDto>, AppError> {
+        if max_age < chrono::Duration::zero() {
+            return Err(AppError::BadRequest);
+        }
+
+        let now = Utc::now();
+        let orders = self
+            .order_repository
+            .overdue_dispatched(area, max_age)
+            .await?;
+
+        Ok(orders
+            .into_iter()
+            .map(|order| OverdueOrderDto {
+                id: order.id,
+                dispatcher_id: order.dispatcher_id,
+                tow_truck_id: order.tow_truck_id,
+                order_time: order.order_time,
+                age_seconds: (now - order.order_time).num_seconds(),
+            })
+            .collect())
+    }
+}
+
+// 注文地点に到達できるトラックのIDを router が返す近い順に並べる (同じ距離ならトラックIDが小さい順)
+fn rank_tow_truck_ids_by_distance(
+    router: &dyn Router,
+    order: &Order,
+    graph: &Graph,
+    mut tow_trucks: Vec<TowTruck>,
+) -> Result<Vec<i32>, AppError> {
+    ensure_start_node(graph, order.node_id)?;
+    // トラックIDの順に候補を渡すと、同じ距離のノードはその順のまま返る
+    tow_trucks.sort_by_key(|tow_truck| tow_truck.id);
+    let truck_nodes: Vec<i32> = tow_trucks
+        .iter()
+        .map(|tow_truck| tow_truck.node_id)
+        .collect();
+    // 同じノードに複数のトラックがいる場合は、pop でIDが小さい順に取り出せるよう逆順に積む
+    let mut tow_truck_ids_by_node: HashMap<i32, Vec<i32>> = HashMap::new();
+    for tow_truck in tow_trucks.iter().rev() {
+        tow_truck_ids_by_node
+            .entry(tow_truck.node_id)
+            .or_default()
+            .push(tow_truck.id);
+    }
+
+    Ok(router
+        .rank(graph, order.node_id, &truck_nodes)
+        .into_iter()
+        .filter_map(|(node_id, _)| {
+            tow_truck_ids_by_node
+                .get_mut(&node_id)
+                .and_then(|tow_truck_ids| tow_truck_ids.pop())
+        })
+        .collect())
+}
+
+// 注文ID (受付順) と、注文ごとの到達できるトラックの (距離, トラックID) の昇順の一覧から、
+// 注文ID -> (トラックID, 距離) の割り当てを作る
+// 未割り当ての注文のうち、残っている最寄りのトラックまでの距離が最大のもの (同じなら受付の早いもの) から順に、
+// その最寄りのトラックを割り当てる
+fn minmax_assignments(
+    order_ids: &[i32],
+    reachable_by_order: &HashMap<i32, Vec<(i32, i32)>>,
+) -> HashMap<i32, (i32, i32)> {
+    let mut assigned_tow_truck_ids: Vec<i32> = Vec::new();
+    let mut assignments: HashMap<i32, (i32, i32)> = HashMap::new();
+    loop {
+        let worst_off = order_ids
+            .iter()
+            .filter(|order_id| !assignments.contains_key(order_id))
+            .filter_map(|&order_id| {
+                reachable_by_order
+                    .get(&order_id)?
+                    .iter()
+                    .find(|(_, tow_truck_id)| !assigned_tow_truck_ids.contains(tow_truck_id))
+                    .map(|&(distance, tow_truck_id)| (order_id, distance, tow_truck_id))
+            })
+            .fold(
+                None,
+                |worst: Option<(i32, i32, i32)>, candidate| match worst {
+                    Some(worst) if worst.1 >= candidate.1 => Some(worst),
+                    _ => Some(candidate),
+                },
+            );
+        match worst_off {
+            Some((order_id, distance, tow_truck_id)) => {
+                assigned_tow_truck_ids.push(tow_truck_id);
+                assignments.insert(order_id, (tow_truck_id, distance));
+            }
+            None => break,
+        }
+    }
+
+    assignments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domains::dto::batch::BatchItemResult;
+    use crate::domains::router::DijkstraRouter;
+    use crate::models::graph::{Edge, Node};
+    use crate::repositories::auth_repository::AuthRepositoryImpl;
+    use crate::repositories::map_repository::MapRepositoryImpl;
+    use crate::repositories::order_repository::OrderRepositoryImpl;
+    use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
+    use crate::testsupport::{
+        circuit_breaker, seed_area, seed_dispatch, seed_dispatcher, seed_edge, seed_node,
+        seed_order, seed_session, seed_truck, seed_user, test_pool, CountingPool,
+    };
+    use sqlx::mysql::MySqlPool;
+    use std::time::Duration;
+
+    type TestOrderService = OrderService<
+        OrderRepositoryImpl,
+        TowTruckRepositoryImpl,
+        AuthRepositoryImpl,
+        MapRepositoryImpl,
+    >;
+
+    fn service(pool: &MySqlPool, tow_truck_speed: f64) -> TestOrderService {
+        counted_service(&CountingPool::from(pool.clone()), tow_truck_speed)
+    }
+
+    // すべてのリポジトリが同じ CountingPool を使い、サービス全体のクエリ数を数えられるようにする
+    fn counted_service(pool: &CountingPool, tow_truck_speed: f64) -> TestOrderService {
+        OrderService::new(
+            OrderRepositoryImpl::new(pool.clone(), circuit_breaker()),
+            TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker()),
+            AuthRepositoryImpl::new(pool.clone(), circuit_breaker()),
+            MapRepositoryImpl::new(pool.clone(), circuit_breaker()),
+            Arc::new(GraphCache::new(None)),
+            Arc::new(DijkstraRouter),
+            DispatchLimiter::new(2),
+            ClientOrderLimiter::new(10, Duration::from_secs(60)),
+            Arc::new(EventBus::new()),
+            Arc::new(ShutdownCoordinator::new()),
+            tow_truck_speed,
+        )
+    }
+
+    fn order_at(node_id: i32) -> Order {
+        Order {
+            id: 1,
+            client_id: 1,
+            dispatcher_id: None,
+            tow_truck_id: None,
+            status: "pending".to_string(),
+            node_id,
+            destination_node_id: None,
+            car_value: 0.0,
+            contact_phone: None,
+            trucks_required: 1,
+            cancel_reason: None,
+            order_time: Utc::now(),
+            completed_time: None,
+            area_id: 1,
+            eta_seconds: None,
+        }
+    }
+
+    fn tow_truck_at(id: i32, node_id: i32) -> TowTruck {
+        TowTruck {
+            id,
+            driver_id: id,
+            driver_username: None,
+            status: "available".to_string(),
+            area_id: 1,
+            node_id,
+            model: None,
+            year: None,
+            deleted_at: None,
+        }
+    }
+
+    // 1 - 2 - 3 - 4 の一本道 (各辺の重みは1) と、どこにもつながらないノード 5
+    fn line_graph() -> Graph {
+        let mut graph = Graph::new();
+        for id in 1..=5 {
+            graph.add_node(Node {
+                id,
+                name: format!("node{}", id),
+                area_id: 1,
+                x: 0,
+                y: 0,
+            });
+        }
+        for id in 1..=3 {
+            graph.add_edge(Edge {
+                node_a_id: id,
+                node_b_id: id + 1,
+                weight: 1,
+            });
+        }
+        graph
+    }
+
+    // 渡された候補を逆順に返す、探索方法の差し替えを確認するためのスタブ
+    #[derive(Debug)]
+    struct ReversingRouter;
+
+    impl Router for ReversingRouter {
+        fn rank(&self, _graph: &Graph, _order_node: i32, truck_nodes: &[i32]) -> Vec<(i32, i32)> {
+            truck_nodes
+                .iter()
+                .rev()
+                .map(|&node_id| (node_id, 0))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn ranks_tow_trucks_by_distance_then_id() {
+        let tow_trucks = vec![
+            tow_truck_at(30, 4),
+            tow_truck_at(20, 2),
+            tow_truck_at(10, 2),
+            tow_truck_at(40, 5),
+        ];
+
+        let ranked = rank_tow_truck_ids_by_distance(
+            &DijkstraRouter,
+            &order_at(1),
+            &line_graph(),
+            tow_trucks,
+        )
+        .unwrap();
+
+        assert_eq!(ranked, vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn ranks_tow_trucks_in_the_order_the_router_returns() {
+        let tow_trucks = vec![tow_truck_at(1, 2), tow_truck_at(2, 3), tow_truck_at(3, 4)];
+
+        let ranked = rank_tow_truck_ids_by_distance(
+            &ReversingRouter,
+            &order_at(1),
+            &line_graph(),
+            tow_trucks,
+        )
+        .unwrap();
+
+        assert_eq!(ranked, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn rejects_ranking_from_a_node_missing_from_the_graph() {
+        let result = rank_tow_truck_ids_by_distance(
+            &DijkstraRouter,
+            &order_at(99),
+            &line_graph(),
+            vec![tow_truck_at(1, 2)],
+        );
+
+        assert!(matches!(result, Err(AppError::NotFound)));
+    }
+
+    #[test]
+    fn accepts_e164_like_contact_phones() {
+        assert!(validate_contact_phone("+819012345678").is_ok());
+        assert!(validate_contact_phone("0312345678").is_ok());
+        assert!(validate_contact_phone("1234567").is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_contact_phones() {
+        for contact_phone in [
+            "",
+            "+",
+            "123456",
+            "1234567890123456",
+            "090-1234-5678",
+            "++819012345678",
+            "81+9012345678",
+        ] {
+            assert!(
+                matches!(
+                    validate_contact_phone(contact_phone),
+                    Err(AppError::BadRequest)
+                ),
+                "{contact_phone}"
+            );
+        }
+    }
+
+    #[test]
+    fn masks_contact_phone_except_last_digits_for_other_roles() {
+        let contact_phone = Some("+819012345678".to_string());
+        for role in [Role::Client, Role::Driver] {
+            assert_eq!(
+                visible_contact_phone(contact_phone.clone(), role).as_deref(),
+                Some("+********5678")
+            );
+        }
+        for role in [Role::Dispatcher, Role::Admin] {
+            assert_eq!(
+                visible_contact_phone(contact_phone.clone(), role),
+                contact_phone
+            );
+        }
+        assert_eq!(visible_contact_phone(None, Role::Client), None);
+    }
+
+    #[test]
+    fn converts_distance_to_eta_seconds_by_speed() {
+        assert_eq!(eta_seconds_for_distance(0, 1.0), 0);
+        assert_eq!(eta_seconds_for_distance(5, 1.0), 300);
+        assert_eq!(eta_seconds_for_distance(7, 2.0), 210);
+        assert_eq!(eta_seconds_for_distance(1, 3.0), 20);
+    }
+
+    // 比較用の最寄り順の貪欲法: 全体で最も近い (注文, トラック) の組から順に割り当てる
+    fn greedy_assignments(
+        reachable_by_order: &HashMap<i32, Vec<(i32, i32)>>,
+    ) -> HashMap<i32, (i32, i32)> {
+        let mut pairs: Vec<(i32, i32, i32)> = reachable_by_order
+            .iter()
+            .flat_map(|(&order_id, reachable)| {
+                reachable
+                    .iter()
+                    .map(move |&(distance, tow_truck_id)| (distance, order_id, tow_truck_id))
+            })
+            .collect();
+        pairs.sort_unstable();
+        let mut assigned_tow_truck_ids = Vec::new();
+        let mut assignments = HashMap::new();
+        for (distance, order_id, tow_truck_id) in pairs {
+            if !assignments.contains_key(&order_id)
+                && !assigned_tow_truck_ids.contains(&tow_truck_id)
+            {
+                assigned_tow_truck_ids.push(tow_truck_id);
+                assignments.insert(order_id, (tow_truck_id, distance));
+            }
+        }
+        assignments
+    }
+
+    fn max_distance(assignments: &HashMap<i32, (i32, i32)>) -> i32 {
+        assignments
+            .values()
+            .map(|&(_, distance)| distance)
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn minmax_assignment_beats_greedy_when_greedy_strands_a_remote_order() {
+        // 注文 1 はトラック 10 のすぐ近くだが、注文 2 にとってもトラック 10 以外は遠い
+        // 貪欲法は注文 1 にトラック 10 を取られ、注文 2 の待ちが 100 になる
+        let reachable_by_order =
+            HashMap::from([(1, vec![(1, 10), (10, 20)]), (2, vec![(2, 10), (100, 20)])]);
+
+        let greedy = greedy_assignments(&reachable_by_order);
+        let minmax = minmax_assignments(&[1, 2], &reachable_by_order);
+
+        assert_eq!(max_distance(&greedy), 100);
+        assert_eq!(minmax, HashMap::from([(1, (20, 10)), (2, (10, 2))]));
+        assert_eq!(max_distance(&minmax), 10);
+    }
+
+    #[test]
+    fn minmax_assignment_is_no_worse_than_greedy_and_skips_unreachable_orders() {
+        // 注文 4 はどのトラックにも到達できない
+        let reachable_by_order = HashMap::from([
+            (1, vec![(3, 10), (4, 20), (9, 30)]),
+            (2, vec![(1, 20), (5, 10), (6, 30)]),
+            (3, vec![(2, 30), (7, 20), (8, 10)]),
+            (4, vec![]),
+        ]);
+
+        let greedy = greedy_assignments(&reachable_by_order);
+        let minmax = minmax_assignments(&[1, 2, 3, 4], &reachable_by_order);
+
+        assert_eq!(minmax.len(), 3);
+        assert!(!minmax.contains_key(&4));
+        let mut tow_truck_ids: Vec<i32> = minmax.values().map(|&(id, _)| id).collect();
+        tow_truck_ids.sort_unstable();
+        assert_eq!(tow_truck_ids, vec![10, 20, 30]);
+        assert!(max_distance(&minmax) <= max_distance(&greedy));
+    }
+
+    #[test]
+    fn completion_sla_report_of_no_orders_has_no_average_or_p95() {
+        let report = completion_sla_report(vec![]);
+
+        assert_eq!(report.count, 0);
+        assert_eq!(report.average_seconds, None);
+        assert_eq!(report.p95_seconds, None);
+    }
+
+    #[test]
+    fn completion_sla_report_of_one_order_uses_its_duration() {
+        let report = completion_sla_report(vec![420]);
+
+        assert_eq!(report.count, 1);
+        assert_eq!(report.average_seconds, Some(420.0));
+        assert_eq!(report.p95_seconds, Some(420));
+    }
+
+    #[test]
+    fn completion_sla_p95_uses_the_nearest_rank() {
+        // 20件なら 19 番目、21件なら 20 番目の値 (入力の順序には依らない)
+        let twenty: Vec<i64> = (1..=20).rev().collect();
+        let report = completion_sla_report(twenty);
+        assert_eq!(report.average_seconds, Some(10.5));
+        assert_eq!(report.p95_seconds, Some(19));
+
+        let report = completion_sla_report((1..=21).collect());
+        assert_eq!(report.p95_seconds, Some(20));
+    }
+
+    #[actix_rt::test]
+    async fn completion_sla_report_covers_completed_orders_in_the_window() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let service = service(&pool, 60.0);
+        let now = Utc::now();
+        let from = now - chrono::Duration::days(1);
+        let to = now + chrono::Duration::minutes(1);
+        // (完了までの秒数, 受付時刻が期間内か)。完了は update_order_status で現在時刻に行う
+        let completed_orders = [
+            (60, true),
+            (120, true),
+            (180, true),
+            (600, true),
+            (60, false),
+        ];
+        for (completed_after, in_window) in completed_orders {
+            let order_time = match in_window {
+                true => now - chrono::Duration::seconds(completed_after),
+                false => from - chrono::Duration::seconds(completed_after),
+            };
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            let driver_id = seed_user(&pool, "driver").await;
+            let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+            seed_dispatch(&pool, order_id, tow_truck_id).await;
+            sqlx::query("UPDATE orders SET order_time = ? WHERE id = ?")
+                .bind(order_time)
+                .bind(order_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            service
+                .update_order_status(order_id, "completed")
+                .await
+                .unwrap();
+        }
+        // 配車済みで未完了の注文は、配車時に作られた completed_orders の行があっても集計に含めない
+        let dispatched_id = seed_order(&pool, client_id, node_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        seed_dispatch(&pool, dispatched_id, tow_truck_id).await;
+        sqlx::query(
+            "INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)",
+        )
+        .bind(dispatched_id)
+        .bind(tow_truck_id)
+        .bind(now)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let report = service
+            .get_completion_sla_report(AreaId(area_id), from, to)
+            .await
+            .unwrap();
+
+        // 完了時刻はテストの実行中に決まるため、数秒の遅れを許す
+        assert_eq!(report.count, 4);
+        let average_seconds = report.average_seconds.unwrap();
+        assert!(
+            (240.0..245.0).contains(&average_seconds),
+            "{}",
+            average_seconds
+        );
+        let p95_seconds = report.p95_seconds.unwrap();
+        assert!((600..605).contains(&p95_seconds), "{}", p95_seconds);
+    }
+
+    #[actix_rt::test]
+    async fn dispatch_persists_eta_converted_from_path_distance() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 注文地点 0 - 1 - 2 (重み 3, 4) で、トラックはノード 2 (距離 7) にいる
+        let area_id = seed_area(&pool).await;
+        let mut node_ids = Vec::new();
+        for _ in 0..3 {
+            node_ids.push(seed_node(&pool, area_id).await);
+        }
+        seed_edge(&pool, node_ids[0], node_ids[1], 3).await;
+        seed_edge(&pool, node_ids[1], node_ids[2], 4).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[2]).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_ids[0]).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+
+        let result = service(&pool, 2.0)
+            .dispatch_and_describe(order_id, dispatcher_id, false, Role::Dispatcher)
+            .await
+            .unwrap();
+
+        assert_eq!(result.truck.id, tow_truck_id);
+        assert_eq!(result.distance, 7);
+        let eta_seconds: Option<i32> =
+            sqlx::query_scalar("SELECT eta_seconds FROM orders WHERE id = ?")
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(eta_seconds, Some(eta_seconds_for_distance(7, 2.0)));
+    }
+
+    #[actix_rt::test]
+    async fn order_dispatched_from_an_adjacent_area_can_be_completed() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 注文のエリアには空きトラックがおらず、隣接エリアのノードに辺 (重み 5) でつながったトラックがいる
+        let area_id = seed_area(&pool).await;
+        let adjacent_area_id = seed_area(&pool).await;
+        let order_node_id = seed_node(&pool, area_id).await;
+        let adjacent_node_id = seed_node(&pool, adjacent_area_id).await;
+        seed_edge(&pool, order_node_id, adjacent_node_id, 5).await;
+        sqlx::query("INSERT INTO area_adjacencies (area_id, adjacent_area_id) VALUES (?, ?)")
+            .bind(area_id)
+            .bind(adjacent_area_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, adjacent_area_id, adjacent_node_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, order_node_id).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let service = service(&pool, 60.0);
+
+        // 隣接エリアを探さない場合は配車できない
+        assert!(matches!(
+            service
+                .dispatch_and_describe(order_id, dispatcher_id, false, Role::Dispatcher)
+                .await,
+            Err(AppError::NotFound)
+        ));
+        let result = service
+            .dispatch_and_describe(order_id, dispatcher_id, true, Role::Dispatcher)
+            .await
+            .unwrap();
+        assert_eq!(result.truck.id, tow_truck_id);
+        assert_eq!(result.distance, 5);
+
+        service
+            .update_order_status(order_id, "completed")
+            .await
+            .unwrap();
+
+        let (order_status,): (String,) = sqlx::query_as("SELECT status FROM orders WHERE id = ?")
+            .bind(order_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(order_status, "completed");
+        // トラックは注文のエリアに移ったうえで空きに戻る
+        let (tow_truck_status, tow_truck_area_id): (String, i32) =
+            sqlx::query_as("SELECT status, area_id FROM tow_trucks WHERE id = ?")
+                .bind(tow_truck_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(tow_truck_status, "available");
+        assert_eq!(tow_truck_area_id, area_id);
+    }
+
+    #[actix_rt::test]
+    async fn paginated_orders_are_enriched_with_one_query_per_kind() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 5件の注文のうち2件は配車担当者とトラックが付いている
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        for i in 0..5 {
+            let client_id = seed_user(&pool, "client").await;
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            if i < 2 {
+                let driver_id = seed_user(&pool, "driver").await;
+                let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+                seed_dispatch(&pool, order_id, tow_truck_id).await;
+                sqlx::query("UPDATE orders SET dispatcher_id = ? WHERE id = ?")
+                    .bind(dispatcher_id)
+                    .bind(order_id)
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+            }
+        }
+        let counting_pool = CountingPool::from(pool.clone());
+        let filter = OrderFilter {
+            area: Some(AreaId(area_id)),
+            ..OrderFilter::default()
+        };
+
+        let orders = counted_service(&counting_pool, 60.0)
+            .get_paginated_orders(0, 10, None, None, filter, Role::Dispatcher)
+            .await
+            .unwrap();
+
+        // 注文の一覧、配車担当者、トラック、ユーザーをそれぞれ1回ずつ問い合わせる
+        assert_eq!(orders.len(), 5);
+        assert_eq!(counting_pool.query_count(), 4);
+    }
+
+    #[actix_rt::test]
+    async fn large_pages_are_enriched_in_chunked_queries() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        // クライアントが ENRICH_CHUNK_SIZE を超えるページを作る
+        let order_count = ENRICH_CHUNK_SIZE + 50;
+        for _ in 0..order_count {
+            let client_id = seed_user(&pool, "client").await;
+            seed_order(&pool, client_id, node_id).await;
+        }
+        let counting_pool = CountingPool::from(pool.clone());
+        let filter = OrderFilter {
+            area: Some(AreaId(area_id)),
+            ..OrderFilter::default()
+        };
+
+        let orders = counted_service(&counting_pool, 60.0)
+            .get_paginated_orders(0, order_count as i32, None, None, filter, Role::Dispatcher)
+            .await
+            .unwrap();
+
+        // 注文の一覧1回と、クライアントのユーザーを 100 件と 50 件に分けた2回
+        assert_eq!(orders.len(), order_count);
+        assert_eq!(counting_pool.query_count(), 3);
+        assert!(orders.iter().all(|order| order.client_username.is_some()));
+    }
+
+    #[actix_rt::test]
+    async fn queries_inside_a_transaction_are_counted() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, order_id, tow_truck_id).await;
+        let counting_pool = CountingPool::from(pool.clone());
+
+        counted_service(&counting_pool, 60.0)
+            .update_order_status(order_id, "completed")
+            .await
+            .unwrap();
+
+        // 完了はすべてトランザクションの中で行う
+        // 注文のロック、トラックのエリア確認、完了の記録、注文とトラックの更新、履歴、補助トラックの解放の7回
+        assert_eq!(counting_pool.query_count(), 7);
+    }
+
+    #[actix_rt::test]
+    async fn unassigned_orders_are_enriched_without_truck_or_dispatcher_lookups() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        for _ in 0..3 {
+            let client_id = seed_user(&pool, "client").await;
+            seed_order(&pool, client_id, node_id).await;
+        }
+        let counting_pool = CountingPool::from(pool.clone());
+        let filter = OrderFilter {
+            area: Some(AreaId(area_id)),
+            ..OrderFilter::default()
+        };
+
+        let orders = counted_service(&counting_pool, 60.0)
+            .get_paginated_orders(0, 10, None, None, filter, Role::Dispatcher)
+            .await
+            .unwrap();
+
+        // 注文の一覧とクライアントのユーザーだけを問い合わせる
+        assert_eq!(orders.len(), 3);
+        assert_eq!(counting_pool.query_count(), 2);
+        assert!(orders.iter().all(|order| order.client_username.is_some()
+            && order.dispatcher_user_id.is_none()
+            && order.driver_user_id.is_none()));
+    }
+
+    #[actix_rt::test]
+    async fn enriched_orders_carry_the_dispatcher_and_driver_users() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        // dispatchers.id と users.id がずれるよう、配車担当者を2人作って2人目を使う
+        seed_dispatcher(&pool, area_id).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let dispatcher_user_id: i32 =
+            sqlx::query_scalar("SELECT user_id FROM dispatchers WHERE id = ?")
+                .bind(dispatcher_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, order_id, tow_truck_id).await;
+        sqlx::query("UPDATE orders SET dispatcher_id = ? WHERE id = ?")
+            .bind(dispatcher_id)
+            .bind(order_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let filter = OrderFilter {
+            area: Some(AreaId(area_id)),
+            ..OrderFilter::default()
+        };
+
+        let orders = service(&pool, 60.0)
+            .get_paginated_orders(0, 10, None, None, filter, Role::Dispatcher)
+            .await
+            .unwrap();
+
+        assert_eq!(orders.len(), 1);
+        let order = &orders[0];
+        assert_eq!(
+            order.client_username,
+            Some(username(&pool, client_id).await)
+        );
+        assert_eq!(order.dispatcher_user_id, Some(dispatcher_user_id));
+        assert_eq!(
+            order.dispatcher_username,
+            Some(username(&pool, dispatcher_user_id).await)
+        );
+        assert_eq!(order.driver_user_id, Some(driver_id));
+        assert_eq!(
+            order.driver_username,
+            Some(username(&pool, driver_id).await)
+        );
+    }
+
+    #[actix_rt::test]
+    async fn dispatcher_only_operations_reject_clients_and_drivers() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let service = service(&pool, 60.0);
+        for (role, expected) in [
+            ("dispatcher", Some(Role::Dispatcher)),
+            ("admin", Some(Role::Admin)),
+            ("client", None),
+            ("driver", None),
+        ] {
+            let user_id = seed_user(&pool, role).await;
+            let session_token = seed_session(&pool, user_id).await;
+
+            match (service.ensure_dispatcher(&session_token).await, expected) {
+                (Ok(role), Some(expected)) => assert_eq!(role, expected),
+                (Err(AppError::Forbidden), None) => {}
+                (result, _) => panic!("unexpected result for {}: {:?}", role, result),
+            }
+        }
+        assert!(matches!(
+            service.ensure_dispatcher("no_such_session").await,
+            Err(AppError::Unauthorized)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn completed_time_is_read_from_the_completed_orders_row() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let service = service(&pool, 60.0);
+        let completed_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, completed_order_id, tow_truck_id).await;
+        service
+            .update_order_status(completed_order_id, "completed")
+            .await
+            .unwrap();
+        // 配車済みで未完了の注文に completed_orders の行が残っていても完了時刻として返さない
+        let dispatched_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, dispatched_order_id, tow_truck_id).await;
+        sqlx::query(
+            "INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, NOW())",
+        )
+        .bind(dispatched_order_id)
+        .bind(tow_truck_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let recorded: DateTime<Utc> =
+            sqlx::query_scalar("SELECT completed_time FROM completed_orders WHERE order_id = ?")
+                .bind(completed_order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+        let completed = service
+            .get_order_by_id(completed_order_id, Role::Dispatcher)
+            .await
+            .unwrap();
+        let dispatched = service
+            .get_order_by_id(dispatched_order_id, Role::Dispatcher)
+            .await
+            .unwrap();
+        let listed = service
+            .get_paginated_orders(
+                0,
+                10,
+                None,
+                None,
+                OrderFilter {
+                    statuses: Vec::new(),
+                    area: Some(AreaId(area_id)),
+                    assigned: None,
+                },
+                Role::Dispatcher,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(completed.completed_time, Some(recorded));
+        assert_eq!(dispatched.completed_time, None);
+        let listed_completed_times: HashMap<i32, Option<DateTime<Utc>>> = listed
+            .iter()
+            .map(|order| (order.id, order.completed_time))
+            .collect();
+        assert_eq!(listed_completed_times[&completed_order_id], Some(recorded));
+        assert_eq!(listed_completed_times[&dispatched_order_id], None);
+    }
+
+    #[actix_rt::test]
+    async fn dispatcher_for_order_joins_the_dispatcher_user() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let (dispatcher_user_id, dispatcher_username): (i32, String) = sqlx::query_as(
+            "SELECT u.id, u.username FROM dispatchers d JOIN users u ON u.id = d.user_id WHERE d.id = ?",
+        )
+        .bind(dispatcher_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let dispatched_order_id = seed_order(&pool, client_id, node_id).await;
+        sqlx::query("UPDATE orders SET dispatcher_id = ? WHERE id = ?")
+            .bind(dispatcher_id)
+            .bind(dispatched_order_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let pending_order_id = seed_order(&pool, client_id, node_id).await;
+        let service = service(&pool, 60.0);
+
+        let dispatcher = service
+            .dispatcher_for_order(dispatched_order_id)
+            .await
+            .unwrap()
+            .expect("the order has a dispatcher");
+        assert_eq!(dispatcher.id, dispatcher_id);
+        assert_eq!(dispatcher.user_id, dispatcher_user_id);
+        assert_eq!(dispatcher.username, dispatcher_username);
+        assert_eq!(dispatcher.area_id, area_id);
+
+        assert!(service
+            .dispatcher_for_order(pending_order_id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    async fn username(pool: &MySqlPool, user_id: i32) -> String {
+        sqlx::query_scalar("SELECT username FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn completed_orders_are_enriched_with_one_users_query() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 3件の完了済みの注文を、2人のドライバーで担当する
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_ids = [
+            seed_user(&pool, "driver").await,
+            seed_user(&pool, "driver").await,
+        ];
+        let mut tow_truck_ids = Vec::new();
+        for driver_id in driver_ids {
+            tow_truck_ids.push(seed_truck(&pool, driver_id, area_id, node_id).await);
+        }
+        let service = service(&pool, 60.0);
+        let mut expected: HashMap<i32, (i32, i32)> = HashMap::new();
+        for i in 0..3 {
+            let client_id = seed_user(&pool, "client").await;
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            seed_dispatch(&pool, order_id, tow_truck_ids[i % 2]).await;
+            service
+                .update_order_status(order_id, "completed")
+                .await
+                .unwrap();
+            expected.insert(order_id, (client_id, driver_ids[i % 2]));
+        }
+        let counting_pool = CountingPool::from(pool.clone());
+
+        let completed_orders = counted_service(&counting_pool, 60.0)
+            .get_completed_orders(0, 50)
+            .await
+            .unwrap();
+
+        // 完了済みの注文の一覧、トラック、ユーザー (クライアントとドライバー) をそれぞれ1回ずつ問い合わせる
+        assert_eq!(counting_pool.query_count(), 3);
+        let ours: Vec<&CompletedOrderDetailDto> = completed_orders
+            .iter()
+            .filter(|completed_order| expected.contains_key(&completed_order.order_id))
+            .collect();
+        assert_eq!(ours.len(), 3);
+        for completed_order in ours {
+            let (client_id, driver_id) = expected[&completed_order.order_id];
+            assert_eq!(
+                completed_order.client_username,
+                Some(username(&pool, client_id).await)
+            );
+            assert_eq!(completed_order.driver_user_id, Some(driver_id));
+            assert_eq!(
+                completed_order.driver_username,
+                Some(username(&pool, driver_id).await)
+            );
+        }
+    }
+
+    // 注文を dispatcher_id の担当者が dispatched_at に配車した状態にする
+    async fn seed_dispatch_by(
+        pool: &MySqlPool,
+        order_id: i32,
+        tow_truck_id: i32,
+        dispatcher_id: i32,
+        dispatched_at: DateTime<Utc>,
+    ) {
+        seed_dispatch(pool, order_id, tow_truck_id).await;
+        sqlx::query("UPDATE orders SET dispatcher_id = ? WHERE id = ?")
+            .bind(dispatcher_id)
+            .bind(order_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO order_events (order_id, tow_truck_id, event_type, occurred_at)
+            VALUES (?, ?, 'dispatched', ?)",
+        )
+        .bind(order_id)
+        .bind(tow_truck_id)
+        .bind(dispatched_at)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn dispatch_fairness_counts_dispatches_by_when_they_happened() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let busy_dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let quiet_dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let from = Utc::now() - chrono::Duration::hours(2);
+        let to = Utc::now() - chrono::Duration::hours(1);
+        let inside = from + chrono::Duration::minutes(30);
+        // (担当者, 受付時刻, 配車時刻)。受付が期間より前でも期間内に配車した注文は数え、
+        // 受付が期間内でも期間の後に配車した注文は数えない
+        let dispatches = [
+            (busy_dispatcher_id, inside, inside),
+            (
+                busy_dispatcher_id,
+                from - chrono::Duration::hours(1),
+                inside,
+            ),
+            (
+                busy_dispatcher_id,
+                inside,
+                to + chrono::Duration::minutes(1),
+            ),
+            (quiet_dispatcher_id, inside, inside),
+        ];
+        for (dispatcher_id, order_time, dispatched_at) in dispatches {
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            sqlx::query("UPDATE orders SET order_time = ? WHERE id = ?")
+                .bind(order_time)
+                .bind(order_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            seed_dispatch_by(&pool, order_id, tow_truck_id, dispatcher_id, dispatched_at).await;
+        }
+        let service = service(&pool, 60.0);
+
+        let fairness = service
+            .get_dispatch_fairness(AreaId(area_id), from, to)
+            .await
+            .unwrap();
+
+        assert_eq!(fairness.counts.len(), 2);
+        assert_eq!(fairness.counts[&busy_dispatcher_id], 2);
+        assert_eq!(fairness.counts[&quiet_dispatcher_id], 1);
+        assert_eq!(fairness.imbalance, Some(2.0));
+        assert!(matches!(
+            service
+                .get_dispatch_fairness(AreaId(area_id), to, from)
+                .await,
+            Err(AppError::BadRequest)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn dedupe_pending_cancels_repeats_at_the_same_node_within_the_window() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let other_node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let other_client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let base = Utc::now() - chrono::Duration::hours(1);
+        let seed_order_at = |client_id: i32, node_id: i32, seconds: i64| {
+            let pool = pool.clone();
+            async move {
+                let order_id = seed_order(&pool, client_id, node_id).await;
+                sqlx::query("UPDATE orders SET order_time = ? WHERE id = ?")
+                    .bind(base + chrono::Duration::seconds(seconds))
+                    .bind(order_id)
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+                order_id
+            }
+        };
+        let first_id = seed_order_at(client_id, node_id, 0).await;
+        let repeat_id = seed_order_at(client_id, node_id, 30).await;
+        let later_id = seed_order_at(client_id, node_id, 200).await;
+        let other_node_order_id = seed_order_at(client_id, other_node_id, 10).await;
+        let other_client_order_id = seed_order_at(other_client_id, node_id, 5).await;
+        let dispatched_order_id = seed_order_at(client_id, node_id, 20).await;
+        seed_dispatch(&pool, dispatched_order_id, tow_truck_id).await;
+        let service = service(&pool, 60.0);
+
+        let deduped = service
+            .dedupe_pending(client_id, chrono::Duration::seconds(60))
+            .await
+            .unwrap();
+
+        assert_eq!(deduped.len(), 1);
+        assert_eq!(deduped[0].kept_order_id, first_id);
+        assert_eq!(deduped[0].canceled_order_ids, vec![repeat_id]);
+        let (status, cancel_reason): (String, Option<String>) =
+            sqlx::query_as("SELECT status, cancel_reason FROM orders WHERE id = ?")
+                .bind(repeat_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(status, "canceled");
+        assert_eq!(cancel_reason.as_deref(), Some("duplicate"));
+        for (order_id, expected_status) in [
+            (first_id, "pending"),
+            (later_id, "pending"),
+            (other_node_order_id, "pending"),
+            (other_client_order_id, "pending"),
+            (dispatched_order_id, "dispatched"),
+        ] {
+            let status: String = sqlx::query_scalar("SELECT status FROM orders WHERE id = ?")
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(status, expected_status);
+        }
+        // 重ねて呼んでも取り消す注文は残っていない
+        assert!(service
+            .dedupe_pending(client_id, chrono::Duration::seconds(60))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            service
+                .dedupe_pending(client_id, chrono::Duration::seconds(-1))
+                .await,
+            Err(AppError::BadRequest)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn preferred_dispatch_uses_the_preferred_truck_only_when_it_is_usable() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 注文地点 - (1) - 最寄りのトラックのいるノード。指定トラックは注文地点か別のエリアにいる
+        let area_id = seed_area(&pool).await;
+        let order_node_id = seed_node(&pool, area_id).await;
+        let nearest_node_id = seed_node(&pool, area_id).await;
+        seed_edge(&pool, order_node_id, nearest_node_id, 1).await;
+        let other_area_id = seed_area(&pool).await;
+        let other_node_id = seed_node(&pool, other_area_id).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let service = service(&pool, 60.0);
+
+        // (指定トラックの状態, 指定トラックのエリアとノード, 指定トラックを使うか)
+        let cases = [
+            ("available", (area_id, order_node_id), true),
+            ("busy", (area_id, order_node_id), false),
+            ("available", (other_area_id, other_node_id), false),
+        ];
+        for (status, (preferred_area_id, preferred_node_id), expect_preferred) in cases {
+            let nearest_tow_truck_id = seed_truck(&pool, driver_id, area_id, nearest_node_id).await;
+            let preferred_tow_truck_id =
+                seed_truck(&pool, driver_id, preferred_area_id, preferred_node_id).await;
+            sqlx::query("UPDATE tow_trucks SET status = ? WHERE id = ?")
+                .bind(status)
+                .bind(preferred_tow_truck_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            let order_id = seed_order(&pool, client_id, order_node_id).await;
+
+            let result = service
+                .create_dispatcher_order_preferred(order_id, dispatcher_id, preferred_tow_truck_id)
+                .await
+                .unwrap();
+
+            assert_eq!(result.used_preferred, expect_preferred);
+            let expected_tow_truck_id = match expect_preferred {
+                true => preferred_tow_truck_id,
+                false => nearest_tow_truck_id,
+            };
+            assert_eq!(result.tow_truck_id, expected_tow_truck_id);
+            let assigned: Option<i32> =
+                sqlx::query_scalar("SELECT tow_truck_id FROM orders WHERE id = ?")
+                    .bind(order_id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(assigned, Some(expected_tow_truck_id));
+            // 次のケースに影響しないよう、このケースのトラックを候補から外す
+            sqlx::query("UPDATE tow_trucks SET status = 'busy' WHERE id IN (?, ?)")
+                .bind(nearest_tow_truck_id)
+                .bind(preferred_tow_truck_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[actix_rt::test]
+    async fn multi_truck_dispatch_takes_the_nearest_trucks_and_records_the_last_arrival() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 注文地点から 3, 5, 9 の距離に1台ずつトラックがいて、2台が必要な注文
+        let area_id = seed_area(&pool).await;
+        let order_node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let mut tow_truck_ids = Vec::new();
+        for weight in [3, 5, 9] {
+            let node_id = seed_node(&pool, area_id).await;
+            seed_edge(&pool, order_node_id, node_id, weight).await;
+            tow_truck_ids.push(seed_truck(&pool, driver_id, area_id, node_id).await);
+        }
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, order_node_id).await;
+        sqlx::query("UPDATE orders SET trucks_required = 2 WHERE id = ?")
+            .bind(order_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let dispatched = service(&pool, 2.0)
+            .dispatch_required_tow_trucks(order_id, dispatcher_id)
+            .await
+            .unwrap();
+
+        assert_eq!(dispatched, tow_truck_ids[..2].to_vec());
+        let (primary_tow_truck_id, eta_seconds): (Option<i32>, Option<i32>) =
+            sqlx::query_as("SELECT tow_truck_id, eta_seconds FROM orders WHERE id = ?")
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(primary_tow_truck_id, Some(tow_truck_ids[0]));
+        assert_eq!(eta_seconds, Some(eta_seconds_for_distance(5, 2.0)));
+        let mut assigned: Vec<i32> =
+            sqlx::query_scalar("SELECT tow_truck_id FROM order_tow_trucks WHERE order_id = ?")
+                .bind(order_id)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assigned.sort();
+        assert_eq!(assigned, tow_truck_ids[..2].to_vec());
+    }
+
+    #[actix_rt::test]
+    async fn overdue_orders_are_those_dispatched_longer_ago_than_the_threshold() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let now = Utc::now();
+        // しきい値 (1時間) の1分前後に受け付けた配車済みの注文と、しきい値を超えた pending の注文
+        let mut order_ids = Vec::new();
+        for (minutes_ago, dispatched) in [(59, true), (61, true), (120, false)] {
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            sqlx::query("UPDATE orders SET order_time = ? WHERE id = ?")
+                .bind(now - chrono::Duration::minutes(minutes_ago))
+                .bind(order_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            if dispatched {
+                let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+                seed_dispatch(&pool, order_id, tow_truck_id).await;
+            }
+            order_ids.push(order_id);
+        }
+        let service = service(&pool, 60.0);
+
+        let overdue = service
+            .get_overdue_dispatched_orders(AreaId(area_id), chrono::Duration::hours(1))
+            .await
+            .unwrap();
+
+        let overdue_ids: Vec<i32> = overdue.iter().map(|order| order.id).collect();
+        assert_eq!(overdue_ids, vec![order_ids[1]]);
+        assert!(overdue[0].age_seconds >= 61 * 60);
+        // 基準時刻を表せない長さは panic せずに拒否する
+        assert!(matches!(
+            service
+                .get_overdue_dispatched_orders(AreaId(area_id), chrono::Duration::max_value())
+                .await,
+            Err(AppError::BadRequest)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn canceling_records_the_reason_and_rejects_unknown_reasons() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        let service = service(&pool, 60.0);
+
+        // 許可されていない理由では取り消さない
+        assert!(matches!(
+            service.cancel_order(order_id, "changed_mind").await,
+            Err(AppError::BadRequest)
+        ));
+        let order = service
+            .get_order_by_id(order_id, Role::Dispatcher)
+            .await
+            .unwrap();
+        assert_eq!(order.status, OrderStatus::Pending);
+        assert_eq!(order.cancel_reason, None);
+
+        service
+            .cancel_order(order_id, "customer_canceled")
+            .await
+            .unwrap();
+        let order = service
+            .get_order_by_id(order_id, Role::Dispatcher)
+            .await
+            .unwrap();
+        assert_eq!(order.status, OrderStatus::Canceled);
+        assert_eq!(order.cancel_reason, Some(CancelReason::CustomerCanceled));
+    }
+
+    #[actix_rt::test]
+    async fn pending_orders_within_returns_only_in_radius_pending_orders() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 拠点 0 - 1 - 2 - 3 (重みはすべて 2) の一本道
+        let area_id = seed_area(&pool).await;
+        let mut node_ids = Vec::new();
+        for _ in 0..4 {
+            node_ids.push(seed_node(&pool, area_id).await);
+        }
+        for pair in node_ids.windows(2) {
+            seed_edge(&pool, pair[0], pair[1], 2).await;
+        }
+        let client_id = seed_user(&pool, "client").await;
+        let mut order_ids = Vec::new();
+        for &node_id in &node_ids {
+            order_ids.push(seed_order(&pool, client_id, node_id).await);
+        }
+        // 半径内でも配車済みの注文は含めない
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[1]).await;
+        let dispatched_order_id = seed_order(&pool, client_id, node_ids[1]).await;
+        seed_dispatch(&pool, dispatched_order_id, tow_truck_id).await;
+        let service = service(&pool, 60.0);
+
+        let nearby: Vec<(i32, i32)> = service
+            .get_pending_orders_within(node_ids[0], 4)
+            .await
+            .unwrap()
+            .iter()
+            .map(|order| (order.id, order.distance))
+            .collect();
+
+        // 距離がちょうど半径の注文は含み、半径を超える注文は含まない
+        assert_eq!(
+            nearby,
+            vec![(order_ids[0], 0), (order_ids[1], 2), (order_ids[2], 4)]
+        );
+        assert!(matches!(
+            service.get_pending_orders_within(node_ids[0], -1).await,
+            Err(AppError::BadRequest)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn paginated_orders_reject_negative_page_and_page_size() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let service = service(&pool, 60.0);
+
+        for (page, page_size) in [(-1, 10), (0, -1), (0, 0)] {
+            assert!(matches!(
+                service
+                    .get_paginated_orders(
+                        page,
+                        page_size,
+                        None,
+                        None,
+                        OrderFilter::default(),
+                        Role::Dispatcher
+                    )
+                    .await,
+                Err(AppError::BadRequest)
+            ));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn dispatcher_queue_pages_pending_orders_of_the_dispatchers_area_oldest_first() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let other_area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let other_node_id = seed_node(&pool, other_area_id).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let now = Utc::now();
+        // 受付時刻を登録順とは逆にする
+        let mut queued_order_ids = Vec::new();
+        for minutes_ago in [10, 30, 20] {
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            sqlx::query("UPDATE orders SET order_time = ? WHERE id = ?")
+                .bind(now - chrono::Duration::minutes(minutes_ago))
+                .bind(order_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            queued_order_ids.push(order_id);
+        }
+        // 他のエリアの注文と配車済みの注文はキューに入らない
+        seed_order(&pool, client_id, other_node_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let dispatched_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, dispatched_order_id, tow_truck_id).await;
+        let service = service(&pool, 60.0);
+
+        let first_page = service
+            .get_dispatcher_queue(dispatcher_id, None, 2)
+            .await
+            .unwrap();
+        let first_ids: Vec<i32> = first_page.orders.iter().map(|order| order.id).collect();
+        assert_eq!(first_ids, vec![queued_order_ids[1], queued_order_ids[2]]);
+        let cursor = first_page.next_cursor.unwrap();
+        assert_eq!(cursor.after_id, queued_order_ids[2]);
+
+        let second_page = service
+            .get_dispatcher_queue(dispatcher_id, Some((cursor.after_time, cursor.after_id)), 2)
+            .await
+            .unwrap();
+        let second_ids: Vec<i32> = second_page.orders.iter().map(|order| order.id).collect();
+        assert_eq!(second_ids, vec![queued_order_ids[0]]);
+        assert!(second_page.next_cursor.is_none());
+
+        assert!(matches!(
+            service.get_dispatcher_queue(dispatcher_id, None, 0).await,
+            Err(AppError::BadRequest)
+        ));
+        assert!(matches!(
+            service.get_dispatcher_queue(-1, None, 2).await,
+            Err(AppError::NotFound)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn bulk_complete_reports_partial_success_in_request_order() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let mut dispatched_order_ids = Vec::new();
+        for _ in 0..2 {
+            let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            seed_dispatch(&pool, order_id, tow_truck_id).await;
+            dispatched_order_ids.push(order_id);
+        }
+        let pending_order_id = seed_order(&pool, client_id, node_id).await;
+        let unknown_order_id = -1;
+        let requested = [
+            dispatched_order_ids[0],
+            pending_order_id,
+            unknown_order_id,
+            dispatched_order_ids[1],
+        ];
+
+        let result = service(&pool, 60.0)
+            .bulk_complete(&requested, Utc::now())
+            .await
+            .unwrap();
+
+        // 失敗した注文があっても残りの注文は完了する
+        assert_eq!(result.succeeded, 2);
+        assert_eq!(result.failed, 2);
+        let outcomes: Vec<(i32, Option<&str>)> = result
+            .items
+            .iter()
+            .map(|item| match item {
+                BatchItemResult::Ok { id, .. } => (*id, None),
+                BatchItemResult::Err { id, error } => (*id, Some(error.as_str())),
+            })
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (dispatched_order_ids[0], None),
+                (pending_order_id, Some("not_dispatched")),
+                (unknown_order_id, Some("not_found")),
+                (dispatched_order_ids[1], None),
+            ]
+        );
+        for order_id in dispatched_order_ids {
+            let status: String = sqlx::query_scalar("SELECT status FROM orders WHERE id = ?")
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(status, "completed");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn recent_orders_are_those_received_within_the_window() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let now = Utc::now();
+        let mut order_ids = Vec::new();
+        for minutes_ago in [10, 50] {
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            sqlx::query("UPDATE orders SET order_time = ? WHERE id = ?")
+                .bind(now - chrono::Duration::minutes(minutes_ago))
+                .bind(order_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            order_ids.push(order_id);
+        }
+        let service = service(&pool, 60.0);
+
+        let recent = service
+            .get_recent_orders(
+                AreaId(area_id),
+                chrono::Duration::minutes(30),
+                Role::Dispatcher,
+            )
+            .await
+            .unwrap();
+
+        let recent_ids: Vec<i32> = recent.iter().map(|order| order.id).collect();
+        assert_eq!(recent_ids, vec![order_ids[0]]);
+        // 基準時刻を表せない長さは panic せずに拒否する
+        assert!(matches!(
+            service
+                .get_recent_orders(
+                    AreaId(area_id),
+                    chrono::Duration::max_value(),
+                    Role::Dispatcher
+                )
+                .await,
+            Err(AppError::BadRequest)
+        ));
     }
 }
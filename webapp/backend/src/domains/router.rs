@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use crate::models::graph::Graph;
+
+// 最寄りトラックの探索方法を差し替えられるようにするためのトレイト
+pub trait Router: Debug + Send + Sync {
+    // truck_nodes のうち order_node から到達できるノードを (ノードID, 距離) の近い順に返す
+    // 同じ距離のノードは truck_nodes での並び順を保つ (重複したノードもそのまま返す)
+    fn rank(&self, graph: &Graph, order_node: i32, truck_nodes: &[i32]) -> Vec<(i32, i32)>;
+
+    // truck_nodes のうち order_node に最も近いノードと、その距離を返す
+    // 同じ距離のノードが複数ある場合は truck_nodes で先に現れるものを返す
+    fn nearest(&self, graph: &Graph, order_node: i32, truck_nodes: &[i32]) -> Option<(i32, i32)> {
+        self.rank(graph, order_node, truck_nodes).into_iter().next()
+    }
+
+    // nearest と同じ結果を返すが、order_node からの最短距離 (キャッシュ済みのもの) を使える場合に呼ぶ
+    // 既定では distances を使わずに nearest に任せる。距離をそのまま使える実装だけ上書きする
+    fn nearest_with_distances(
+        &self,
+        graph: &Graph,
+        order_node: i32,
+        distances: &HashMap<i32, i32>,
+        truck_nodes: &[i32],
+    ) -> Option<(i32, i32)> {
+        let _ = distances;
+        self.nearest(graph, order_node, truck_nodes)
+    }
+}
+
+// デフォルトの探索方法: 注文地点から1回ダイクストラ法を実行する
+#[derive(Debug, Default)]
+pub struct DijkstraRouter;
+
+impl Router for DijkstraRouter {
+    fn rank(&self, graph: &Graph, order_node: i32, truck_nodes: &[i32]) -> Vec<(i32, i32)> {
+        graph.sorted_reachable(order_node, truck_nodes)
+    }
+
+    // 注文地点からの最短距離が既にあれば、ダイクストラ法を実行し直さずに並べ替える
+    fn nearest_with_distances(
+        &self,
+        _graph: &Graph,
+        _order_node: i32,
+        distances: &HashMap<i32, i32>,
+        truck_nodes: &[i32],
+    ) -> Option<(i32, i32)> {
+        Graph::sort_reachable(distances, truck_nodes)
+            .into_iter()
+            .next()
+    }
+}
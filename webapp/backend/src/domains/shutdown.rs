@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+use crate::errors::AppError;
+
+// デプロイ時に新しい配車の受付を止め、処理中の配車が終わるのを待つための調整役
+// 停止を始めた後の配車は ServiceUnavailable で拒否し、既に始まっている配車はそのまま完了させる
+#[derive(Debug, Default)]
+pub struct ShutdownCoordinator {
+    draining: Arc<AtomicBool>,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+// 保持している間、処理中の配車として数える
+pub struct InFlightGuard<'a> {
+    coordinator: &'a ShutdownCoordinator,
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        ShutdownCoordinator::default()
+    }
+
+    pub fn begin(&self) -> Result<InFlightGuard<'_>, AppError> {
+        if self.draining.load(Ordering::SeqCst) {
+            return Err(AppError::ServiceUnavailable);
+        }
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlightGuard { coordinator: self };
+        // 件数を増やす間に停止が始まった場合は、drained が待たずに終わる可能性があるため受け付けない
+        if self.draining.load(Ordering::SeqCst) {
+            return Err(AppError::ServiceUnavailable);
+        }
+
+        Ok(guard)
+    }
+
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    // 処理中の配車がすべて終わるまで待つ
+    pub async fn drained(&self) {
+        loop {
+            let notified = self.idle.notified();
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        if self.coordinator.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.coordinator.idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[test]
+    fn rejects_new_dispatches_once_draining() {
+        let coordinator = ShutdownCoordinator::new();
+        assert!(coordinator.begin().is_ok());
+
+        coordinator.start_draining();
+
+        assert!(matches!(
+            coordinator.begin(),
+            Err(AppError::ServiceUnavailable)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn drained_waits_for_in_flight_dispatches() {
+        let coordinator = ShutdownCoordinator::new();
+        let first = coordinator.begin().unwrap();
+        let second = coordinator.begin().unwrap();
+        coordinator.start_draining();
+
+        let mut drained = Box::pin(coordinator.drained());
+        assert!((&mut drained).now_or_never().is_none());
+        drop(first);
+        assert!((&mut drained).now_or_never().is_none());
+        drop(second);
+        assert!(drained.now_or_never().is_some());
+    }
+
+    #[actix_rt::test]
+    async fn drained_returns_immediately_without_in_flight_dispatches() {
+        let coordinator = ShutdownCoordinator::new();
+        drop(coordinator.begin().unwrap());
+        coordinator.start_draining();
+
+        assert!(coordinator.drained().now_or_never().is_some());
+    }
+}
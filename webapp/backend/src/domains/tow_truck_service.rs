@@ -1,9 +1,24 @@
-use super::dto::tow_truck::TowTruckDto;
+use super::distance_cache::DistanceCache;
+use super::dto::tow_truck::{
+    DepotDistanceDto, FleetAvailabilityDto, FleetCentroidDto, LocationHistoryDto,
+    PendingAssignmentDto, PendingAssignmentResultDto, ReconciledTowTruckCountDto,
+    TowTruckCandidateDto, TowTruckCoverageDto, TowTruckDistanceDto, TowTruckDto,
+    TowTruckUtilizationDto,
+};
+use super::event_bus::{DomainEvent, EventBus};
+use super::graph_cache::GraphCache;
 use super::map_service::MapRepository;
 use super::order_service::OrderRepository;
+use super::router::Router;
 use crate::errors::AppError;
+use crate::models::area::AreaId;
 use crate::models::graph::Graph;
-use crate::models::tow_truck::TowTruck;
+use crate::models::order::{Order, OrderEvent};
+use crate::models::tow_truck::{LocationRecord, TowTruck};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::Arc;
 
 pub trait TowTruckRepository {
     async fn get_paginated_tow_trucks(
@@ -11,12 +26,111 @@ pub trait TowTruckRepository {
         page: i32,
         page_size: i32,
         status: Option<String>,
-        area_id: Option<i32>,
+        area_id: Option<AreaId>,
+        include_deleted: bool,
+        model: Option<String>,
     ) -> Result<Vec<TowTruck>, AppError>;
-    async fn update_location(&self, truck_id: i32, node_id: i32) -> Result<(), AppError>;
-    async fn update_status(&self, truck_id: i32, status: &str) -> Result<(), AppError>;
+    async fn get_all_tow_trucks(
+        &self,
+        status: Option<String>,
+        area_id: Option<AreaId>,
+        include_deleted: bool,
+        model: Option<String>,
+    ) -> Result<Vec<TowTruck>, AppError>;
+    async fn update_location(
+        &self,
+        truck_id: i32,
+        node_id: i32,
+        timestamp: DateTime<Utc>,
+    ) -> Result<bool, AppError>;
+    async fn heartbeat(&self, truck_id: i32) -> Result<(), AppError>;
+    async fn location_history(
+        &self,
+        truck_id: i32,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<LocationRecord>, AppError>;
+    async fn update_status(&self, truck_id: i32, status: &str) -> Result<bool, AppError>;
     async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError>;
     async fn find_tow_truck_by_ids(&self, ids: &[i32]) -> Result<Vec<TowTruck>, AppError>;
+    async fn available_in_area(&self, area_id: AreaId) -> Result<Vec<TowTruck>, AppError>;
+    async fn find_by_driver_username(&self, username: &str) -> Result<Vec<TowTruck>, AppError>;
+    async fn count_by_status_and_area(
+        &self,
+        area_id: AreaId,
+    ) -> Result<HashMap<String, i64>, AppError>;
+    async fn stale_busy(
+        &self,
+        area_id: Option<AreaId>,
+        stale_after_seconds: i64,
+    ) -> Result<Vec<TowTruck>, AppError>;
+    async fn free_busy_without_active_order(
+        &self,
+        area_id: Option<AreaId>,
+    ) -> Result<Vec<i32>, AppError>;
+}
+
+// 環境変数から busy のまま報告が途絶えたとみなすまでの秒数を取得し、デフォルトを600秒に設定
+pub fn stale_threshold_from_env() -> i64 {
+    env::var("TOW_TRUCK_STALE_THRESHOLD_SECONDS")
+        .unwrap_or_else(|_| "600".to_string())
+        .parse()
+        .expect("TOW_TRUCK_STALE_THRESHOLD_SECONDS must be a valid number")
+}
+
+// 起点が地図に無いと、ダイクストラ法は起点だけを返して全トラックが到達不能に見えてしまうため
+// 計算の前に確認し、ノードの欠落として NotFound を返す
+pub fn ensure_start_node(graph: &Graph, start_node_id: i32) -> Result<(), AppError> {
+    if !graph.contains_node(start_node_id) {
+        log::warn!(
+            "起点ノード {} が地図に存在しません (start node missing from graph)",
+            start_node_id
+        );
+        return Err(AppError::NotFound);
+    }
+    Ok(())
+}
+
+// 1台分の履歴 (時刻順) から、期間 [from, to) のうち busy だった秒数を求める
+// 配車から完了までを busy とし、期間の開始前に配車された場合は開始時刻から数える
+// 期間の終了時点で完了していない場合は終了時刻まで busy とみなす
+fn busy_seconds_in_window(events: &[&OrderEvent], from: DateTime<Utc>, to: DateTime<Utc>) -> i64 {
+    let mut busy_seconds = 0;
+    let mut busy_since: Option<DateTime<Utc>> = None;
+    for event in events {
+        match event.event_type.as_str() {
+            "dispatched" => {
+                busy_since.get_or_insert(event.occurred_at);
+            }
+            "completed" => {
+                if let Some(since) = busy_since.take() {
+                    let span = event.occurred_at.min(to) - since.max(from);
+                    busy_seconds += span.num_seconds().max(0);
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(since) = busy_since {
+        busy_seconds += (to - since.max(from)).num_seconds().max(0);
+    }
+
+    busy_seconds
+}
+
+// 期間 [from, to) に対する busy だった時間の割合 (%) を求める
+// 1秒未満の期間は1秒として扱い、0 での割り算 (NaN や無限大) にならないようにする
+fn utilization_percent(busy_seconds: i64, from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    let window_seconds = (to - from).num_seconds().max(1) as f64;
+    busy_seconds as f64 / window_seconds * 100.0
+}
+
+pub enum NearestTowTruck {
+    Found(TowTruckDto),
+    // エリア内に空きトラックが1台もない
+    NoAvailable,
+    // 空きトラックはいるが、どれも注文地点に到達できない
+    Unreachable { available_count: usize },
 }
 
 #[derive(Debug)]
@@ -28,6 +142,11 @@ pub struct TowTruckService<
     tow_truck_repository: T,
     order_repository: U,
     map_repository: V,
+    distance_cache: Arc<DistanceCache>,
+    graph_cache: Arc<GraphCache>,
+    router: Arc<dyn Router>,
+    event_bus: Arc<EventBus>,
+    stale_threshold_seconds: i64,
 }
 
 impl<
@@ -36,11 +155,26 @@ impl<
         V: MapRepository + std::fmt::Debug,
     > TowTruckService<T, U, V>
 {
-    pub fn new(tow_truck_repository: T, order_repository: U, map_repository: V) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        tow_truck_repository: T,
+        order_repository: U,
+        map_repository: V,
+        distance_cache: Arc<DistanceCache>,
+        graph_cache: Arc<GraphCache>,
+        router: Arc<dyn Router>,
+        event_bus: Arc<EventBus>,
+        stale_threshold_seconds: i64,
+    ) -> Self {
         TowTruckService {
             tow_truck_repository,
             order_repository,
             map_repository,
+            distance_cache,
+            graph_cache,
+            router,
+            event_bus,
+            stale_threshold_seconds,
         }
     }
 
@@ -49,132 +183,629 @@ impl<
         Ok(tow_truck.map(TowTruckDto::from_entity))
     }
 
+    // page_size を省略した場合は全件を返す
     pub async fn get_all_tow_trucks(
         &self,
         page: i32,
-        page_size: i32,
+        page_size: Option<i32>,
         status: Option<String>,
-        area: Option<i32>,
+        area: Option<AreaId>,
+        include_deleted: bool,
+        model: Option<String>,
     ) -> Result<Vec<TowTruckDto>, AppError> {
-        let tow_trucks = self
-            .tow_truck_repository
-            .get_paginated_tow_trucks(page, page_size, status, area)
-            .await?;
+        let tow_trucks = match page_size {
+            Some(page_size) => {
+                self.tow_truck_repository
+                    .get_paginated_tow_trucks(page, page_size, status, area, include_deleted, model)
+                    .await?
+            }
+            None => {
+                self.tow_truck_repository
+                    .get_all_tow_trucks(status, area, include_deleted, model)
+                    .await?
+            }
+        };
+        // 担当中の注文をまとめて取得し、トラックごとに紐付ける
+        let tow_truck_ids: Vec<i32> = tow_trucks.iter().map(|tow_truck| tow_truck.id).collect();
+        let active_order_map: HashMap<i32, i32> = self
+            .order_repository
+            .find_active_order_ids_by_tow_truck_ids(&tow_truck_ids)
+            .await?
+            .into_iter()
+            .collect();
         let tow_truck_dtos = tow_trucks
             .into_iter()
-            .map(TowTruckDto::from_entity)
+            .map(|tow_truck| {
+                let order_id = active_order_map.get(&tow_truck.id).cloned();
+                TowTruckDto {
+                    order_id,
+                    ..TowTruckDto::from_entity(tow_truck)
+                }
+            })
             .collect();
 
         Ok(tow_truck_dtos)
     }
 
-    pub async fn update_location(&self, truck_id: i32, node_id: i32) -> Result<(), AppError> {
-        self.tow_truck_repository
-            .update_location(truck_id, node_id)
+    // 指定した順にトラックを返す (存在しないIDは飛ばす)
+    // 担当中の注文もまとめて取得して紐付ける
+    pub async fn get_tow_trucks_by_ids(&self, ids: &[i32]) -> Result<Vec<TowTruckDto>, AppError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut tow_truck_map: HashMap<i32, TowTruck> = self
+            .tow_truck_repository
+            .find_tow_truck_by_ids(ids)
+            .await?
+            .into_iter()
+            .map(|tow_truck| (tow_truck.id, tow_truck))
+            .collect();
+        let found_ids: Vec<i32> = tow_truck_map.keys().cloned().collect();
+        let active_order_map: HashMap<i32, i32> = self
+            .order_repository
+            .find_active_order_ids_by_tow_truck_ids(&found_ids)
+            .await?
+            .into_iter()
+            .collect();
+
+        // 同じIDが重複して渡された場合は最初の1回だけ返す
+        Ok(ids
+            .iter()
+            .filter_map(|id| tow_truck_map.remove(id))
+            .map(|tow_truck| TowTruckDto {
+                order_id: active_order_map.get(&tow_truck.id).cloned(),
+                ..TowTruckDto::from_entity(tow_truck)
+            })
+            .collect())
+    }
+
+    // ドライバーのユーザー名から、そのドライバーが運転するトラックを探す
+    pub async fn get_tow_trucks_by_driver_username(
+        &self,
+        username: &str,
+    ) -> Result<Vec<TowTruckDto>, AppError> {
+        let tow_trucks = self
+            .tow_truck_repository
+            .find_by_driver_username(username)
             .await?;
 
-        Ok(())
+        Ok(tow_trucks
+            .into_iter()
+            .map(TowTruckDto::from_entity)
+            .collect())
     }
 
-    pub async fn get_nearest_available_tow_trucks(
+    // timestamp は位置情報を送信した時刻 (省略時は受信時刻)
+    pub async fn update_location(
         &self,
-        order_id: i32,
-    ) -> Result<Option<TowTruckDto>, AppError> {
-        let order = self.order_repository.find_order_by_id(order_id).await?;
-        let area_id = self
-            .map_repository
-            .get_area_id_by_node_id(order.node_id)
+        truck_id: i32,
+        node_id: i32,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> Result<(), AppError> {
+        let timestamp = timestamp.unwrap_or_else(Utc::now);
+        let applied = self
+            .tow_truck_repository
+            .update_location(truck_id, node_id, timestamp)
             .await?;
+        if !applied {
+            log::debug!(
+                "トラック {} の {} 時点の位置情報は古いため破棄しました",
+                truck_id,
+                timestamp
+            );
+        }
+
+        Ok(())
+    }
+
+    // busy のまま一定時間報告のないトラックを、オペレーターが確認して回収できるように一覧にする
+    pub async fn get_reclaimable_tow_trucks(
+        &self,
+        area_id: Option<AreaId>,
+    ) -> Result<Vec<TowTruckDto>, AppError> {
         let tow_trucks = self
             .tow_truck_repository
-            .get_paginated_tow_trucks(0, -1, Some("available".to_string()), Some(area_id))
+            .stale_busy(area_id, self.stale_threshold_seconds)
+            .await?;
+
+        Ok(tow_trucks
+            .into_iter()
+            .map(TowTruckDto::from_entity)
+            .collect())
+    }
+
+    // 一斉キャンセルなどで解放されずに busy のまま残った、担当中の注文が無いトラックを空きに戻す
+    pub async fn reconcile_busy_without_active_order(
+        &self,
+        area_id: AreaId,
+    ) -> Result<ReconciledTowTruckCountDto, AppError> {
+        let tow_truck_ids = self
+            .tow_truck_repository
+            .free_busy_without_active_order(Some(area_id))
+            .await?;
+        if !tow_truck_ids.is_empty() {
+            log::info!(
+                "エリア {} の担当中の注文が無い busy のトラック {:?} を空きに戻しました",
+                area_id.0,
+                tow_truck_ids
+            );
+        }
+
+        Ok(ReconciledTowTruckCountDto {
+            area_id: area_id.0,
+            freed_count: tow_truck_ids.len(),
+        })
+    }
+
+    // エリア内のトラックのうち空いている台数の割合 (%) を返す
+    // トラックが1台もいないエリアは 0% とする
+    pub async fn get_fleet_availability(
+        &self,
+        area_id: AreaId,
+    ) -> Result<FleetAvailabilityDto, AppError> {
+        let counts = self
+            .tow_truck_repository
+            .count_by_status_and_area(area_id)
+            .await?;
+        let available_count = counts.get("available").cloned().unwrap_or(0);
+        let total_count: i64 = counts.values().sum();
+        let availability = match total_count {
+            0 => 0.0,
+            total_count => available_count as f64 / total_count as f64 * 100.0,
+        };
+
+        Ok(FleetAvailabilityDto {
+            area_id: area_id.0,
+            available_count,
+            total_count,
+            availability,
+        })
+    }
+
+    // 期間 [from, to] の位置情報を古い順に返す (再生や分析用)
+    pub async fn get_location_history(
+        &self,
+        truck_id: i32,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<LocationHistoryDto>, AppError> {
+        if from > to {
+            return Err(AppError::BadRequest);
+        }
+        if self
+            .tow_truck_repository
+            .find_tow_truck_by_id(truck_id)
+            .await?
+            .is_none()
+        {
+            return Err(AppError::NotFound);
+        }
+
+        let records = self
+            .tow_truck_repository
+            .location_history(truck_id, from, to)
             .await?;
 
-        let nodes = self.map_repository.get_all_nodes(Some(area_id)).await?;
-        let edges = self.map_repository.get_all_edges(Some(area_id)).await?;
+        Ok(records
+            .into_iter()
+            .map(|record| LocationHistoryDto {
+                node_id: record.node_id,
+                timestamp: record.timestamp,
+            })
+            .collect())
+    }
 
-        let mut graph = Graph::new();
-        for node in nodes {
-            graph.add_node(node);
+    // トラックのステータスを変更し、実際に変わったかどうかを返す
+    // 既に同じステータスだった場合はイベントを発行しない
+    pub async fn update_tow_truck_status(
+        &self,
+        tow_truck_id: i32,
+        status: &str,
+    ) -> Result<bool, AppError> {
+        if status != "available" && status != "busy" {
+            return Err(AppError::BadRequest);
         }
-        for edge in edges {
-            graph.add_edge(edge);
+
+        let changed = self
+            .tow_truck_repository
+            .update_status(tow_truck_id, status)
+            .await?;
+        if !changed {
+            // 存在しないトラックと、既に同じステータスのトラックを区別する
+            if self
+                .tow_truck_repository
+                .find_tow_truck_by_id(tow_truck_id)
+                .await?
+                .is_none()
+            {
+                return Err(AppError::NotFound);
+            }
+            return Ok(false);
         }
 
-        // let sorted_tow_trucks_by_distance = {
-        //     let mut tow_trucks_with_distance: Vec<_> = tow_trucks
-        //         .into_iter()
-        //         .map(|truck| {
-        //             let distance = calculate_distance(&graph, truck.node_id, order.node_id);
-        //             (distance, truck)
-        //         })
-        //         .collect();
+        self.event_bus.publish(DomainEvent::TowTruckStatusChanged {
+            tow_truck_id,
+            status: status.to_string(),
+        });
+
+        Ok(true)
+    }
 
-        //     tow_trucks_with_distance.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-        //     tow_trucks_with_distance
-        // };
+    // 位置を変えずに生存報告だけを行う
+    pub async fn heartbeat(&self, truck_id: i32) -> Result<(), AppError> {
+        self.tow_truck_repository.heartbeat(truck_id).await?;
 
-        // let sorted_tow_trucks_by_distance = {
-        //     let mut tow_trucks_with_distance: Vec<_> = tow_trucks
-        //         .into_iter()
-        //         .map(|truck| {
-        //             let distance = calculate_distance(&graph, truck.node_id, order.node_id);
-        //             (distance, truck)
-        //         })
-        //         .collect();
+        Ok(())
+    }
 
-        //     tow_trucks_with_distance.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-        //     tow_trucks_with_distance
-        // };
+    // 注文のエリアと、そのエリアにいる空きトラックを取得する
+    async fn load_available_tow_trucks(
+        &self,
+        order: &Order,
+    ) -> Result<(AreaId, Vec<TowTruck>), AppError> {
+        // 注文のノードが削除されている場合は DB エラーではなく NotFound として扱う
+        let area_id = match self
+            .map_repository
+            .get_area_id_by_node_id(order.node_id)
+            .await
+        {
+            Ok(area_id) => area_id,
+            Err(sqlx::Error::RowNotFound) => {
+                log::warn!(
+                    "注文 {} のノード {} が見つかりません (order node missing)",
+                    order.id,
+                    order.node_id
+                );
+                return Err(AppError::NotFound);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let tow_trucks = self.tow_truck_repository.available_in_area(area_id).await?;
 
-        let nearest_tow_truck = {
-            // ダイクストラ法を使用して、order.node_id（ユーザーがいる位置）から各ノードまでの最短距離を計算
-            let distances_from_order = graph.dijkstra(order.node_id);
+        Ok((area_id, tow_trucks))
+    }
+
+    async fn load_area_graph(&self, area_id: AreaId) -> Result<Arc<Graph>, AppError> {
+        self.graph_cache.load(&self.map_repository, area_id).await
+    }
+
+    // start_node_id から各ノードまでの最短距離を返す
+    // キャッシュが有効な間はグラフの取得とダイクストラ法の計算を省略する
+    async fn distances_from(
+        &self,
+        area_id: AreaId,
+        start_node_id: i32,
+    ) -> Result<Arc<HashMap<i32, i32>>, AppError> {
+        if let Some(distances) = self.distance_cache.get(area_id, start_node_id) {
+            return Ok(distances);
+        }
+
+        let graph = self.load_area_graph(area_id).await?;
+        ensure_start_node(&graph, start_node_id)?;
+        let distances = Arc::new(graph.dijkstra(start_node_id));
+        #[cfg(feature = "routing-trace")]
+        log::debug!(
+            "dijkstra area={} start={} settle_order={:?}",
+            area_id.0,
+            start_node_id,
+            graph.dijkstra_trace(start_node_id).1
+        );
+        self.distance_cache
+            .insert(area_id, start_node_id, distances.clone());
+
+        Ok(distances)
+    }
 
-            // 最短距離とそのトラックを保持するための変数。初期値として非常に大きな距離 (10000001) を設定
-            let mut nearest_truck: Option<TowTruck> = None;
-            let mut min_distance = 10000001;
-            let mut min_truck_id = i32::MAX; // 最小IDを保持するための変数
+    // 到達可能な空きトラックのうち、最も近いものと最も遠いものを1回のダイクストラ法で求める
+    // 同じ距離のトラックが複数ある場合はどちらもトラックIDが最小のものを選ぶ
+    pub async fn get_tow_truck_coverage(
+        &self,
+        order_id: i32,
+    ) -> Result<Option<TowTruckCoverageDto>, AppError> {
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let (area_id, tow_trucks) = self.load_available_tow_trucks(&order).await?;
+        let distances_from_order = self.distances_from(area_id, order.node_id).await?;
 
-            for truck in tow_trucks {
-                // トラックの位置 (truck.node_id) までの最短距離を取得
-                let distance = distances_from_order
+        let reachable_tow_trucks: Vec<(i32, TowTruck)> = tow_trucks
+            .into_iter()
+            .filter_map(|truck| {
+                distances_from_order
                     .get(&truck.node_id)
-                    .cloned()
-                    .unwrap_or(10000001);
+                    .map(|&distance| (distance, truck))
+            })
+            .collect();
+
+        let nearest = reachable_tow_trucks
+            .iter()
+            .min_by_key(|(distance, truck)| (*distance, truck.id));
+        let furthest = reachable_tow_trucks
+            .iter()
+            .min_by_key(|(distance, truck)| (Reverse(*distance), truck.id));
+
+        match (nearest, furthest) {
+            (
+                Some((nearest_distance, nearest_truck)),
+                Some((furthest_distance, furthest_truck)),
+            ) => Ok(Some(TowTruckCoverageDto {
+                nearest: TowTruckDistanceDto {
+                    tow_truck: TowTruckDto::from_entity(nearest_truck.clone()),
+                    distance: *nearest_distance,
+                },
+                furthest: TowTruckDistanceDto {
+                    tow_truck: TowTruckDto::from_entity(furthest_truck.clone()),
+                    distance: *furthest_distance,
+                },
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    // 配車画面のプレビュー用に、エリア内の空きトラックすべてを注文地点までの距離付きで返す
+    // 距離の近い順 (同じ距離ならトラックIDが小さい順) に並べ、到達できないトラックは末尾に置く
+    pub async fn candidates_for_order(
+        &self,
+        order_id: i32,
+    ) -> Result<Vec<TowTruckCandidateDto>, AppError> {
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let (area_id, tow_trucks) = self.load_available_tow_trucks(&order).await?;
+        let distances_from_order = self.distances_from(area_id, order.node_id).await?;
+
+        let mut candidates: Vec<TowTruckCandidateDto> = tow_trucks
+            .into_iter()
+            .map(|truck| TowTruckCandidateDto {
+                distance: distances_from_order.get(&truck.node_id).cloned(),
+                tow_truck: TowTruckDto::from_entity(truck),
+            })
+            .collect();
+        candidates.sort_by_key(|candidate| {
+            (
+                candidate.distance.is_none(),
+                candidate.distance,
+                candidate.tow_truck.id,
+            )
+        });
+
+        Ok(candidates)
+    }
+
+    pub async fn get_nearest_available_tow_trucks(
+        &self,
+        order_id: i32,
+    ) -> Result<NearestTowTruck, AppError> {
+        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let (area_id, tow_trucks) = self.load_available_tow_trucks(&order).await?;
+        let available_count = tow_trucks.len();
+        if available_count == 0 {
+            return Ok(NearestTowTruck::NoAvailable);
+        }
+
+        let nearest_tow_truck = {
+            // 総コスト (トラック -> 引き取り地点 -> 搬送先) のうち、搬送区間 (引き取り地点 -> 搬送先) の
+            // 距離はどのトラックでも同じなので、総コストが最小のトラックは引き取り地点までの距離が
+            // 最小のトラックと常に一致する。そのため搬送区間はトラックの比較には加えず、
+            // 搬送先が指定されている場合に引き取り地点から到達できるかの確認にだけ使う
+            if let Some(destination_node_id) = order.destination_node_id {
+                let distances_from_order = self.distances_from(area_id, order.node_id).await?;
+                if !distances_from_order.contains_key(&destination_node_id) {
+                    // 搬送先に到達できない場合はどのトラックでも引き受けられない
+                    return Ok(NearestTowTruck::Unreachable { available_count });
+                }
+            }
+
+            // 選択規則:
+            // - 到達できないトラック (距離が求まらないもの) は候補から外す
+            // - 引き取り地点までの距離 (上記の理由で総コストと同じ順序になる) が最小のトラックを選ぶ
+            // - 同じコストのトラックが複数ある場合はトラックIDが最小のものを選ぶ
+            // トラックIDは一意なので、この規則で選ばれるトラックは常に1台に定まる
+            // 候補をトラックIDの昇順で渡し、Router が同じ距離なら先に現れるノードを返すことで
+            // 最後の規則を満たす。注文地点にいるトラックは距離0の候補になる
+            // 注文地点からの距離はキャッシュしたものを Router に渡し、ダイクストラ法の再計算を省く
+            let distances_from_order = self.distances_from(area_id, order.node_id).await?;
+            let graph = self.load_area_graph(area_id).await?;
+            let mut candidates: Vec<TowTruck> = tow_trucks
+                .into_iter()
+                // 地図に存在しないノードにいるトラック (位置データの不整合) は候補にしない
+                .filter(|truck| graph.contains_node(truck.node_id))
+                .collect();
+            candidates.sort_by_key(|truck| truck.id);
+            let truck_nodes: Vec<i32> = candidates.iter().map(|truck| truck.node_id).collect();
+
+            self.router
+                .nearest_with_distances(&graph, order.node_id, &distances_from_order, &truck_nodes)
+                .and_then(|(node_id, _)| {
+                    candidates
+                        .into_iter()
+                        .find(|truck| truck.node_id == node_id)
+                })
+        };
+
+        // 空きトラックはいるが、地図上で注文地点とつながっていない場合は区別して返す
+        match nearest_tow_truck {
+            Some(truck) => Ok(NearestTowTruck::Found(TowTruckDto::from_entity(truck))),
+            None => Ok(NearestTowTruck::Unreachable { available_count }),
+        }
+    }
+
+    // エリア内の未配車の注文に空きトラックを割り当てる案を作る (DB は更新しない)
+    // 空きトラックのいるノードを起点にダイクストラ法を1回だけ実行し、各注文の最寄りのトラックを求める
+    // 距離の短い注文から順に割り当て、最寄りのノードのトラックが既に他の注文に割り当てられていれば
+    // その注文は未割り当てとして返す (次の呼び出しで改めて割り当てる)
+    pub async fn assign_pending_in_area(
+        &self,
+        area_id: AreaId,
+    ) -> Result<PendingAssignmentResultDto, AppError> {
+        let orders = self.order_repository.pending_in_area(area_id).await?;
+        let tow_trucks = self.tow_truck_repository.available_in_area(area_id).await?;
+        let graph = self.load_area_graph(area_id).await?;
+
+        // ノードごとに、そこにいるトラックをIDの昇順で割り当てられるようにする
+        let mut tow_trucks_by_node: HashMap<i32, Vec<i32>> = HashMap::new();
+        for truck in tow_trucks
+            .iter()
+            .filter(|truck| graph.contains_node(truck.node_id))
+        {
+            tow_trucks_by_node
+                .entry(truck.node_id)
+                .or_default()
+                .push(truck.id);
+        }
+        for truck_ids in tow_trucks_by_node.values_mut() {
+            truck_ids.sort_unstable_by_key(|&id| Reverse(id));
+        }
+        let mut source_node_ids: Vec<i32> = tow_trucks_by_node.keys().cloned().collect();
+        source_node_ids.sort_unstable();
+
+        let nearest = graph.multi_source_dijkstra(&source_node_ids);
 
-                // 現在の距離が min_distance より小さい場合、または同じ距離でトラックのIDが小さい場合に更新
-                if distance < min_distance
-                    || (distance == min_distance && truck.node_id < min_truck_id)
-                {
-                    min_distance = distance;
-                    min_truck_id = truck.node_id; // IDも更新
-                    nearest_truck = Some(truck);
+        let mut unassigned_order_ids = Vec::new();
+        let mut candidates = Vec::new();
+        for (index, order) in orders.iter().enumerate() {
+            match nearest.get(&order.node_id) {
+                Some(&(distance, source_node_id)) => {
+                    candidates.push((distance, index, order.id, source_node_id))
                 }
+                None => unassigned_order_ids.push(order.id),
             }
+        }
+        // 同じ距離なら受付の早い注文を優先する
+        candidates.sort_unstable();
 
-            // 最短距離が初期値のままかどうかをチェック
-            if min_distance == 10000001 {
-                None
-            } else {
-                nearest_truck
+        let mut assignments = Vec::new();
+        for (distance, _, order_id, source_node_id) in candidates {
+            match tow_trucks_by_node
+                .get_mut(&source_node_id)
+                .and_then(|truck_ids| truck_ids.pop())
+            {
+                Some(tow_truck_id) => assignments.push(PendingAssignmentDto {
+                    order_id,
+                    tow_truck_id,
+                    distance,
+                }),
+                None => unassigned_order_ids.push(order_id),
             }
+        }
+
+        Ok(PendingAssignmentResultDto {
+            assignments,
+            unassigned_order_ids,
+        })
+    }
+
+    // エリア内のトラックごとに、期間 [from, to) のうち busy だった時間の割合を返す
+    // 配車・完了の履歴が1件もないトラックは含めない
+    pub async fn get_utilization(
+        &self,
+        area_id: AreaId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TowTruckUtilizationDto>, AppError> {
+        if from >= to {
+            return Err(AppError::BadRequest);
+        }
+
+        let events = self
+            .order_repository
+            .order_events_before(area_id, to)
+            .await?;
+        let mut events_by_tow_truck: BTreeMap<i32, Vec<&OrderEvent>> = BTreeMap::new();
+        for event in &events {
+            events_by_tow_truck
+                .entry(event.tow_truck_id)
+                .or_default()
+                .push(event);
+        }
+
+        Ok(events_by_tow_truck
+            .into_iter()
+            .map(|(tow_truck_id, events)| {
+                let busy_seconds = busy_seconds_in_window(&events, from, to);
+                TowTruckUtilizationDto {
+                    tow_truck_id,
+                    busy_seconds,
+                    utilization: utilization_percent(busy_seconds, from, to),
+                }
+            })
+            .collect())
+    }
+
+    // 完了後に戻る拠点として、トラックの現在地から最も近い拠点を返す
+    // 同じ距離の拠点が複数ある場合はノードIDが最小のものを選ぶ
+    pub async fn nearest_depot(&self, truck_id: i32) -> Result<Option<DepotDistanceDto>, AppError> {
+        let tow_truck = match self
+            .tow_truck_repository
+            .find_tow_truck_by_id(truck_id)
+            .await?
+        {
+            Some(tow_truck) => tow_truck,
+            None => return Err(AppError::NotFound),
         };
+        let area_id = AreaId::from(tow_truck.area_id);
+        let depot_node_ids = self.map_repository.get_depot_node_ids(area_id).await?;
+        if depot_node_ids.is_empty() {
+            return Ok(None);
+        }
+
+        let distances = self.distances_from(area_id, tow_truck.node_id).await?;
+
+        Ok(depot_node_ids
+            .into_iter()
+            .filter_map(|node_id| distances.get(&node_id).map(|&distance| (distance, node_id)))
+            .min()
+            .map(|(distance, node_id)| DepotDistanceDto { node_id, distance }))
+    }
+
+    // エリア内の空きトラックに対する中心ノード (1-median の近似) を求める
+    // 候補はトラックがいるノードに限定し、到達できるトラックが最も多いもの、
+    // 次に総距離が最小のもの、最後にノードIDが最小のものを選ぶ
+    pub async fn get_fleet_centroid(
+        &self,
+        area_id: AreaId,
+    ) -> Result<Option<FleetCentroidDto>, AppError> {
+        let tow_trucks = self.tow_truck_repository.available_in_area(area_id).await?;
+
+        let mut candidate_node_ids: Vec<i32> =
+            tow_trucks.iter().map(|truck| truck.node_id).collect();
+        candidate_node_ids.sort_unstable();
+        candidate_node_ids.dedup();
 
-        if let Some(truck) = nearest_tow_truck {
-            Ok(Some(TowTruckDto::from_entity(truck)))
-        } else {
-            Ok(None)
+        let mut centroid: Option<FleetCentroidDto> = None;
+        for node_id in candidate_node_ids {
+            let distances = self.distances_from(area_id, node_id).await?;
+            let reachable_distances: Vec<i32> = tow_trucks
+                .iter()
+                .filter_map(|truck| distances.get(&truck.node_id).cloned())
+                .collect();
+            let candidate = FleetCentroidDto {
+                node_id,
+                total_distance: reachable_distances.iter().sum(),
+                tow_truck_count: reachable_distances.len(),
+            };
+
+            let is_better = match &centroid {
+                None => true,
+                Some(current) => {
+                    (Reverse(candidate.tow_truck_count), candidate.total_distance)
+                        < (Reverse(current.tow_truck_count), current.total_distance)
+                }
+            };
+            if is_better {
+                centroid = Some(candidate);
+            }
         }
+
+        Ok(centroid)
     }
 }
 
-// fn calculate_distance(graph: &Graph, node_id_1: i32, node_id_2: i32) -> i32 {
-//     graph.shortest_path(node_id_1, node_id_2)
-// }
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
 
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
+// テストで dijkstra が実行された回数を数え、距離のキャッシュが再計算を省いていることを確かめる
+#[cfg(test)]
+thread_local! {
+    static DIJKSTRA_RUNS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
 
 #[derive(Debug, Eq, PartialEq)]
 struct State {
@@ -197,7 +828,11 @@ impl PartialOrd for State {
 }
 
 impl Graph {
+    // 戻り値には start_node_id 自身 (距離0) が必ず含まれる
+    // 辺を持たないノード (1ノードだけのエリアなど) から始めた場合は start_node_id のみを返す
     pub fn dijkstra(&self, start_node_id: i32) -> HashMap<i32, i32> {
+        #[cfg(test)]
+        DIJKSTRA_RUNS.with(|runs| runs.set(runs.get() + 1));
         let mut distances: HashMap<i32, i32> = HashMap::new();
         let mut heap = BinaryHeap::new();
 
@@ -222,14 +857,15 @@ impl Graph {
                 for edge in edges {
                     let next = State {
                         node_id: edge.node_b_id,
-                        cost: cost + edge.weight,
+                        cost: cost.saturating_add(edge.weight),
                     };
-
-                    let current_distance =
-                        distances.get(&next.node_id).cloned().unwrap_or(i32::MAX);
-
                     // より短い経路が見つかったら更新
-                    if next.cost < current_distance {
+                    // 距離の上限 (i32::MAX) に達した経路も到達できるものとして扱う
+                    let is_shorter = match distances.get(&next.node_id) {
+                        Some(&current_distance) => next.cost < current_distance,
+                        None => true,
+                    };
+                    if is_shorter {
                         distances.insert(next.node_id, next.cost);
                         heap.push(next);
                     }
@@ -239,14 +875,1303 @@ impl Graph {
 
         distances
     }
-}
 
-// 2つのノード間の最短距離を求める関数
-fn calculate_distance(graph: &Graph, node_id_1: i32, node_id_2: i32) -> i32 {
-    let distances_from_node_1 = graph.dijkstra(node_id_1);
-    // node_id_2 までの距離を取得し、なければ i32::MAX を返す
-    distances_from_node_1
-        .get(&node_id_2)
-        .cloned()
-        .unwrap_or(i32::MAX)
+    // start_node_id から target_node_id までの最短距離だけを求める (到達できない場合は None)
+    // target_node_id が確定した時点で探索を打ち切るため、全ノードを求める dijkstra より速い
+    pub fn distance_to(&self, start_node_id: i32, target_node_id: i32) -> Option<i32> {
+        let mut distances: HashMap<i32, i32> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+
+        distances.insert(start_node_id, 0);
+        heap.push(State {
+            node_id: start_node_id,
+            cost: 0,
+        });
+
+        while let Some(State { node_id, cost }) = heap.pop() {
+            if let Some(&current_cost) = distances.get(&node_id) {
+                if cost > current_cost {
+                    continue;
+                }
+            }
+            // ヒープから取り出した時点で、そのノードまでの距離は確定している
+            if node_id == target_node_id {
+                return Some(cost);
+            }
+
+            if let Some(edges) = self.edges.get(&node_id) {
+                for edge in edges {
+                    let next = State {
+                        node_id: edge.node_b_id,
+                        cost: cost.saturating_add(edge.weight),
+                    };
+                    let is_shorter = match distances.get(&next.node_id) {
+                        Some(&current_distance) => next.cost < current_distance,
+                        None => true,
+                    };
+                    if is_shorter {
+                        distances.insert(next.node_id, next.cost);
+                        heap.push(next);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    // デバッグ用: dijkstra と同じ計算を行い、ノードが確定した順序も返す
+    // 本番の経路探索を遅くしないよう、routing-trace フィーチャーを有効にしたときだけ組み込む
+    #[cfg(feature = "routing-trace")]
+    pub fn dijkstra_trace(&self, start_node_id: i32) -> (HashMap<i32, i32>, Vec<i32>) {
+        let mut distances: HashMap<i32, i32> = HashMap::new();
+        let mut settle_order = Vec::new();
+        let mut heap = BinaryHeap::new();
+
+        distances.insert(start_node_id, 0);
+        heap.push(State {
+            node_id: start_node_id,
+            cost: 0,
+        });
+
+        while let Some(State { node_id, cost }) = heap.pop() {
+            if let Some(&current_cost) = distances.get(&node_id) {
+                if cost > current_cost {
+                    continue;
+                }
+            }
+            // より短い距離でしか再投入しないため、ここに来た時点でこのノードの距離は確定している
+            settle_order.push(node_id);
+
+            if let Some(edges) = self.edges.get(&node_id) {
+                for edge in edges {
+                    let next = State {
+                        node_id: edge.node_b_id,
+                        cost: cost.saturating_add(edge.weight),
+                    };
+                    let is_shorter = match distances.get(&next.node_id) {
+                        Some(&current_distance) => next.cost < current_distance,
+                        None => true,
+                    };
+                    if is_shorter {
+                        distances.insert(next.node_id, next.cost);
+                        heap.push(next);
+                    }
+                }
+            }
+        }
+
+        (distances, settle_order)
+    }
+
+    // start_node_id からの最短距離が radius 以下のノードと、その距離を返す
+    // radius を超えた経路はそれ以上たどらないため、グラフ全体を探索するより速い
+    pub fn nodes_within(&self, start_node_id: i32, radius: i32) -> HashMap<i32, i32> {
+        let mut distances: HashMap<i32, i32> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+
+        distances.insert(start_node_id, 0);
+        heap.push(State {
+            node_id: start_node_id,
+            cost: 0,
+        });
+
+        while let Some(State { node_id, cost }) = heap.pop() {
+            if let Some(&current_cost) = distances.get(&node_id) {
+                if cost > current_cost {
+                    continue;
+                }
+            }
+
+            if let Some(edges) = self.edges.get(&node_id) {
+                for edge in edges {
+                    let next = State {
+                        node_id: edge.node_b_id,
+                        cost: cost.saturating_add(edge.weight),
+                    };
+                    if next.cost > radius {
+                        continue;
+                    }
+                    let is_shorter = match distances.get(&next.node_id) {
+                        Some(&current_distance) => next.cost < current_distance,
+                        None => true,
+                    };
+                    if is_shorter {
+                        distances.insert(next.node_id, next.cost);
+                        heap.push(next);
+                    }
+                }
+            }
+        }
+
+        distances
+    }
+
+    // candidate_nodes のうち start_node_id から到達できるものを (ノードID, 距離) の近い順に返す
+    // 同じ距離のノードは candidate_nodes での並び順を保つ (重複したノードもそのまま返す)
+    pub fn sorted_reachable(&self, start_node_id: i32, candidate_nodes: &[i32]) -> Vec<(i32, i32)> {
+        Self::sort_reachable(&self.dijkstra(start_node_id), candidate_nodes)
+    }
+
+    // sorted_reachable と同じ並べ替えを、計算済みの最短距離 (dijkstra の戻り値) から行う
+    pub fn sort_reachable(
+        distances: &HashMap<i32, i32>,
+        candidate_nodes: &[i32],
+    ) -> Vec<(i32, i32)> {
+        let mut reachable: Vec<(i32, i32)> = candidate_nodes
+            .iter()
+            .filter_map(|node_id| distances.get(node_id).map(|&distance| (*node_id, distance)))
+            .collect();
+        reachable.sort_by_key(|&(_, distance)| distance);
+
+        reachable
+    }
+
+    // a から b と b から a の両方向に経路があるか (往復できるか) を判定する
+    // add_edge で作ったグラフは常に双方向だが、辺を直接組み立てた一方通行のグラフでは片方向だけのことがある
+    pub fn mutually_reachable(&self, a: i32, b: i32) -> bool {
+        self.distance_to(a, b).is_some() && self.distance_to(b, a).is_some()
+    }
+
+    // node_id から到達できる他のノードまでの最短距離の最大値 (離心率) を返す
+    // 拠点の配置検討に使い、他のどのノードにも到達できない場合は None を返す
+    pub fn eccentricity(&self, node_id: i32) -> Option<i32> {
+        self.dijkstra(node_id)
+            .into_iter()
+            .filter(|&(other_node_id, _)| other_node_id != node_id)
+            .map(|(_, distance)| distance)
+            .max()
+    }
+
+    // 複数の起点から同時にダイクストラ法を実行し、各ノードについて (最短距離, 最寄りの起点) を返す
+    // 同じ距離の起点が複数ある場合はノードIDが最小の起点を選ぶ
+    pub fn multi_source_dijkstra(&self, source_node_ids: &[i32]) -> HashMap<i32, (i32, i32)> {
+        let mut nearest: HashMap<i32, (i32, i32)> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+
+        for &source_node_id in source_node_ids {
+            nearest.insert(source_node_id, (0, source_node_id));
+            heap.push(Reverse((0, source_node_id, source_node_id)));
+        }
+
+        while let Some(Reverse((cost, source_node_id, node_id))) = heap.pop() {
+            if let Some(&current) = nearest.get(&node_id) {
+                if (cost, source_node_id) > current {
+                    continue;
+                }
+            }
+
+            if let Some(edges) = self.edges.get(&node_id) {
+                for edge in edges {
+                    let next = (cost.saturating_add(edge.weight), source_node_id);
+                    let is_nearer = match nearest.get(&edge.node_b_id) {
+                        Some(&current) => next < current,
+                        None => true,
+                    };
+                    if is_nearer {
+                        nearest.insert(edge.node_b_id, next);
+                        heap.push(Reverse((next.0, next.1, edge.node_b_id)));
+                    }
+                }
+            }
+        }
+
+        nearest
+    }
+
+    // start_node_id から goal_node_id までの最短経路をノードIDの列で返す (到達できなければ None)
+    pub fn shortest_path_nodes(&self, start_node_id: i32, goal_node_id: i32) -> Option<Vec<i32>> {
+        let mut distances: HashMap<i32, i32> = HashMap::new();
+        let mut predecessors: HashMap<i32, i32> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+
+        distances.insert(start_node_id, 0);
+        heap.push(State {
+            node_id: start_node_id,
+            cost: 0,
+        });
+
+        while let Some(State { node_id, cost }) = heap.pop() {
+            if node_id == goal_node_id {
+                break;
+            }
+            if let Some(&current_cost) = distances.get(&node_id) {
+                if cost > current_cost {
+                    continue;
+                }
+            }
+
+            if let Some(edges) = self.edges.get(&node_id) {
+                for edge in edges {
+                    let next = State {
+                        node_id: edge.node_b_id,
+                        cost: cost.saturating_add(edge.weight),
+                    };
+                    let is_shorter = match distances.get(&next.node_id) {
+                        Some(&current_distance) => next.cost < current_distance,
+                        None => true,
+                    };
+                    if is_shorter {
+                        distances.insert(next.node_id, next.cost);
+                        predecessors.insert(next.node_id, node_id);
+                        heap.push(next);
+                    }
+                }
+            }
+        }
+
+        if !distances.contains_key(&goal_node_id) {
+            return None;
+        }
+
+        // ゴールから直前のノードをたどって経路を復元する
+        let mut path = vec![goal_node_id];
+        let mut current = goal_node_id;
+        while let Some(&previous) = predecessors.get(&current) {
+            path.push(previous);
+            current = previous;
+        }
+        path.reverse();
+
+        Some(path)
+    }
+}
+
+// 2つのノード間の最短距離を求める関数
+#[allow(dead_code)]
+fn calculate_distance(graph: &Graph, node_id_1: i32, node_id_2: i32) -> i32 {
+    let distances_from_node_1 = graph.dijkstra(node_id_1);
+    // node_id_2 までの距離を取得し、なければ i32::MAX を返す
+    distances_from_node_1
+        .get(&node_id_2)
+        .cloned()
+        .unwrap_or(i32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domains::map_service::MapService;
+    use crate::domains::router::DijkstraRouter;
+    use crate::repositories::map_repository::MapRepositoryImpl;
+    use crate::repositories::order_repository::OrderRepositoryImpl;
+    use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
+    use crate::testsupport::{
+        circuit_breaker, seed_area, seed_dispatch, seed_edge, seed_node, seed_order, seed_truck,
+        seed_user, test_pool,
+    };
+    use chrono::TimeZone;
+    use sqlx::mysql::MySqlPool;
+    use std::time::Duration;
+
+    type TestTowTruckService =
+        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>;
+
+    fn service(pool: &MySqlPool) -> TestTowTruckService {
+        service_with_caches(
+            pool,
+            Arc::new(DistanceCache::new(Duration::from_secs(1))),
+            Arc::new(GraphCache::new(None)),
+        )
+    }
+
+    fn service_with_caches(
+        pool: &MySqlPool,
+        distance_cache: Arc<DistanceCache>,
+        graph_cache: Arc<GraphCache>,
+    ) -> TestTowTruckService {
+        service_with_parts(
+            pool,
+            distance_cache,
+            graph_cache,
+            Arc::new(DijkstraRouter),
+            Arc::new(EventBus::new()),
+        )
+    }
+
+    fn service_with_parts(
+        pool: &MySqlPool,
+        distance_cache: Arc<DistanceCache>,
+        graph_cache: Arc<GraphCache>,
+        router: Arc<dyn Router>,
+        event_bus: Arc<EventBus>,
+    ) -> TestTowTruckService {
+        TowTruckService::new(
+            TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker()),
+            OrderRepositoryImpl::new(pool.clone(), circuit_breaker()),
+            MapRepositoryImpl::new(pool.clone(), circuit_breaker()),
+            distance_cache,
+            graph_cache,
+            router,
+            event_bus,
+            600,
+        )
+    }
+
+    // 距離に関係なく、候補のうち最後に渡されたノードを選ぶ Router
+    #[derive(Debug)]
+    struct LastCandidateRouter;
+
+    impl Router for LastCandidateRouter {
+        fn rank(&self, _graph: &Graph, _order_node: i32, truck_nodes: &[i32]) -> Vec<(i32, i32)> {
+            truck_nodes
+                .iter()
+                .rev()
+                .map(|&node_id| (node_id, 0))
+                .collect()
+        }
+    }
+
+    fn dijkstra_runs() -> usize {
+        DIJKSTRA_RUNS.with(|runs| runs.get())
+    }
+
+    // 2030-01-01 の hour 時 minute 分
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2030, 1, 1, hour, minute, 0).unwrap()
+    }
+
+    fn event(event_type: &str, occurred_at: DateTime<Utc>) -> OrderEvent {
+        OrderEvent {
+            tow_truck_id: 1,
+            event_type: event_type.to_string(),
+            occurred_at,
+        }
+    }
+
+    fn busy_seconds(events: &[OrderEvent]) -> i64 {
+        let events: Vec<&OrderEvent> = events.iter().collect();
+        busy_seconds_in_window(&events, at(10, 0), at(11, 0))
+    }
+
+    #[test]
+    fn busy_seconds_sum_jobs_inside_the_window() {
+        let events = [
+            event("dispatched", at(10, 10)),
+            event("completed", at(10, 40)),
+            event("dispatched", at(10, 45)),
+            event("completed", at(10, 50)),
+        ];
+
+        assert_eq!(busy_seconds(&events), 35 * 60);
+    }
+
+    #[test]
+    fn busy_seconds_clip_jobs_to_the_window() {
+        // 期間前に配車された分は開始時刻から、完了していない分は終了時刻まで数える
+        let events = [
+            event("dispatched", at(9, 50)),
+            event("completed", at(10, 5)),
+            event("dispatched", at(10, 50)),
+        ];
+        assert_eq!(busy_seconds(&events), 15 * 60);
+
+        let after_window = [
+            event("dispatched", at(10, 50)),
+            event("completed", at(11, 30)),
+        ];
+        assert_eq!(busy_seconds(&after_window), 10 * 60);
+    }
+
+    #[test]
+    fn busy_seconds_ignore_jobs_outside_the_window_and_unmatched_completions() {
+        let events = [
+            event("dispatched", at(9, 0)),
+            event("completed", at(9, 30)),
+            event("completed", at(10, 20)),
+        ];
+
+        assert_eq!(busy_seconds(&events), 0);
+        assert_eq!(busy_seconds(&[]), 0);
+    }
+
+    #[test]
+    fn busy_seconds_count_from_the_first_of_repeated_dispatches() {
+        let events = [
+            event("dispatched", at(10, 0)),
+            event("dispatched", at(10, 30)),
+            event("completed", at(10, 40)),
+        ];
+
+        assert_eq!(busy_seconds(&events), 40 * 60);
+    }
+
+    #[test]
+    fn utilization_is_finite_for_empty_and_sub_second_windows() {
+        assert_eq!(utilization_percent(30 * 60, at(10, 0), at(11, 0)), 50.0);
+        // 1秒未満の期間でも 0 で割らない
+        let sub_second = at(10, 0) + chrono::Duration::milliseconds(500);
+        for busy_seconds in [0, 1] {
+            let utilization = utilization_percent(busy_seconds, at(10, 0), sub_second);
+            assert!(utilization.is_finite());
+        }
+        assert_eq!(utilization_percent(0, at(10, 0), sub_second), 0.0);
+        assert_eq!(utilization_percent(0, at(10, 0), at(10, 0)), 0.0);
+    }
+
+    #[actix_rt::test]
+    async fn nearest_available_tow_truck_is_the_closest_by_path() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 注文地点 0 - 1 - 2 - 3 (重み 1, 1, 5) の一本道で、トラックはノード 2 と 3 にいる
+        let area_id = seed_area(&pool).await;
+        let mut node_ids = Vec::new();
+        for _ in 0..4 {
+            node_ids.push(seed_node(&pool, area_id).await);
+        }
+        seed_edge(&pool, node_ids[0], node_ids[1], 1).await;
+        seed_edge(&pool, node_ids[1], node_ids[2], 1).await;
+        seed_edge(&pool, node_ids[2], node_ids[3], 5).await;
+        let far_driver_id = seed_user(&pool, "driver").await;
+        let near_driver_id = seed_user(&pool, "driver").await;
+        seed_truck(&pool, far_driver_id, area_id, node_ids[3]).await;
+        let near_tow_truck_id = seed_truck(&pool, near_driver_id, area_id, node_ids[2]).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_ids[0]).await;
+
+        let nearest = service(&pool)
+            .get_nearest_available_tow_trucks(order_id)
+            .await
+            .unwrap();
+
+        match nearest {
+            NearestTowTruck::Found(tow_truck) => assert_eq!(tow_truck.id, near_tow_truck_id),
+            _ => panic!("expected the tow truck two hops away to be found"),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn nearest_available_tow_truck_reuses_cached_distances() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let order_node_id = seed_node(&pool, area_id).await;
+        let truck_node_id = seed_node(&pool, area_id).await;
+        seed_edge(&pool, order_node_id, truck_node_id, 3).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, truck_node_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, order_node_id).await;
+        let service = service_with_caches(
+            &pool,
+            Arc::new(DistanceCache::new(Duration::from_secs(60))),
+            Arc::new(GraphCache::new(None)),
+        );
+
+        let runs_before = dijkstra_runs();
+        for _ in 0..3 {
+            match service.get_nearest_available_tow_trucks(order_id).await {
+                Ok(NearestTowTruck::Found(tow_truck)) => assert_eq!(tow_truck.id, tow_truck_id),
+                _ => panic!("expected the tow truck to be found"),
+            }
+        }
+
+        // 2回目以降はキャッシュした距離を Router に渡すので、ダイクストラ法は1回だけ実行される
+        assert_eq!(dijkstra_runs() - runs_before, 1);
+    }
+
+    #[actix_rt::test]
+    async fn nearest_available_tow_truck_is_chosen_by_the_injected_router() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 注文地点 0 - 1 - 2 の一本道で、Dijkstra なら近いノード 1 のトラックが選ばれる
+        let area_id = seed_area(&pool).await;
+        let mut node_ids = Vec::new();
+        for _ in 0..3 {
+            node_ids.push(seed_node(&pool, area_id).await);
+        }
+        seed_edge(&pool, node_ids[0], node_ids[1], 1).await;
+        seed_edge(&pool, node_ids[1], node_ids[2], 1).await;
+        let near_driver_id = seed_user(&pool, "driver").await;
+        let far_driver_id = seed_user(&pool, "driver").await;
+        seed_truck(&pool, near_driver_id, area_id, node_ids[1]).await;
+        let far_tow_truck_id = seed_truck(&pool, far_driver_id, area_id, node_ids[2]).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_ids[0]).await;
+        let service = service_with_parts(
+            &pool,
+            Arc::new(DistanceCache::new(Duration::from_secs(1))),
+            Arc::new(GraphCache::new(None)),
+            Arc::new(LastCandidateRouter),
+            Arc::new(EventBus::new()),
+        );
+
+        // 候補はトラックIDの昇順で渡されるので、差し替えた Router は後から登録したトラックを選ぶ
+        match service.get_nearest_available_tow_trucks(order_id).await {
+            Ok(NearestTowTruck::Found(tow_truck)) => assert_eq!(tow_truck.id, far_tow_truck_id),
+            _ => panic!("expected the tow truck chosen by the router to be found"),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn pending_assignment_matches_the_per_order_nearest_search_with_one_dijkstra() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 0 - 1 - 2 - 3 - 4 (重みはすべて 1) の一本道で、トラックはノード 0 と 4、注文はノード 1 と 3 にある
+        let area_id = seed_area(&pool).await;
+        let mut node_ids = Vec::new();
+        for _ in 0..5 {
+            node_ids.push(seed_node(&pool, area_id).await);
+        }
+        for pair in node_ids.windows(2) {
+            seed_edge(&pool, pair[0], pair[1], 1).await;
+        }
+        let west_driver_id = seed_user(&pool, "driver").await;
+        let east_driver_id = seed_user(&pool, "driver").await;
+        seed_truck(&pool, west_driver_id, area_id, node_ids[0]).await;
+        seed_truck(&pool, east_driver_id, area_id, node_ids[4]).await;
+        let client_id = seed_user(&pool, "client").await;
+        let west_order_id = seed_order(&pool, client_id, node_ids[1]).await;
+        let east_order_id = seed_order(&pool, client_id, node_ids[3]).await;
+        let service = service(&pool);
+
+        let runs_before = dijkstra_runs();
+        let result = service
+            .assign_pending_in_area(AreaId(area_id))
+            .await
+            .unwrap();
+        // 注文がいくつあっても多始点のダイクストラ法1回で割り当てる
+        assert_eq!(dijkstra_runs() - runs_before, 1);
+
+        assert!(result.unassigned_order_ids.is_empty());
+        assert_eq!(result.assignments.len(), 2);
+        // 注文ごとに最寄りのトラックを探した結果と同じトラックが割り当てられる
+        for order_id in [west_order_id, east_order_id] {
+            let assignment = result
+                .assignments
+                .iter()
+                .find(|assignment| assignment.order_id == order_id)
+                .unwrap();
+            assert_eq!(assignment.distance, 1);
+            match service.get_nearest_available_tow_trucks(order_id).await {
+                Ok(NearestTowTruck::Found(tow_truck)) => {
+                    assert_eq!(assignment.tow_truck_id, tow_truck.id)
+                }
+                _ => panic!("expected a nearest tow truck for order {}", order_id),
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn nearest_available_tow_truck_is_not_found_when_the_order_node_was_deleted() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let order_node_id = seed_node(&pool, area_id).await;
+        let truck_node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        seed_truck(&pool, driver_id, area_id, truck_node_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, order_node_id).await;
+        // 外部キーの CASCADE で注文ごと消えないよう、同じ接続で制約を外してノードだけを削除する
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 0")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM nodes WHERE id = ?")
+            .bind(order_node_id)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 1")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        drop(conn);
+
+        let result = service(&pool)
+            .get_nearest_available_tow_trucks(order_id)
+            .await;
+
+        assert!(matches!(result, Err(AppError::NotFound)));
+    }
+
+    #[actix_rt::test]
+    async fn nearest_available_tow_truck_distinguishes_empty_and_unreachable_areas() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let order_node_id = seed_node(&pool, area_id).await;
+        let isolated_node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, order_node_id).await;
+        let service = service(&pool);
+
+        assert!(matches!(
+            service.get_nearest_available_tow_trucks(order_id).await,
+            Ok(NearestTowTruck::NoAvailable)
+        ));
+
+        let driver_id = seed_user(&pool, "driver").await;
+        seed_truck(&pool, driver_id, area_id, isolated_node_id).await;
+
+        assert!(matches!(
+            service.get_nearest_available_tow_trucks(order_id).await,
+            Ok(NearestTowTruck::Unreachable { available_count: 1 })
+        ));
+    }
+
+    async fn set_destination(pool: &MySqlPool, order_id: i32, destination_node_id: Option<i32>) {
+        sqlx::query("UPDATE orders SET destination_node_id = ? WHERE id = ?")
+            .bind(destination_node_id)
+            .bind(order_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn destination_leg_does_not_favor_trucks_near_the_destination() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 引き取り地点 p、搬送先 d。トラック A は d の隣 (p まで 4)、トラック B は p の隣 (p まで 1)
+        // p -> d は 5 で、総コストは A が 4 + 5、B が 1 + 5 になる
+        let area_id = seed_area(&pool).await;
+        let pickup_node_id = seed_node(&pool, area_id).await;
+        let near_pickup_node_id = seed_node(&pool, area_id).await;
+        let near_destination_node_id = seed_node(&pool, area_id).await;
+        let destination_node_id = seed_node(&pool, area_id).await;
+        seed_edge(&pool, pickup_node_id, near_pickup_node_id, 1).await;
+        seed_edge(&pool, pickup_node_id, near_destination_node_id, 4).await;
+        seed_edge(&pool, near_destination_node_id, destination_node_id, 1).await;
+        seed_edge(&pool, pickup_node_id, destination_node_id, 6).await;
+        let driver_a_id = seed_user(&pool, "driver").await;
+        let driver_b_id = seed_user(&pool, "driver").await;
+        seed_truck(&pool, driver_a_id, area_id, near_destination_node_id).await;
+        let tow_truck_b_id = seed_truck(&pool, driver_b_id, area_id, near_pickup_node_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, pickup_node_id).await;
+        let service = service(&pool);
+
+        for destination in [None, Some(destination_node_id)] {
+            set_destination(&pool, order_id, destination).await;
+
+            match service.get_nearest_available_tow_trucks(order_id).await {
+                Ok(NearestTowTruck::Found(tow_truck)) => assert_eq!(tow_truck.id, tow_truck_b_id),
+                _ => panic!("expected truck B for destination {:?}", destination),
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn unreachable_destination_leaves_the_order_unassignable() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let pickup_node_id = seed_node(&pool, area_id).await;
+        let truck_node_id = seed_node(&pool, area_id).await;
+        let isolated_node_id = seed_node(&pool, area_id).await;
+        seed_edge(&pool, pickup_node_id, truck_node_id, 1).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, truck_node_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, pickup_node_id).await;
+        let service = service(&pool);
+
+        set_destination(&pool, order_id, Some(isolated_node_id)).await;
+        assert!(matches!(
+            service.get_nearest_available_tow_trucks(order_id).await,
+            Ok(NearestTowTruck::Unreachable { available_count: 1 })
+        ));
+
+        // 搬送先がなければ引き取り地点までの距離だけで選ぶ
+        set_destination(&pool, order_id, None).await;
+        match service.get_nearest_available_tow_trucks(order_id).await {
+            Ok(NearestTowTruck::Found(tow_truck)) => assert_eq!(tow_truck.id, tow_truck_id),
+            _ => panic!("expected the adjacent tow truck without a destination"),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn candidates_list_every_available_tow_truck_by_distance_with_unreachable_last() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 注文地点 0 - 1 - 2 (重み 2, 3) の一本道と、どこにもつながっていないノード 3
+        let area_id = seed_area(&pool).await;
+        let mut node_ids = Vec::new();
+        for _ in 0..4 {
+            node_ids.push(seed_node(&pool, area_id).await);
+        }
+        seed_edge(&pool, node_ids[0], node_ids[1], 2).await;
+        seed_edge(&pool, node_ids[1], node_ids[2], 3).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let unreachable_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[3]).await;
+        let far_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[2]).await;
+        let near_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[1]).await;
+        let co_located_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[0]).await;
+        // 配車中のトラックは候補に含めない
+        let busy_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[1]).await;
+        let client_id = seed_user(&pool, "client").await;
+        let busy_order_id = seed_order(&pool, client_id, node_ids[1]).await;
+        seed_dispatch(&pool, busy_order_id, busy_tow_truck_id).await;
+        let order_id = seed_order(&pool, client_id, node_ids[0]).await;
+
+        let runs_before = dijkstra_runs();
+        let candidates = service(&pool).candidates_for_order(order_id).await.unwrap();
+
+        assert_eq!(dijkstra_runs() - runs_before, 1);
+        let listed: Vec<(i32, Option<i32>)> = candidates
+            .iter()
+            .map(|candidate| (candidate.tow_truck.id, candidate.distance))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                (co_located_tow_truck_id, Some(0)),
+                (near_tow_truck_id, Some(2)),
+                (far_tow_truck_id, Some(5)),
+                (unreachable_tow_truck_id, None),
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn repeated_candidates_reuse_cached_distances_until_an_edge_changes() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let order_node_id = seed_node(&pool, area_id).await;
+        let truck_node_id = seed_node(&pool, area_id).await;
+        seed_edge(&pool, order_node_id, truck_node_id, 3).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        seed_truck(&pool, driver_id, area_id, truck_node_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, order_node_id).await;
+        // テスト中に期限が切れないよう、有効期間を長くとる
+        let distance_cache = Arc::new(DistanceCache::new(Duration::from_secs(600)));
+        let graph_cache = Arc::new(GraphCache::new(None));
+        let service = service_with_caches(&pool, distance_cache.clone(), graph_cache.clone());
+        let map_service = MapService::new(
+            MapRepositoryImpl::new(pool.clone(), circuit_breaker()),
+            distance_cache,
+            graph_cache,
+        );
+
+        let runs_before = dijkstra_runs();
+        let first = service.candidates_for_order(order_id).await.unwrap();
+        let second = service.candidates_for_order(order_id).await.unwrap();
+
+        assert_eq!(dijkstra_runs() - runs_before, 1);
+        assert_eq!(first[0].distance, Some(3));
+        assert_eq!(second[0].distance, Some(3));
+
+        map_service
+            .update_edge(order_node_id, truck_node_id, 8)
+            .await
+            .unwrap();
+        let updated = service.candidates_for_order(order_id).await.unwrap();
+
+        assert_eq!(dijkstra_runs() - runs_before, 2);
+        assert_eq!(updated[0].distance, Some(8));
+    }
+
+    #[actix_rt::test]
+    async fn busy_tow_trucks_carry_their_active_order_id() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let busy_driver_id = seed_user(&pool, "driver").await;
+        let busy_tow_truck_id = seed_truck(&pool, busy_driver_id, area_id, node_id).await;
+        let active_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, active_order_id, busy_tow_truck_id).await;
+        // 完了済みの注文しか担当していないトラックには注文IDを付けない
+        let idle_driver_id = seed_user(&pool, "driver").await;
+        let idle_tow_truck_id = seed_truck(&pool, idle_driver_id, area_id, node_id).await;
+        let completed_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, completed_order_id, idle_tow_truck_id).await;
+        sqlx::query("UPDATE orders SET status = 'completed' WHERE id = ?")
+            .bind(completed_order_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let service = service(&pool);
+
+        let listed = service
+            .get_all_tow_trucks(0, None, None, Some(AreaId(area_id)), false, None)
+            .await
+            .unwrap();
+        let by_ids = service
+            .get_tow_trucks_by_ids(&[busy_tow_truck_id, idle_tow_truck_id])
+            .await
+            .unwrap();
+
+        for tow_trucks in [listed, by_ids] {
+            let order_ids: HashMap<i32, Option<i32>> = tow_trucks
+                .iter()
+                .map(|tow_truck| (tow_truck.id, tow_truck.order_id))
+                .collect();
+            assert_eq!(order_ids.len(), 2);
+            assert_eq!(order_ids[&busy_tow_truck_id], Some(active_order_id));
+            assert_eq!(order_ids[&idle_tow_truck_id], None);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn coverage_reports_the_nearest_and_furthest_reachable_tow_trucks() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 注文地点 0 - 1 - 2 - 3 (重み 1, 3, 2) の一本道で、トラックはノード 1, 2, 3 と孤立したノードにいる
+        let area_id = seed_area(&pool).await;
+        let mut node_ids = Vec::new();
+        for _ in 0..5 {
+            node_ids.push(seed_node(&pool, area_id).await);
+        }
+        seed_edge(&pool, node_ids[0], node_ids[1], 1).await;
+        seed_edge(&pool, node_ids[1], node_ids[2], 3).await;
+        seed_edge(&pool, node_ids[2], node_ids[3], 2).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let nearest_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[1]).await;
+        seed_truck(&pool, driver_id, area_id, node_ids[2]).await;
+        let furthest_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[3]).await;
+        seed_truck(&pool, driver_id, area_id, node_ids[4]).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_ids[0]).await;
+
+        let runs_before = dijkstra_runs();
+        let coverage = service(&pool)
+            .get_tow_truck_coverage(order_id)
+            .await
+            .unwrap()
+            .expect("reachable tow trucks exist");
+
+        assert_eq!(dijkstra_runs() - runs_before, 1);
+        assert_eq!(coverage.nearest.tow_truck.id, nearest_tow_truck_id);
+        assert_eq!(coverage.nearest.distance, 1);
+        // 孤立したノードのトラックは到達できないため、最も遠いトラックには含めない
+        assert_eq!(coverage.furthest.tow_truck.id, furthest_tow_truck_id);
+        assert_eq!(coverage.furthest.distance, 6);
+    }
+
+    #[actix_rt::test]
+    async fn fleet_centroid_is_the_truck_node_with_the_smallest_total_distance() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 0 - 1 - 2 - 3 - 4 (重みはすべて 1) の一本道で、トラックはノード 0, 1, 3, 3, 4 にいる
+        // 総距離は 0: 11, 1: 8, 3: 6, 4: 9 になる
+        let area_id = seed_area(&pool).await;
+        let mut node_ids = Vec::new();
+        for _ in 0..5 {
+            node_ids.push(seed_node(&pool, area_id).await);
+        }
+        for pair in node_ids.windows(2) {
+            seed_edge(&pool, pair[0], pair[1], 1).await;
+        }
+        let driver_id = seed_user(&pool, "driver").await;
+        for index in [0, 1, 3, 3, 4] {
+            seed_truck(&pool, driver_id, area_id, node_ids[index]).await;
+        }
+        let empty_area_id = seed_area(&pool).await;
+        let service = service(&pool);
+
+        let centroid = service
+            .get_fleet_centroid(AreaId(area_id))
+            .await
+            .unwrap()
+            .expect("the area has available tow trucks");
+
+        assert_eq!(centroid.node_id, node_ids[3]);
+        assert_eq!(centroid.total_distance, 6);
+        assert_eq!(centroid.tow_truck_count, 5);
+        assert!(service
+            .get_fleet_centroid(AreaId(empty_area_id))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    async fn set_last_updated_at(pool: &MySqlPool, tow_truck_id: i32, seconds_ago: i64) {
+        sqlx::query(
+            "UPDATE tow_trucks SET last_updated_at = NOW() - INTERVAL ? SECOND WHERE id = ?",
+        )
+        .bind(seconds_ago)
+        .bind(tow_truck_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn only_busy_tow_trucks_past_the_threshold_are_reclaimable() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let stale_busy_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let fresh_busy_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let stale_available_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        sqlx::query("UPDATE tow_trucks SET status = 'busy' WHERE id IN (?, ?)")
+            .bind(stale_busy_tow_truck_id)
+            .bind(fresh_busy_tow_truck_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        // しきい値は 600 秒
+        set_last_updated_at(&pool, stale_busy_tow_truck_id, 3600).await;
+        set_last_updated_at(&pool, fresh_busy_tow_truck_id, 60).await;
+        set_last_updated_at(&pool, stale_available_tow_truck_id, 3600).await;
+
+        let reclaimable = service(&pool)
+            .get_reclaimable_tow_trucks(Some(AreaId(area_id)))
+            .await
+            .unwrap();
+
+        let tow_truck_ids: Vec<i32> = reclaimable.iter().map(|tow_truck| tow_truck.id).collect();
+        assert_eq!(tow_truck_ids, vec![stale_busy_tow_truck_id]);
+    }
+
+    #[actix_rt::test]
+    async fn heartbeat_refreshes_a_stale_tow_truck_without_moving_it() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        sqlx::query("UPDATE tow_trucks SET status = 'busy' WHERE id = ?")
+            .bind(tow_truck_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        set_last_updated_at(&pool, tow_truck_id, 3600).await;
+        let location_count = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM locations WHERE tow_truck_id = ?")
+                .bind(tow_truck_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        };
+        let locations_before = location_count().await;
+        let service = service(&pool);
+        assert_eq!(
+            service
+                .get_reclaimable_tow_trucks(Some(AreaId(area_id)))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        service.heartbeat(tow_truck_id).await.unwrap();
+
+        let seconds_since_update: i64 = sqlx::query_scalar(
+            "SELECT TIMESTAMPDIFF(SECOND, last_updated_at, NOW()) FROM tow_trucks WHERE id = ?",
+        )
+        .bind(tow_truck_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!(seconds_since_update < 60);
+        assert!(service
+            .get_reclaimable_tow_trucks(Some(AreaId(area_id)))
+            .await
+            .unwrap()
+            .is_empty());
+        // 位置の履歴は増えず、トラックは同じノードにいる
+        assert_eq!(location_count().await, locations_before);
+        let tow_trucks = service
+            .get_tow_trucks_by_ids(&[tow_truck_id])
+            .await
+            .unwrap();
+        assert_eq!(tow_trucks[0].node_id, node_id);
+    }
+
+    async fn seed_depot(pool: &MySqlPool, area_id: i32, node_id: i32) {
+        sqlx::query("INSERT INTO depots (area_id, node_id) VALUES (?, ?)")
+            .bind(area_id)
+            .bind(node_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn nearest_depot_is_the_closer_of_two_depots() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // 遠い拠点 - (5) - トラック - (2) - 近い拠点
+        let area_id = seed_area(&pool).await;
+        let far_depot_node_id = seed_node(&pool, area_id).await;
+        let truck_node_id = seed_node(&pool, area_id).await;
+        let near_depot_node_id = seed_node(&pool, area_id).await;
+        seed_edge(&pool, far_depot_node_id, truck_node_id, 5).await;
+        seed_edge(&pool, truck_node_id, near_depot_node_id, 2).await;
+        seed_depot(&pool, area_id, far_depot_node_id).await;
+        seed_depot(&pool, area_id, near_depot_node_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, truck_node_id).await;
+        // 拠点のないエリアのトラック
+        let no_depot_area_id = seed_area(&pool).await;
+        let no_depot_node_id = seed_node(&pool, no_depot_area_id).await;
+        let no_depot_tow_truck_id =
+            seed_truck(&pool, driver_id, no_depot_area_id, no_depot_node_id).await;
+        let service = service(&pool);
+
+        let depot = service
+            .nearest_depot(tow_truck_id)
+            .await
+            .unwrap()
+            .expect("the area has depots");
+
+        assert_eq!(depot.node_id, near_depot_node_id);
+        assert_eq!(depot.distance, 2);
+        assert!(service
+            .nearest_depot(no_depot_tow_truck_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            service.nearest_depot(-1).await,
+            Err(AppError::NotFound)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn single_node_area_finds_a_co_located_truck_at_distance_zero() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        let service = service(&pool);
+
+        match service.get_nearest_available_tow_trucks(order_id).await {
+            Ok(NearestTowTruck::Found(tow_truck)) => assert_eq!(tow_truck.id, tow_truck_id),
+            _ => panic!("expected the co-located tow truck to be found"),
+        }
+        let candidates = service.candidates_for_order(order_id).await.unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].distance, Some(0));
+    }
+
+    #[actix_rt::test]
+    async fn single_node_area_with_the_truck_elsewhere_is_unreachable() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        // トラックはエリアに所属しているが、位置はエリアの地図にないノードになっている
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let elsewhere_area_id = seed_area(&pool).await;
+        let elsewhere_node_id = seed_node(&pool, elsewhere_area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        seed_truck(&pool, driver_id, area_id, elsewhere_node_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        let service = service(&pool);
+
+        assert!(matches!(
+            service.get_nearest_available_tow_trucks(order_id).await,
+            Ok(NearestTowTruck::Unreachable { available_count: 1 })
+        ));
+        let candidates = service.candidates_for_order(order_id).await.unwrap();
+        assert_eq!(candidates[0].distance, None);
+    }
+
+    #[actix_rt::test]
+    async fn decommissioned_tow_trucks_are_listed_only_when_requested() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let active_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let deleted_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        sqlx::query("UPDATE tow_trucks SET deleted_at = NOW() WHERE id = ?")
+            .bind(deleted_tow_truck_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let service = service(&pool);
+
+        // ページ指定の有無にかかわらず同じ結果になる
+        for page_size in [None, Some(10)] {
+            let listed: Vec<(i32, bool)> = service
+                .get_all_tow_trucks(0, page_size, None, Some(AreaId(area_id)), false, None)
+                .await
+                .unwrap()
+                .iter()
+                .map(|tow_truck| (tow_truck.id, tow_truck.deleted))
+                .collect();
+            assert_eq!(listed, vec![(active_tow_truck_id, false)]);
+
+            let listed_with_deleted: Vec<(i32, bool)> = service
+                .get_all_tow_trucks(0, page_size, None, Some(AreaId(area_id)), true, None)
+                .await
+                .unwrap()
+                .iter()
+                .map(|tow_truck| (tow_truck.id, tow_truck.deleted))
+                .collect();
+            assert_eq!(
+                listed_with_deleted,
+                vec![(active_tow_truck_id, false), (deleted_tow_truck_id, true)]
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn fleet_availability_is_half_for_a_half_busy_area_and_zero_for_an_empty_one() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let empty_area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let client_id = seed_user(&pool, "client").await;
+        for i in 0..4 {
+            let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+            if i % 2 == 0 {
+                let order_id = seed_order(&pool, client_id, node_id).await;
+                seed_dispatch(&pool, order_id, tow_truck_id).await;
+            }
+        }
+        let service = service(&pool);
+
+        let availability = service
+            .get_fleet_availability(AreaId(area_id))
+            .await
+            .unwrap();
+        assert_eq!(availability.available_count, 2);
+        assert_eq!(availability.total_count, 4);
+        assert_eq!(availability.availability, 50.0);
+
+        let empty = service
+            .get_fleet_availability(AreaId(empty_area_id))
+            .await
+            .unwrap();
+        assert_eq!(empty.total_count, 0);
+        assert_eq!(empty.availability, 0.0);
+    }
+
+    #[actix_rt::test]
+    async fn utilization_of_an_area_without_history_is_empty() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        seed_truck(&pool, driver_id, area_id, node_id).await;
+        let service = service(&pool);
+        let now = Utc::now();
+
+        // 配車の履歴がないトラックは含めず、NaN の割合を返すこともない
+        assert!(service
+            .get_utilization(AreaId(area_id), now - chrono::Duration::hours(1), now)
+            .await
+            .unwrap()
+            .is_empty());
+        // 長さ 0 や逆向きの期間は割合を計算せずに弾く
+        for (from, to) in [(now, now), (now, now - chrono::Duration::hours(1))] {
+            assert!(matches!(
+                service.get_utilization(AreaId(area_id), from, to).await,
+                Err(AppError::BadRequest)
+            ));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn repeated_status_updates_change_the_truck_and_publish_only_once() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let published = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let event_bus = Arc::new(EventBus::new());
+        let recorder = published.clone();
+        event_bus.subscribe(move |event| {
+            if let DomainEvent::TowTruckStatusChanged { status, .. } = event {
+                recorder.lock().unwrap().push(status.clone());
+            }
+        });
+        let service = service_with_parts(
+            &pool,
+            Arc::new(DistanceCache::new(Duration::from_secs(1))),
+            Arc::new(GraphCache::new(None)),
+            Arc::new(DijkstraRouter),
+            event_bus,
+        );
+
+        // 既に available のトラックを available にしても変更もイベントもない
+        assert!(!service
+            .update_tow_truck_status(tow_truck_id, "available")
+            .await
+            .unwrap());
+        assert!(service
+            .update_tow_truck_status(tow_truck_id, "busy")
+            .await
+            .unwrap());
+        assert!(!service
+            .update_tow_truck_status(tow_truck_id, "busy")
+            .await
+            .unwrap());
+
+        assert_eq!(*published.lock().unwrap(), vec!["busy".to_string()]);
+        assert!(matches!(
+            service
+                .update_tow_truck_status(tow_truck_id, "broken")
+                .await,
+            Err(AppError::BadRequest)
+        ));
+        assert!(matches!(
+            service.update_tow_truck_status(-1, "busy").await,
+            Err(AppError::NotFound)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn tow_trucks_by_ids_keep_the_requested_order_and_skip_missing_ids() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let mut tow_truck_ids = Vec::new();
+        for _ in 0..3 {
+            tow_truck_ids.push(seed_truck(&pool, driver_id, area_id, node_id).await);
+        }
+        let service = service(&pool);
+
+        // 存在しないIDは飛ばし、重複したIDは最初の1回だけ返す
+        let tow_trucks = service
+            .get_tow_trucks_by_ids(&[tow_truck_ids[2], -1, tow_truck_ids[0], tow_truck_ids[2]])
+            .await
+            .unwrap();
+
+        let found_ids: Vec<i32> = tow_trucks.iter().map(|tow_truck| tow_truck.id).collect();
+        assert_eq!(found_ids, vec![tow_truck_ids[2], tow_truck_ids[0]]);
+        assert!(tow_trucks
+            .iter()
+            .all(|tow_truck| tow_truck.node_id == node_id));
+        assert!(service.get_tow_trucks_by_ids(&[]).await.unwrap().is_empty());
+    }
 }
@@ -1,4 +1,5 @@
 use actix_web::{HttpResponse, ResponseError};
+use log::error;
 use serde::Serialize;
 use thiserror::Error;
 
@@ -6,15 +7,25 @@ use thiserror::Error;
 pub enum AppError {
     #[error("Bad Request")]
     BadRequest,
+    // 入力のどこが不正かをメッセージとしてそのままクライアントに返す
+    #[error("{0}")]
+    ValidationError(String),
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Forbidden")]
+    Forbidden,
     #[error("Not Found")]
     NotFound,
     #[error("Conflict")]
     Conflict,
+    #[error("Too Many Requests")]
+    TooManyRequests,
     #[error("Internal Server Error")]
     InternalServerError,
-    #[error(transparent)]
+    #[error("Service Unavailable")]
+    ServiceUnavailable,
+    // SQL やスキーマの情報をクライアントに返さないよう、詳細はログにのみ出力する
+    #[error("Internal Server Error")]
     SqlxError(#[from] sqlx::Error),
 }
 
@@ -25,6 +36,15 @@ struct ErrorResponse {
 
 impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
+        // 接続を待ちきれなかった場合 (遮断器で遮断した場合を含む) は DB の一時的な障害として扱う
+        if let AppError::SqlxError(e @ sqlx::Error::PoolTimedOut) = self {
+            error!("データベースに接続できません: {:?}", e);
+            return AppError::ServiceUnavailable.error_response();
+        }
+        if let AppError::SqlxError(e) = self {
+            error!("データベースエラー: {:?}", e);
+        }
+
         let error_message = self.to_string();
         let error_response = ErrorResponse {
             message: error_message,
@@ -32,13 +52,64 @@ impl ResponseError for AppError {
 
         match *self {
             AppError::BadRequest => HttpResponse::BadRequest().json(error_response),
+            AppError::ValidationError(_) => HttpResponse::BadRequest().json(error_response),
             AppError::Unauthorized => HttpResponse::Unauthorized().json(error_response),
+            AppError::Forbidden => HttpResponse::Forbidden().json(error_response),
             AppError::NotFound => HttpResponse::NotFound().json(error_response),
             AppError::Conflict => HttpResponse::Conflict().json(error_response),
+            AppError::TooManyRequests => HttpResponse::TooManyRequests().json(error_response),
             AppError::InternalServerError => {
                 HttpResponse::InternalServerError().json(error_response)
             }
+            AppError::ServiceUnavailable => HttpResponse::ServiceUnavailable().json(error_response),
             AppError::SqlxError(_) => HttpResponse::InternalServerError().json(error_response),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_is_a_fixed_message_for_each_variant() {
+        let cases = [
+            (AppError::BadRequest, "Bad Request"),
+            (AppError::Unauthorized, "Unauthorized"),
+            (AppError::Forbidden, "Forbidden"),
+            (AppError::NotFound, "Not Found"),
+            (AppError::Conflict, "Conflict"),
+            (AppError::TooManyRequests, "Too Many Requests"),
+            (AppError::InternalServerError, "Internal Server Error"),
+            (AppError::ServiceUnavailable, "Service Unavailable"),
+        ];
+        for (error, message) in cases {
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn validation_error_displays_only_its_message() {
+        let error = AppError::ValidationError("window_minutes must be positive".to_string());
+        assert_eq!(error.to_string(), "window_minutes must be positive");
+    }
+
+    #[test]
+    fn sqlx_error_display_hides_the_database_details() {
+        let errors = [
+            sqlx::Error::Protocol("SELECT password FROM users WHERE id = 1".to_string()),
+            sqlx::Error::ColumnNotFound("completed_time".to_string()),
+            sqlx::Error::RowNotFound,
+        ];
+        for error in errors {
+            let message = AppError::from(error).to_string();
+            assert_eq!(message, "Internal Server Error");
+        }
+    }
+
+    #[test]
+    fn app_error_is_a_std_error() {
+        let error: Box<dyn std::error::Error> = Box::new(AppError::Conflict);
+        assert_eq!(error.to_string(), "Conflict");
+    }
+}
@@ -0,0 +1,103 @@
+use actix_web::rt::{self, task::JoinHandle, time};
+use log::{error, warn};
+use std::env;
+use std::time::Duration;
+
+use crate::domains::tow_truck_service::TowTruckRepository;
+
+// 環境変数から確認の間隔(秒)を取得し、デフォルトを60秒に設定
+pub fn sweep_interval_from_env() -> Duration {
+    let seconds: u64 = env::var("BUSY_TRUCK_SWEEP_INTERVAL_SECONDS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse()
+        .expect("BUSY_TRUCK_SWEEP_INTERVAL_SECONDS must be a valid number");
+
+    Duration::from_secs(seconds)
+}
+
+// 配車処理が途中で失敗して busy のまま取り残されたトラックを定期的に自己修復する
+// 「担当中の注文が無い busy のトラック」の判定は管理者向けの手動の修復と同じものを使う
+pub fn spawn_busy_truck_sweeper<T: TowTruckRepository + 'static>(
+    repository: T,
+    interval: Duration,
+) -> JoinHandle<()> {
+    rt::spawn(async move {
+        let mut ticker = time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match repository.free_busy_without_active_order(None).await {
+                Ok(tow_truck_ids) => {
+                    for tow_truck_id in tow_truck_ids {
+                        warn!(
+                            "担当中の注文がない busy のトラック {} を available に戻しました",
+                            tow_truck_id
+                        );
+                    }
+                }
+                Err(e) => error!("busy のトラックの確認に失敗しました: {:?}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
+    use crate::testsupport::{
+        circuit_breaker, seed_area, seed_dispatch, seed_node, seed_order, seed_truck, seed_user,
+        test_pool,
+    };
+    use sqlx::mysql::MySqlPool;
+
+    async fn tow_truck_status(pool: &MySqlPool, tow_truck_id: i32) -> String {
+        sqlx::query_scalar("SELECT status FROM tow_trucks WHERE id = ?")
+            .bind(tow_truck_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn spawned_sweeper_frees_stranded_trucks_and_keeps_dispatched_ones_busy() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let client_id = seed_user(&pool, "client").await;
+        // 配車が途中で失敗し、担当中の注文がないまま busy になったトラック
+        let stranded_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        sqlx::query("UPDATE tow_trucks SET status = 'busy' WHERE id = ?")
+            .bind(stranded_tow_truck_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let dispatched_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, order_id, dispatched_tow_truck_id).await;
+
+        // 最初の tick はすぐに発火するため、起動直後に1回確認される
+        let handle = spawn_busy_truck_sweeper(
+            TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker()),
+            Duration::from_secs(3600),
+        );
+        let mut swept = false;
+        for _ in 0..50 {
+            if tow_truck_status(&pool, stranded_tow_truck_id).await == "available" {
+                swept = true;
+                break;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        handle.abort();
+
+        assert!(swept);
+        assert_eq!(
+            tow_truck_status(&pool, dispatched_tow_truck_id).await,
+            "busy"
+        );
+        assert!(handle.await.unwrap_err().is_cancelled());
+    }
+}
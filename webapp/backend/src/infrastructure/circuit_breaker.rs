@@ -0,0 +1,338 @@
+use std::env;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::errors::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BreakerState {
+    Closed { consecutive_failures: u32 },
+    Open { opened_at: Instant },
+    // クールダウン明けに1件だけ試しに通している状態
+    // 試しの処理がクールダウンを過ぎても終わらない場合は、次の1件を試しに通す
+    HalfOpen { probing_since: Instant },
+}
+
+// 遮断器を通す処理のエラー型。DB への接続そのものの失敗かどうかと、遮断中に返すエラーを定める
+pub trait BreakerError {
+    fn is_connection_error(&self) -> bool;
+    fn unavailable() -> Self;
+}
+
+impl BreakerError for sqlx::Error {
+    // 行が見つからない等のクエリ結果は含めない
+    fn is_connection_error(&self) -> bool {
+        matches!(
+            self,
+            sqlx::Error::Io(_)
+                | sqlx::Error::Tls(_)
+                | sqlx::Error::PoolTimedOut
+                | sqlx::Error::PoolClosed
+        )
+    }
+
+    // 接続を待ちきれなかった場合と同じエラーにする
+    fn unavailable() -> Self {
+        sqlx::Error::PoolTimedOut
+    }
+}
+
+impl BreakerError for AppError {
+    fn is_connection_error(&self) -> bool {
+        matches!(self, AppError::SqlxError(err) if err.is_connection_error())
+    }
+
+    fn unavailable() -> Self {
+        AppError::ServiceUnavailable
+    }
+}
+
+// DB に接続できない状態が続いたときに、接続待ちで詰まる前にリポジトリの呼び出しを即座に失敗させる
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::Closed {
+                consecutive_failures: 0,
+            }),
+        }
+    }
+
+    // 環境変数から閾値とクールダウン(秒)を取得し、デフォルトをそれぞれ5回・10秒に設定
+    pub fn from_env() -> Self {
+        let failure_threshold: u32 = env::var("CIRCUIT_BREAKER_FAILURE_THRESHOLD")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .expect("CIRCUIT_BREAKER_FAILURE_THRESHOLD must be a valid number");
+        let cooldown_seconds: u64 = env::var("CIRCUIT_BREAKER_COOLDOWN_SECONDS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .expect("CIRCUIT_BREAKER_COOLDOWN_SECONDS must be a valid number");
+
+        CircuitBreaker::new(failure_threshold, Duration::from_secs(cooldown_seconds))
+    }
+
+    // リポジトリの処理を遮断器を通して実行する
+    // 遮断中は処理を実行せずに unavailable() を返し、接続の失敗だけを失敗として数える
+    // (DB が応答したエラーは接続できている証拠なので成功として数える)
+    pub async fn call<T, E, F>(&self, operation: F) -> Result<T, E>
+    where
+        E: BreakerError,
+        F: Future<Output = Result<T, E>>,
+    {
+        if !self.try_acquire() {
+            return Err(E::unavailable());
+        }
+
+        // 結果が出る前に呼び出し元に破棄された場合は、Drop で失敗として数える
+        let mut attempt = Attempt {
+            circuit_breaker: self,
+            finished: false,
+        };
+        let result = operation.await;
+        attempt.finished = true;
+        match &result {
+            Err(err) if err.is_connection_error() => self.record_failure(),
+            _ => self.record_success(),
+        }
+
+        result
+    }
+
+    fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { opened_at } if opened_at.elapsed() >= self.cooldown => {
+                *state = BreakerState::HalfOpen {
+                    probing_since: Instant::now(),
+                };
+                true
+            }
+            BreakerState::HalfOpen { probing_since }
+                if probing_since.elapsed() >= self.cooldown =>
+            {
+                *state = BreakerState::HalfOpen {
+                    probing_since: Instant::now(),
+                };
+                true
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => false,
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        *state = BreakerState::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        *state = match *state {
+            BreakerState::Closed {
+                consecutive_failures,
+            } if consecutive_failures + 1 < self.failure_threshold => BreakerState::Closed {
+                consecutive_failures: consecutive_failures + 1,
+            },
+            _ => BreakerState::Open {
+                opened_at: Instant::now(),
+            },
+        };
+    }
+}
+
+// 遮断器を通した1回の呼び出し。試しに通した処理が破棄されたまま HalfOpen で止まらないようにする
+struct Attempt<'a> {
+    circuit_breaker: &'a CircuitBreaker,
+    finished: bool,
+}
+
+impl Drop for Attempt<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        // 閉じている間に破棄された呼び出し (クライアントの切断など) は接続の失敗とは限らないため数えない
+        let mut state = self.circuit_breaker.state.lock().unwrap();
+        if let BreakerState::HalfOpen { .. } = *state {
+            *state = BreakerState::Open {
+                opened_at: Instant::now(),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    const COOLDOWN: Duration = Duration::from_millis(20);
+
+    // DB の代わりに、接続に失敗する処理と応答が返る処理を渡す
+    async fn connection_refused() -> Result<(), AppError> {
+        Err(AppError::SqlxError(sqlx::Error::PoolTimedOut))
+    }
+
+    async fn query_ok() -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn state(circuit_breaker: &CircuitBreaker) -> BreakerState {
+        *circuit_breaker.state.lock().unwrap()
+    }
+
+    async fn tripped_breaker() -> CircuitBreaker {
+        let circuit_breaker = CircuitBreaker::new(2, COOLDOWN);
+        for _ in 0..2 {
+            assert!(circuit_breaker.call(connection_refused()).await.is_err());
+        }
+        assert!(matches!(state(&circuit_breaker), BreakerState::Open { .. }));
+        circuit_breaker
+    }
+
+    #[actix_rt::test]
+    async fn goes_from_closed_to_open_to_half_open_and_back_to_closed() {
+        let circuit_breaker = CircuitBreaker::new(2, COOLDOWN);
+
+        assert!(circuit_breaker.call(connection_refused()).await.is_err());
+        assert_eq!(
+            state(&circuit_breaker),
+            BreakerState::Closed {
+                consecutive_failures: 1
+            }
+        );
+        assert!(circuit_breaker.call(connection_refused()).await.is_err());
+        assert!(matches!(state(&circuit_breaker), BreakerState::Open { .. }));
+
+        // 遮断中は処理を実行せずに失敗させる
+        let mut called = false;
+        let result = circuit_breaker
+            .call(async {
+                called = true;
+                query_ok().await
+            })
+            .await;
+        assert!(matches!(result, Err(AppError::ServiceUnavailable)));
+        assert!(!called);
+
+        // クールダウン明けは1件だけ試しに通し、その間の他の呼び出しは遮断する
+        actix_rt::time::sleep(COOLDOWN * 2).await;
+        circuit_breaker
+            .call(async {
+                assert!(matches!(
+                    state(&circuit_breaker),
+                    BreakerState::HalfOpen { .. }
+                ));
+                assert!(matches!(
+                    circuit_breaker.call(query_ok()).await,
+                    Err(AppError::ServiceUnavailable)
+                ));
+                query_ok().await
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            state(&circuit_breaker),
+            BreakerState::Closed {
+                consecutive_failures: 0
+            }
+        );
+    }
+
+    #[actix_rt::test]
+    async fn failed_probe_reopens_the_breaker() {
+        let circuit_breaker = tripped_breaker().await;
+        actix_rt::time::sleep(COOLDOWN * 2).await;
+
+        assert!(matches!(
+            circuit_breaker.call(connection_refused()).await,
+            Err(AppError::SqlxError(_))
+        ));
+
+        assert!(matches!(state(&circuit_breaker), BreakerState::Open { .. }));
+    }
+
+    #[actix_rt::test]
+    async fn dropped_probe_reopens_the_breaker() {
+        let circuit_breaker = tripped_breaker().await;
+        actix_rt::time::sleep(COOLDOWN * 2).await;
+
+        let mut probe =
+            Box::pin(circuit_breaker.call(std::future::pending::<Result<(), AppError>>()));
+        assert!(probe.as_mut().now_or_never().is_none());
+        assert!(matches!(
+            state(&circuit_breaker),
+            BreakerState::HalfOpen { .. }
+        ));
+        drop(probe);
+
+        assert!(matches!(state(&circuit_breaker), BreakerState::Open { .. }));
+    }
+
+    #[actix_rt::test]
+    async fn stalled_probe_lets_another_probe_through_after_the_cooldown() {
+        let circuit_breaker = tripped_breaker().await;
+        actix_rt::time::sleep(COOLDOWN * 2).await;
+
+        let mut probe =
+            Box::pin(circuit_breaker.call(std::future::pending::<Result<(), AppError>>()));
+        assert!(probe.as_mut().now_or_never().is_none());
+        actix_rt::time::sleep(COOLDOWN * 2).await;
+
+        assert!(circuit_breaker.call(query_ok()).await.is_ok());
+        assert_eq!(
+            state(&circuit_breaker),
+            BreakerState::Closed {
+                consecutive_failures: 0
+            }
+        );
+    }
+
+    #[actix_rt::test]
+    async fn errors_answered_by_the_database_reset_the_failure_count() {
+        let circuit_breaker = CircuitBreaker::new(2, COOLDOWN);
+
+        assert!(circuit_breaker.call(connection_refused()).await.is_err());
+        let not_found = circuit_breaker
+            .call(async { Err::<(), AppError>(AppError::SqlxError(sqlx::Error::RowNotFound)) })
+            .await;
+        assert!(not_found.is_err());
+
+        assert_eq!(
+            state(&circuit_breaker),
+            BreakerState::Closed {
+                consecutive_failures: 0
+            }
+        );
+    }
+
+    #[actix_rt::test]
+    async fn dropping_a_call_while_closed_is_not_counted_as_a_failure() {
+        let circuit_breaker = CircuitBreaker::new(1, COOLDOWN);
+
+        let mut call =
+            Box::pin(circuit_breaker.call(std::future::pending::<Result<(), AppError>>()));
+        assert!(call.as_mut().now_or_never().is_none());
+        drop(call);
+
+        assert_eq!(
+            state(&circuit_breaker),
+            BreakerState::Closed {
+                consecutive_failures: 0
+            }
+        );
+    }
+}
@@ -2,6 +2,19 @@ use sqlx::mysql::MySqlPool;
 use sqlx::mysql::MySqlPoolOptions;
 use std::env;
 use std::time::Duration;
+
+// リポジトリが持つ接続プールの型。テストではクエリの数を数えるプールに差し替える
+#[cfg(not(test))]
+pub type DbPool = MySqlPool;
+#[cfg(test)]
+pub type DbPool = crate::testsupport::CountingPool;
+
+// DbPool::begin() が返すトランザクションの型。テストではトランザクションの中のクエリも数える
+#[cfg(not(test))]
+pub type DbTransaction = sqlx::Transaction<'static, sqlx::MySql>;
+#[cfg(test)]
+pub type DbTransaction = crate::testsupport::CountingTransaction;
+
 // pub async fn create_pool() -> MySqlPool {
 //     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
 //     MySqlPool::connect(&database_url)
@@ -1 +1,5 @@
+pub mod busy_truck_sweeper;
+pub mod circuit_breaker;
 pub mod db;
+pub mod session_sweeper;
+pub mod shutdown_listener;
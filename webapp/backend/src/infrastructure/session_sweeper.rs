@@ -0,0 +1,108 @@
+use actix_web::rt::{self, task::JoinHandle, time};
+use log::{error, info};
+use sqlx::mysql::MySqlPool;
+use std::env;
+use std::time::Duration;
+
+// 環境変数から掃除の間隔(秒)を取得し、デフォルトを300秒に設定
+pub fn sweep_interval_from_env() -> Duration {
+    let seconds: u64 = env::var("SESSION_SWEEP_INTERVAL_SECONDS")
+        .unwrap_or_else(|_| "300".to_string())
+        .parse()
+        .expect("SESSION_SWEEP_INTERVAL_SECONDS must be a valid number");
+
+    Duration::from_secs(seconds)
+}
+
+// 有効期限切れのセッションを削除し、削除した件数を返す
+pub async fn sweep_expired_sessions(pool: &MySqlPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sessions WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+// 1回の掃除は単一の DELETE 文で完結するため、どのタイミングでタスクを止めても中途半端な状態は残らない
+pub fn spawn_session_sweeper(pool: MySqlPool, interval: Duration) -> JoinHandle<()> {
+    rt::spawn(async move {
+        let mut ticker = time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match sweep_expired_sessions(&pool).await {
+                Ok(0) => {}
+                Ok(count) => info!("期限切れのセッションを {} 件削除しました", count),
+                Err(e) => error!("期限切れのセッションの削除に失敗しました: {:?}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsupport::{seed_session, seed_user, test_pool};
+
+    async fn session_exists(pool: &MySqlPool, session_token: &str) -> bool {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM sessions WHERE session_token = ?")
+            .bind(session_token)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+            > 0
+    }
+
+    async fn expire(pool: &MySqlPool, session_token: &str) {
+        sqlx::query(
+            "UPDATE sessions SET expires_at = NOW() - INTERVAL 1 MINUTE WHERE session_token = ?",
+        )
+        .bind(session_token)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn sweep_removes_expired_sessions_and_keeps_valid_ones() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let user_id = seed_user(&pool, "client").await;
+        let expired_token = seed_session(&pool, user_id).await;
+        let valid_token = seed_session(&pool, user_id).await;
+        expire(&pool, &expired_token).await;
+
+        // 他のテストが作った期限切れのセッションも消えるため、件数は下限だけを確認する
+        assert!(sweep_expired_sessions(&pool).await.unwrap() >= 1);
+
+        assert!(!session_exists(&pool, &expired_token).await);
+        assert!(session_exists(&pool, &valid_token).await);
+    }
+
+    #[actix_rt::test]
+    async fn spawned_sweeper_runs_a_cycle_and_can_be_stopped() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let user_id = seed_user(&pool, "client").await;
+        let expired_token = seed_session(&pool, user_id).await;
+        let valid_token = seed_session(&pool, user_id).await;
+        expire(&pool, &expired_token).await;
+
+        // 最初の tick はすぐに発火するため、起動直後に1回掃除される
+        let handle = spawn_session_sweeper(pool.clone(), Duration::from_secs(3600));
+        let mut swept = false;
+        for _ in 0..50 {
+            if !session_exists(&pool, &expired_token).await {
+                swept = true;
+                break;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        handle.abort();
+
+        assert!(swept);
+        assert!(session_exists(&pool, &valid_token).await);
+        assert!(handle.await.unwrap_err().is_cancelled());
+    }
+}
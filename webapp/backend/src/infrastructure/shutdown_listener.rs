@@ -0,0 +1,56 @@
+use actix_web::dev::ServerHandle;
+use actix_web::rt::{self, signal, task::JoinHandle, time};
+use futures::future::{self, Either};
+use log::{info, warn};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::domains::shutdown::ShutdownCoordinator;
+
+// 環境変数から処理中の配車の完了を待つ上限(秒)を取得し、デフォルトを30秒に設定
+pub fn drain_timeout_from_env() -> Duration {
+    let seconds: u64 = env::var("SHUTDOWN_DRAIN_TIMEOUT_SECONDS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse()
+        .expect("SHUTDOWN_DRAIN_TIMEOUT_SECONDS must be a valid number");
+
+    Duration::from_secs(seconds)
+}
+
+async fn wait_for_signal() {
+    let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())
+        .expect("failed to install SIGTERM handler");
+    let ctrl_c = Box::pin(signal::ctrl_c());
+    let received = future::select(ctrl_c, Box::pin(terminate.recv())).await;
+    match received {
+        Either::Left(_) => info!("SIGINT を受信しました"),
+        Either::Right(_) => info!("SIGTERM を受信しました"),
+    }
+}
+
+// 停止シグナルを受けたら新しい配車の受付を止め、処理中の配車が終わってからサーバーを止める
+// actix-web 自身のシグナル処理は無効にしておくこと (先にサーバーが止まり、配車の完了を待てなくなる)
+pub fn spawn_shutdown_listener(
+    coordinator: Arc<ShutdownCoordinator>,
+    server: ServerHandle,
+    drain_timeout: Duration,
+) -> JoinHandle<()> {
+    rt::spawn(async move {
+        wait_for_signal().await;
+
+        info!("配車の受付を停止し、処理中の配車の完了を待ちます");
+        coordinator.start_draining();
+        if time::timeout(drain_timeout, coordinator.drained())
+            .await
+            .is_err()
+        {
+            warn!(
+                "処理中の配車が {:?} 以内に終わらなかったため停止します",
+                drain_timeout
+            );
+        }
+
+        server.stop(true).await;
+    })
+}
@@ -3,10 +3,20 @@ use std::sync::Arc;
 use actix_cors::Cors;
 use actix_web::{web, App, HttpServer};
 use api::{auth_handler, health_check_handler, map_handler, order_handler, tow_truck_handler};
+use domains::client_order_limiter::ClientOrderLimiter;
+use domains::dispatch_limiter::DispatchLimiter;
+use domains::distance_cache::DistanceCache;
+use domains::event_bus::{log_event, EventBus};
+use domains::graph_cache::{weight_scale_from_env, GraphCache};
 use domains::map_service::MapService;
+use domains::router::DijkstraRouter;
+use domains::shutdown::ShutdownCoordinator;
 use domains::{
-    auth_service::AuthService, order_service::OrderService, tow_truck_service::TowTruckService,
+    auth_service::AuthService,
+    order_service::{self, OrderService},
+    tow_truck_service::{self, TowTruckService},
 };
+use infrastructure::circuit_breaker::CircuitBreaker;
 use middlewares::auth_middleware::AuthMiddleware;
 use repositories::auth_repository::AuthRepositoryImpl;
 use repositories::map_repository::MapRepositoryImpl;
@@ -20,6 +30,8 @@ mod infrastructure;
 mod middlewares;
 mod models;
 mod repositories;
+#[cfg(test)]
+mod testsupport;
 mod utils;
 
 #[actix_web::main]
@@ -31,23 +43,65 @@ async fn main() -> std::io::Result<()> {
         port = 18080;
     }
 
-    let auth_service = web::Data::new(AuthService::new(AuthRepositoryImpl::new(pool.clone())));
-    let auth_service_for_middleware =
-        Arc::new(AuthService::new(AuthRepositoryImpl::new(pool.clone())));
+    // DB に接続できない状態が続いたときに、すべてのリポジトリの呼び出しをまとめて遮断する
+    let circuit_breaker = Arc::new(CircuitBreaker::from_env());
+    let auth_service = web::Data::new(AuthService::new(AuthRepositoryImpl::new(
+        pool.clone(),
+        circuit_breaker.clone(),
+    )));
+    let auth_service_for_middleware = Arc::new(AuthService::new(AuthRepositoryImpl::new(
+        pool.clone(),
+        circuit_breaker.clone(),
+    )));
+    let distance_cache = Arc::new(DistanceCache::from_env());
+    // 設定値の誤りはリクエスト時ではなく起動時に検出する
+    let weight_scale = weight_scale_from_env();
+    if let Some(factor) = weight_scale {
+        log::info!("辺の重みを {} 倍して経路を計算します", factor);
+    }
+    let graph_cache = Arc::new(GraphCache::new(weight_scale));
+    let event_bus = Arc::new(EventBus::new());
+    event_bus.subscribe(log_event);
     let tow_truck_service = web::Data::new(TowTruckService::new(
-        TowTruckRepositoryImpl::new(pool.clone()),
-        OrderRepositoryImpl::new(pool.clone()),
-        MapRepositoryImpl::new(pool.clone()),
+        TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker.clone()),
+        OrderRepositoryImpl::new(pool.clone(), circuit_breaker.clone()),
+        MapRepositoryImpl::new(pool.clone(), circuit_breaker.clone()),
+        distance_cache.clone(),
+        graph_cache.clone(),
+        Arc::new(DijkstraRouter),
+        event_bus.clone(),
+        tow_truck_service::stale_threshold_from_env(),
     ));
+    let shutdown = Arc::new(ShutdownCoordinator::new());
     let order_service = web::Data::new(OrderService::new(
-        OrderRepositoryImpl::new(pool.clone()),
-        TowTruckRepositoryImpl::new(pool.clone()),
-        AuthRepositoryImpl::new(pool.clone()),
-        MapRepositoryImpl::new(pool.clone()),
+        OrderRepositoryImpl::new(pool.clone(), circuit_breaker.clone()),
+        TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker.clone()),
+        AuthRepositoryImpl::new(pool.clone(), circuit_breaker.clone()),
+        MapRepositoryImpl::new(pool.clone(), circuit_breaker.clone()),
+        graph_cache.clone(),
+        Arc::new(DijkstraRouter),
+        DispatchLimiter::from_env(),
+        ClientOrderLimiter::from_env(),
+        event_bus.clone(),
+        shutdown.clone(),
+        order_service::tow_truck_speed_from_env(),
+    ));
+    let map_service = web::Data::new(MapService::new(
+        MapRepositoryImpl::new(pool.clone(), circuit_breaker.clone()),
+        distance_cache.clone(),
+        graph_cache.clone(),
     ));
-    let map_service = web::Data::new(MapService::new(MapRepositoryImpl::new(pool.clone())));
 
-    HttpServer::new(move || {
+    infrastructure::session_sweeper::spawn_session_sweeper(
+        pool.clone(),
+        infrastructure::session_sweeper::sweep_interval_from_env(),
+    );
+    infrastructure::busy_truck_sweeper::spawn_busy_truck_sweeper(
+        TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker.clone()),
+        infrastructure::busy_truck_sweeper::sweep_interval_from_env(),
+    );
+
+    let server = HttpServer::new(move || {
         let mut cors = Cors::default();
 
         cors = cors
@@ -91,6 +145,25 @@ async fn main() -> std::io::Result<()> {
                         web::resource("/user_image/{user_id}")
                             .route(web::get().to(auth_handler::user_profile_image_handler)),
                     )
+                    .service(
+                        web::scope("/user")
+                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+                            .service(
+                                web::resource("/list")
+                                    .route(web::get().to(auth_handler::get_users_handler)),
+                            )
+                            .service(
+                                web::resource("/search")
+                                    .route(web::get().to(auth_handler::search_users_handler)),
+                            )
+                            .service(
+                                web::resource("/{user_id}/session_count")
+                                    .route(web::get().to(auth_handler::get_session_count_handler)),
+                            )
+                            .service(web::resource("/{user_id}/promote_to_dispatcher").route(
+                                web::post().to(auth_handler::promote_to_dispatcher_handler),
+                            )),
+                    )
                     .service(
                         web::scope("/tow_truck")
                             .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
@@ -102,15 +175,68 @@ async fn main() -> std::io::Result<()> {
                                     web::post().to(tow_truck_handler::update_location_handler),
                                 ),
                             )
+                            .service(
+                                web::resource("/heartbeat")
+                                    .route(web::post().to(tow_truck_handler::heartbeat_handler)),
+                            )
+                            .service(web::resource("/status").route(
+                                web::post().to(tow_truck_handler::update_tow_truck_status_handler),
+                            ))
                             .service(web::resource("/nearest").route(
                                 web::get().to(
                                     tow_truck_handler::get_nearest_available_tow_trucks_handler,
                                 ),
                             ))
+                            .service(web::resource("/coverage").route(
+                                web::get().to(tow_truck_handler::get_tow_truck_coverage_handler),
+                            ))
+                            .service(
+                                web::resource("/reclaimable").route(
+                                    web::get()
+                                        .to(tow_truck_handler::get_reclaimable_tow_trucks_handler),
+                                ),
+                            )
+                            .service(web::resource("/centroid").route(
+                                web::get().to(tow_truck_handler::get_fleet_centroid_handler),
+                            ))
+                            .service(web::resource("/assign_pending").route(
+                                web::get().to(tow_truck_handler::assign_pending_in_area_handler),
+                            ))
+                            .service(
+                                web::resource("/reconcile_busy").route(
+                                    web::post()
+                                        .to(tow_truck_handler::reconcile_busy_tow_trucks_handler),
+                                ),
+                            )
+                            .service(
+                                web::resource("/utilization").route(
+                                    web::get().to(tow_truck_handler::get_utilization_handler),
+                                ),
+                            )
+                            .service(web::resource("/candidates").route(
+                                web::get().to(tow_truck_handler::get_candidates_for_order_handler),
+                            ))
+                            .service(web::resource("/availability").route(
+                                web::get().to(tow_truck_handler::get_fleet_availability_handler),
+                            ))
+                            .service(web::resource("/by_driver").route(
+                                web::get().to(tow_truck_handler::get_tow_trucks_by_driver_handler),
+                            ))
+                            .service(web::resource("/by_ids").route(
+                                web::get().to(tow_truck_handler::get_tow_trucks_by_ids_handler),
+                            ))
                             .service(
                                 web::resource("/{id}")
                                     .route(web::get().to(tow_truck_handler::get_tow_truck_handler)),
-                            ),
+                            )
+                            .service(
+                                web::resource("/{id}/nearest_depot").route(
+                                    web::get().to(tow_truck_handler::get_nearest_depot_handler),
+                                ),
+                            )
+                            .service(web::resource("/{id}/location_history").route(
+                                web::get().to(tow_truck_handler::get_location_history_handler),
+                            )),
                     )
                     .service(
                         web::scope("/order")
@@ -133,9 +259,90 @@ async fn main() -> std::io::Result<()> {
                             .service(web::resource("/dispatcher").route(
                                 web::post().to(order_handler::create_dispatcher_order_handler),
                             ))
+                            .service(
+                                web::resource("/bulk_complete").route(
+                                    web::post().to(order_handler::bulk_complete_orders_handler),
+                                ),
+                            )
+                            .service(web::resource("/sla_report").route(
+                                web::get().to(order_handler::get_completion_sla_report_handler),
+                            ))
+                            .service(
+                                web::resource("/reopen")
+                                    .route(web::post().to(order_handler::reopen_order_handler)),
+                            )
+                            .service(
+                                web::resource("/dispatch_fairness").route(
+                                    web::get().to(order_handler::get_dispatch_fairness_handler),
+                                ),
+                            )
+                            .service(
+                                web::resource("/completed").route(
+                                    web::get().to(order_handler::get_completed_orders_handler),
+                                ),
+                            )
+                            .service(web::resource("/dedupe").route(
+                                web::post().to(order_handler::dedupe_pending_orders_handler),
+                            ))
+                            .service(
+                                web::resource("/dispatcher_preferred").route(
+                                    web::post().to(
+                                        order_handler::create_dispatcher_order_preferred_handler,
+                                    ),
+                                ),
+                            )
+                            .service(web::resource("/dispatch_multi").route(
+                                web::post().to(order_handler::dispatch_required_tow_trucks_handler),
+                            ))
+                            .service(web::resource("/overdue").route(
+                                web::get().to(order_handler::get_overdue_dispatched_orders_handler),
+                            ))
+                            .service(
+                                web::resource("/relocate")
+                                    .route(web::post().to(order_handler::relocate_order_handler)),
+                            )
+                            .service(
+                                web::resource("/cancel")
+                                    .route(web::post().to(order_handler::cancel_order_handler)),
+                            )
+                            .service(
+                                web::resource("/cancel_area_pending").route(
+                                    web::post().to(order_handler::cancel_area_pending_handler),
+                                ),
+                            )
+                            .service(web::resource("/nearby").route(
+                                web::get().to(order_handler::get_pending_orders_within_handler),
+                            ))
+                            .service(web::resource("/dispatch_auto").route(
+                                web::post().to(order_handler::dispatch_and_describe_handler),
+                            ))
+                            .service(web::resource("/dispatch_minmax").route(
+                                web::post().to(order_handler::batch_dispatch_minmax_handler),
+                            ))
+                            .service(
+                                web::resource("/dispatcher_queue").route(
+                                    web::get().to(order_handler::get_dispatcher_queue_handler),
+                                ),
+                            )
+                            .service(
+                                web::resource("/recent")
+                                    .route(web::get().to(order_handler::get_recent_orders_handler)),
+                            )
+                            .service(
+                                web::resource("/area_stats")
+                                    .route(web::get().to(order_handler::get_area_stats_handler)),
+                            )
+                            .service(web::resource("/latest_by_client").route(
+                                web::get().to(order_handler::get_latest_orders_by_client_handler),
+                            ))
                             .service(
                                 web::resource("/{id}")
                                     .route(web::get().to(order_handler::get_order_handler)),
+                            )
+                            .service(
+                                web::resource("/{id}/dispatcher").route(
+                                    web::get().to(order_handler::get_order_dispatcher_handler),
+                                ),
                             ),
                     )
                     .service(
@@ -144,12 +351,45 @@ async fn main() -> std::io::Result<()> {
                             .service(
                                 web::resource("/update_edge")
                                     .route(web::put().to(map_handler::update_edge_handler)),
+                            )
+                            .service(
+                                web::resource("/neighborhood")
+                                    .route(web::get().to(map_handler::get_neighborhood_handler)),
+                            )
+                            .service(
+                                web::resource("/import")
+                                    .route(web::post().to(map_handler::import_map_handler)),
+                            )
+                            .service(
+                                web::resource("/route")
+                                    .route(web::get().to(map_handler::get_route_handler)),
+                            )
+                            .service(
+                                web::resource("/eccentricity")
+                                    .route(web::get().to(map_handler::get_eccentricity_handler)),
+                            )
+                            .service(
+                                web::resource("/network_size")
+                                    .route(web::get().to(map_handler::get_network_size_handler)),
+                            )
+                            .service(
+                                web::resource("/export")
+                                    .route(web::get().to(map_handler::export_area_graph_handler)),
                             ),
                     ),
             )
     })
     .bind(format!("0.0.0.0:{port}"))?
+    // 停止シグナルは shutdown_listener で受け、処理中の配車を待ってからサーバーを止める
+    .disable_signals()
     // .workers(1)
-    .run()
-    .await
+    .run();
+
+    infrastructure::shutdown_listener::spawn_shutdown_listener(
+        shutdown,
+        server.handle(),
+        infrastructure::shutdown_listener::drain_timeout_from_env(),
+    );
+
+    server.await
 }
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+// エリアIDを他の整数 (トラックIDやノードIDなど) と取り違えないための型
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[sqlx(transparent)]
+#[serde(transparent)]
+pub struct AreaId(pub i32);
+
+impl From<i32> for AreaId {
+    fn from(id: i32) -> Self {
+        AreaId(id)
+    }
+}
+
+impl From<AreaId> for i32 {
+    fn from(area_id: AreaId) -> Self {
+        area_id.0
+    }
+}
+
+// エリアごとの未配車の注文数・空きトラック数・平均完了時間
+#[derive(FromRow, Clone, Debug)]
+pub struct AreaStats {
+    pub area_id: AreaId,
+    pub pending_order_count: i64,
+    pub available_tow_truck_count: i64,
+    // 完了済みの注文が1件もないエリアは None
+    pub average_completion_seconds: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_id_converts_to_and_from_i32() {
+        let area_id: AreaId = 3.into();
+        assert_eq!(area_id, AreaId(3));
+        assert_eq!(i32::from(area_id), 3);
+    }
+
+    #[test]
+    fn area_id_serializes_as_a_bare_integer() {
+        assert_eq!(serde_json::to_string(&AreaId(3)).unwrap(), "3");
+        assert_eq!(serde_json::from_str::<AreaId>("3").unwrap(), AreaId(3));
+    }
+}
@@ -1,14 +1,17 @@
+use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-#[derive(FromRow, Clone, Debug)]
+#[derive(FromRow, Serialize, Deserialize, Clone, Debug)]
 pub struct Node {
     pub id: i32,
+    pub name: String,
+    pub area_id: i32,
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(FromRow, Clone, Debug)]
+#[derive(FromRow, Serialize, Deserialize, Clone, Debug)]
 pub struct Edge {
     pub node_a_id: i32,
     pub node_b_id: i32,
@@ -33,6 +36,30 @@ impl Graph {
         self.nodes.insert(node.id, node);
     }
 
+    pub fn contains_node(&self, node_id: i32) -> bool {
+        self.nodes.contains_key(&node_id)
+    }
+
+    // 辺は双方向に保持しているため、向きを問わず判定できる
+    pub fn contains_edge(&self, node_a_id: i32, node_b_id: i32) -> bool {
+        self.edges
+            .get(&node_a_id)
+            .is_some_and(|edges| edges.iter().any(|edge| edge.node_b_id == node_b_id))
+    }
+
+    // 別のグラフのノードと辺を取り込む (辺は双方向に保持した状態のまま写す)
+    pub fn merge(&mut self, other: &Graph) {
+        for node in other.nodes.values() {
+            self.add_node(node.clone());
+        }
+        for (&node_id, edges) in &other.edges {
+            self.edges
+                .entry(node_id)
+                .or_default()
+                .extend(edges.iter().cloned());
+        }
+    }
+
     pub fn add_edge(&mut self, edge: Edge) {
         self.edges
             .entry(edge.node_a_id)
@@ -50,6 +77,127 @@ impl Graph {
             .push(reverse_edge);
     }
 
+    // center から hops 本以内の辺でたどれるノードと、それらの間の辺だけを持つグラフを返す
+    // 部分グラフ内で完結する経路の距離は元のグラフと一致する
+    pub fn subgraph_within_hops(&self, center: i32, hops: usize) -> Graph {
+        let mut visited: HashSet<i32> = HashSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert(center);
+        queue.push_back((center, 0));
+
+        while let Some((node_id, depth)) = queue.pop_front() {
+            if depth == hops {
+                continue;
+            }
+            if let Some(edges) = self.edges.get(&node_id) {
+                for edge in edges {
+                    if visited.insert(edge.node_b_id) {
+                        queue.push_back((edge.node_b_id, depth + 1));
+                    }
+                }
+            }
+        }
+
+        let mut subgraph = Graph::new();
+        for node_id in &visited {
+            if let Some(node) = self.nodes.get(node_id) {
+                subgraph.add_node(node.clone());
+            }
+            if let Some(edges) = self.edges.get(node_id) {
+                let inner_edges: Vec<Edge> = edges
+                    .iter()
+                    .filter(|edge| visited.contains(&edge.node_b_id))
+                    .cloned()
+                    .collect();
+                if !inner_edges.is_empty() {
+                    subgraph.edges.insert(*node_id, inner_edges);
+                }
+            }
+        }
+
+        subgraph
+    }
+
+    // 取り込む地図データを検証し、ノードIDの重複と負の重みの辺をすべて列挙したエラーを返す
+    // 負の重みがあるとダイクストラ法の結果が正しくなくなるため、取り込む前に弾く
+    pub fn validate_parts(nodes: &[Node], edges: &[Edge]) -> Result<(), String> {
+        let mut seen_node_ids = HashSet::new();
+        let mut duplicate_node_ids: Vec<i32> = nodes
+            .iter()
+            .filter(|node| !seen_node_ids.insert(node.id))
+            .map(|node| node.id)
+            .collect();
+        duplicate_node_ids.sort_unstable();
+        duplicate_node_ids.dedup();
+
+        let negative_edges: Vec<String> = edges
+            .iter()
+            .filter(|edge| edge.weight < 0)
+            .map(|edge| format!("{}-{} ({})", edge.node_a_id, edge.node_b_id, edge.weight))
+            .collect();
+
+        let mut problems = Vec::new();
+        if !duplicate_node_ids.is_empty() {
+            problems.push(format!("duplicate node ids: {:?}", duplicate_node_ids));
+        }
+        if !negative_edges.is_empty() {
+            problems.push(format!(
+                "negative edge weights: [{}]",
+                negative_edges.join(", ")
+            ));
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(problems.join("; ")),
+        }
+    }
+
+    // チューニングの検証用に、すべての辺の重みを factor 倍する
+    // 整数倍なので経路の長さの大小関係はそのまま保たれる
+    // i32 の範囲を超える重みは上限に丸め、経路探索でも距離の加算は上限で止める
+    pub fn scale_weights(&mut self, factor: i32) {
+        for edges in self.edges.values_mut() {
+            for edge in edges {
+                edge.weight = edge.weight.saturating_mul(factor);
+            }
+        }
+    }
+
+    // 地図の規模の目安として、すべての辺の重みの合計を返す
+    // 双方向に保持している辺はノードの組ごとに1本として数え、同じ組に辺が複数ある場合は重みが最小のものを使う
+    pub fn total_weight(&self) -> i64 {
+        let mut weights: HashMap<(i32, i32), i32> = HashMap::new();
+        for edge in self.edges.values().flatten() {
+            let key = (
+                edge.node_a_id.min(edge.node_b_id),
+                edge.node_a_id.max(edge.node_b_id),
+            );
+            weights
+                .entry(key)
+                .and_modify(|weight| *weight = (*weight).min(edge.weight))
+                .or_insert(edge.weight);
+        }
+
+        weights.values().map(|&weight| i64::from(weight)).sum()
+    }
+
+    // 経路 (ノードIDの列) の隣り合うノード間の辺を返す
+    // 同じノード間に辺が複数ある場合は重みが最小のものを使い、辺のない区間があれば None を返す
+    pub fn path_edges(&self, path: &[i32]) -> Option<Vec<Edge>> {
+        path.windows(2)
+            .map(|pair| {
+                self.edges
+                    .get(&pair[0])?
+                    .iter()
+                    .filter(|edge| edge.node_b_id == pair[1])
+                    .min_by_key(|edge| edge.weight)
+                    .cloned()
+            })
+            .collect()
+    }
+
+    #[allow(dead_code)]
     pub fn shortest_path(&self, from_node_id: i32, to_node_id: i32) -> i32 {
         let mut distances = HashMap::new();
         distances.insert(from_node_id, 0);
@@ -74,3 +222,350 @@ impl Graph {
         distances.get(&to_node_id).cloned().unwrap_or(i32::MAX)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn node(id: i32) -> Node {
+        Node {
+            id,
+            name: format!("node{}", id),
+            area_id: 1,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    fn graph_from(edges: &[(i32, i32, i32)]) -> Graph {
+        let mut graph = Graph::new();
+        for &(node_a_id, node_b_id, weight) in edges {
+            graph.add_node(node(node_a_id));
+            graph.add_node(node(node_b_id));
+            graph.add_edge(Edge {
+                node_a_id,
+                node_b_id,
+                weight,
+            });
+        }
+        graph
+    }
+
+    // 辺のリストだけを使い、start から target までのすべての単純経路をたどって最短の長さを求める
+    fn brute_force_distance(
+        edges: &[(i32, i32, i32)],
+        start: i32,
+        target: i32,
+        visited: &mut Vec<i32>,
+    ) -> Option<i32> {
+        if start == target {
+            return Some(0);
+        }
+        visited.push(start);
+        let mut best: Option<i32> = None;
+        for &(node_a_id, node_b_id, weight) in edges {
+            let next = match (node_a_id == start, node_b_id == start) {
+                (true, _) => node_b_id,
+                (_, true) => node_a_id,
+                _ => continue,
+            };
+            if visited.contains(&next) {
+                continue;
+            }
+            if let Some(rest) = brute_force_distance(edges, next, target, visited) {
+                let distance = weight + rest;
+                best = Some(best.map_or(distance, |best| best.min(distance)));
+            }
+        }
+        visited.pop();
+        best
+    }
+
+    #[test]
+    fn dijkstra_matches_brute_force_on_random_graphs() {
+        // 失敗したときに同じグラフを再現できるよう、乱数の種を固定する
+        const SEED: u64 = 444;
+        let mut rng = StdRng::seed_from_u64(SEED);
+
+        for _ in 0..200 {
+            // 辺を持たないノードや同じノード間の複数の辺、自己ループも含めて作る
+            let node_count = rng.gen_range(1..=7);
+            let edge_count = rng.gen_range(0..=node_count * 2);
+            let edges: Vec<(i32, i32, i32)> = (0..edge_count)
+                .map(|_| {
+                    (
+                        rng.gen_range(1..=node_count),
+                        rng.gen_range(1..=node_count),
+                        rng.gen_range(1..=20),
+                    )
+                })
+                .collect();
+            let mut graph = graph_from(&edges);
+            for id in 1..=node_count {
+                graph.add_node(node(id));
+            }
+
+            for start in 1..=node_count {
+                let distances = graph.dijkstra(start);
+                for target in 1..=node_count {
+                    let expected = brute_force_distance(&edges, start, target, &mut Vec::new());
+                    assert_eq!(
+                        distances.get(&target).copied(),
+                        expected,
+                        "seed {} edges {:?} from {} to {}",
+                        SEED,
+                        edges,
+                        start,
+                        target
+                    );
+                    assert_eq!(graph.distance_to(start, target), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scaling_weights_preserves_shortest_path_order() {
+        // 1 -> 2 -> 4 (長さ 3) と 1 -> 3 -> 4 (長さ 4)
+        let mut graph = graph_from(&[(1, 2, 1), (2, 4, 2), (1, 3, 2), (3, 4, 2)]);
+        let before = graph.sorted_reachable(1, &[2, 3, 4]);
+
+        graph.scale_weights(7);
+
+        assert_eq!(graph.shortest_path_nodes(1, 4), Some(vec![1, 2, 4]));
+        let after = graph.sorted_reachable(1, &[2, 3, 4]);
+        let order =
+            |reachable: &[(i32, i32)]| reachable.iter().map(|&(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(order(&before), order(&after));
+        for (&(_, before_distance), &(_, after_distance)) in before.iter().zip(&after) {
+            assert_eq!(after_distance, before_distance * 7);
+        }
+    }
+
+    #[test]
+    fn saturated_weights_do_not_overflow_distances() {
+        let mut graph = graph_from(&[(1, 2, 1_000_000), (2, 3, 1_000_000), (3, 4, 1)]);
+
+        graph.scale_weights(i32::MAX);
+
+        assert!(graph.edges[&1].iter().all(|edge| edge.weight == i32::MAX));
+        let distances = graph.dijkstra(1);
+        assert_eq!(distances[&2], i32::MAX);
+        assert_eq!(distances[&4], i32::MAX);
+        assert_eq!(graph.distance_to(1, 4), Some(i32::MAX));
+        assert_eq!(graph.multi_source_dijkstra(&[1])[&3], (i32::MAX, 1));
+    }
+
+    #[test]
+    fn merge_connects_graphs_through_shared_edges() {
+        // エリアをまたぐ辺 (2, 3) は node_a のエリアのグラフにだけ含まれる
+        let mut area_1 = graph_from(&[(1, 2, 1), (2, 3, 5)]);
+        area_1.nodes.remove(&3);
+        let area_2 = graph_from(&[(3, 4, 2)]);
+
+        let mut combined = Graph::new();
+        combined.merge(&area_1);
+        combined.merge(&area_2);
+
+        assert_eq!(combined.nodes.len(), 4);
+        assert_eq!(combined.distance_to(1, 4), Some(8));
+        assert_eq!(combined.distance_to(4, 1), Some(8));
+    }
+
+    #[test]
+    fn subgraph_within_hops_keeps_only_nearby_nodes_and_their_edges() {
+        // 1 - 2 - 3 - 4 の一本道と、1 - 3 を直接つなぐ重い辺
+        let graph = graph_from(&[(1, 2, 1), (2, 3, 1), (3, 4, 1), (1, 3, 10)]);
+
+        let center_only = graph.subgraph_within_hops(1, 0);
+        assert_eq!(center_only.nodes.len(), 1);
+        assert!(center_only.edges.is_empty());
+
+        let subgraph = graph.subgraph_within_hops(1, 1);
+        let mut node_ids: Vec<i32> = subgraph.nodes.keys().cloned().collect();
+        node_ids.sort_unstable();
+        assert_eq!(node_ids, vec![1, 2, 3]);
+        // 範囲外のノード 4 への辺は含めず、範囲内のノード同士の辺 (2, 3) は残す
+        assert!(!subgraph.contains_edge(3, 4));
+        assert!(subgraph.contains_edge(2, 3));
+        assert_eq!(subgraph.distance_to(1, 3), Some(2));
+    }
+
+    #[test]
+    fn subgraph_within_hops_of_an_unknown_center_is_empty() {
+        let graph = graph_from(&[(1, 2, 1)]);
+
+        let subgraph = graph.subgraph_within_hops(99, 2);
+
+        assert!(subgraph.nodes.is_empty());
+        assert!(subgraph.edges.is_empty());
+    }
+
+    #[cfg(feature = "routing-trace")]
+    #[test]
+    fn dijkstra_trace_settles_each_reachable_node_once_in_distance_order() {
+        let mut graph = graph_from(&[(1, 2, 4), (1, 3, 1), (3, 2, 1), (2, 4, 5)]);
+        graph.add_node(node(5));
+
+        let (distances, settle_order) = graph.dijkstra_trace(1);
+
+        assert_eq!(distances, graph.dijkstra(1));
+        assert_eq!(settle_order, vec![1, 3, 2, 4]);
+    }
+
+    #[test]
+    fn shortest_path_prefers_the_lighter_route_over_fewer_hops() {
+        // 1 - 4 を直接つなぐ辺 (重み 10) より、1 - 2 - 3 - 4 (重み 3) を選ぶ
+        let mut graph = graph_from(&[(1, 4, 10), (1, 2, 1), (2, 3, 1), (3, 4, 1)]);
+        graph.add_node(node(5));
+
+        assert_eq!(graph.shortest_path_nodes(1, 4), Some(vec![1, 2, 3, 4]));
+        assert_eq!(graph.shortest_path_nodes(1, 1), Some(vec![1]));
+        assert_eq!(graph.shortest_path_nodes(1, 5), None);
+    }
+
+    #[test]
+    fn path_edges_use_the_lightest_edge_of_each_segment() {
+        let graph = graph_from(&[(1, 2, 5), (1, 2, 3), (2, 3, 4)]);
+
+        let edges = graph.path_edges(&[1, 2, 3]).unwrap();
+        let segments: Vec<(i32, i32, i32)> = edges
+            .iter()
+            .map(|edge| (edge.node_a_id, edge.node_b_id, edge.weight))
+            .collect();
+        assert_eq!(segments, vec![(1, 2, 3), (2, 3, 4)]);
+        assert!(graph.path_edges(&[1]).unwrap().is_empty());
+        // 辺のない区間を含む経路は None
+        assert!(graph.path_edges(&[1, 3]).is_none());
+    }
+
+    #[test]
+    fn contains_node_and_edge_in_either_direction() {
+        let mut graph = graph_from(&[(1, 2, 1)]);
+        graph.add_node(node(3));
+
+        assert!(graph.contains_node(3));
+        assert!(!graph.contains_node(4));
+        assert!(graph.contains_edge(1, 2));
+        assert!(graph.contains_edge(2, 1));
+        assert!(!graph.contains_edge(1, 3));
+        assert!(!graph.contains_edge(4, 1));
+    }
+
+    #[test]
+    fn eccentricity_is_the_farthest_reachable_distance() {
+        // 1 - 2 - 3 (重み 2, 3) と、どこにもつながらないノード 4
+        let mut graph = graph_from(&[(1, 2, 2), (2, 3, 3)]);
+        graph.add_node(node(4));
+
+        assert_eq!(graph.eccentricity(1), Some(5));
+        assert_eq!(graph.eccentricity(2), Some(3));
+        assert_eq!(graph.eccentricity(4), None);
+    }
+
+    #[test]
+    fn validate_parts_accepts_unique_nodes_and_non_negative_weights() {
+        let nodes = vec![node(1), node(2)];
+        let edges = vec![Edge {
+            node_a_id: 1,
+            node_b_id: 2,
+            weight: 0,
+        }];
+
+        assert_eq!(Graph::validate_parts(&nodes, &edges), Ok(()));
+    }
+
+    #[test]
+    fn validate_parts_lists_every_duplicate_node_and_negative_edge() {
+        let nodes = vec![node(2), node(1), node(2), node(1), node(2)];
+        let edges = vec![
+            Edge {
+                node_a_id: 1,
+                node_b_id: 2,
+                weight: -1,
+            },
+            Edge {
+                node_a_id: 2,
+                node_b_id: 1,
+                weight: 3,
+            },
+            Edge {
+                node_a_id: 2,
+                node_b_id: 3,
+                weight: -4,
+            },
+        ];
+
+        assert_eq!(
+            Graph::validate_parts(&nodes, &edges),
+            Err(
+                "duplicate node ids: [1, 2]; negative edge weights: [1-2 (-1), 2-3 (-4)]"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn mutually_reachable_requires_a_path_in_both_directions() {
+        let mut graph = graph_from(&[(1, 2, 1)]);
+        // 2 -> 3 の一方通行の辺を直接組み立てる
+        graph.add_node(node(3));
+        graph.edges.entry(2).or_default().push(Edge {
+            node_a_id: 2,
+            node_b_id: 3,
+            weight: 1,
+        });
+        graph.add_node(node(4));
+
+        assert!(graph.mutually_reachable(1, 2));
+        assert!(graph.mutually_reachable(1, 1));
+        assert!(!graph.mutually_reachable(1, 3));
+        assert!(!graph.mutually_reachable(3, 1));
+        assert!(!graph.mutually_reachable(1, 4));
+    }
+
+    #[test]
+    fn sorted_reachable_orders_by_distance_and_keeps_candidate_order_on_ties() {
+        // 1 から 2 と 3 は距離 2、4 は距離 1、5 には到達できない
+        let mut graph = graph_from(&[(1, 2, 2), (1, 3, 2), (1, 4, 1)]);
+        graph.add_node(node(5));
+
+        assert_eq!(
+            graph.sorted_reachable(1, &[3, 5, 2, 4, 3, 1]),
+            vec![(1, 0), (4, 1), (3, 2), (2, 2), (3, 2)]
+        );
+        assert!(graph.sorted_reachable(1, &[]).is_empty());
+    }
+
+    #[test]
+    fn distance_to_stops_only_once_the_target_is_settled() {
+        // 目標 4 は 1 から直接の重い辺で最初に見つかるが、最短は 1 - 2 - 3 - 4 (重み 3)
+        // 見つけた時点で打ち切ると 10 を返してしまう
+        let mut graph = graph_from(&[(1, 4, 10), (1, 2, 1), (2, 3, 1), (3, 4, 1), (4, 5, 1)]);
+        graph.add_node(node(6));
+
+        assert_eq!(graph.distance_to(1, 4), Some(3));
+        assert_eq!(graph.distance_to(1, 5), Some(4));
+        assert_eq!(graph.distance_to(1, 1), Some(0));
+        assert_eq!(graph.distance_to(1, 6), None);
+        assert_eq!(graph.distance_to(99, 1), None);
+    }
+
+    #[test]
+    fn total_weight_counts_each_node_pair_once_with_its_lightest_edge() {
+        // 辺 (1, 2) は重み 5 と 3 の2本があり、3 だけを数える
+        let graph = graph_from(&[(1, 2, 5), (2, 1, 3), (2, 3, 4)]);
+
+        assert_eq!(graph.total_weight(), 7);
+        assert_eq!(Graph::new().total_weight(), 0);
+    }
+
+    #[test]
+    fn total_weight_does_not_overflow_i32() {
+        let graph = graph_from(&[(1, 2, i32::MAX), (2, 3, i32::MAX)]);
+
+        assert_eq!(graph.total_weight(), 2 * i64::from(i32::MAX));
+    }
+}
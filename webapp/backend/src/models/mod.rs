@@ -1,3 +1,4 @@
+pub mod area;
 pub mod graph;
 pub mod order;
 pub mod tow_truck;
@@ -1,5 +1,112 @@
+use crate::errors::AppError;
+use crate::models::area::AreaId;
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 use sqlx::FromRow;
+use std::str::FromStr;
+
+// API では常に as_str と同じ小文字の値で出力する
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Pending,
+    Dispatched,
+    Completed,
+    Canceled,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Dispatched => "dispatched",
+            OrderStatus::Completed => "completed",
+            OrderStatus::Canceled => "canceled",
+        }
+    }
+
+    // 許可する状態遷移: pending -> dispatched -> completed, pending -> canceled
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        matches!(
+            (self, next),
+            (OrderStatus::Pending, OrderStatus::Dispatched)
+                | (OrderStatus::Dispatched, OrderStatus::Completed)
+                | (OrderStatus::Pending, OrderStatus::Canceled)
+        )
+    }
+
+    // "pending,dispatched" のようなカンマ区切りの指定を解釈する
+    pub fn parse_list(value: &str) -> Result<Vec<OrderStatus>, AppError> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|status| !status.is_empty())
+            .map(OrderStatus::from_str)
+            .collect()
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(OrderStatus::Pending),
+            "dispatched" => Ok(OrderStatus::Dispatched),
+            "completed" => Ok(OrderStatus::Completed),
+            "canceled" => Ok(OrderStatus::Canceled),
+            _ => Err(AppError::BadRequest),
+        }
+    }
+}
+
+// キャンセル理由として受け付ける値
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelReason {
+    // 依頼者による取り消し
+    CustomerCanceled,
+    // 対応できるトラックがいない
+    NoCoverage,
+    // 重複した注文
+    Duplicate,
+    Other,
+}
+
+impl CancelReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CancelReason::CustomerCanceled => "customer_canceled",
+            CancelReason::NoCoverage => "no_coverage",
+            CancelReason::Duplicate => "duplicate",
+            CancelReason::Other => "other",
+        }
+    }
+}
+
+impl FromStr for CancelReason {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "customer_canceled" => Ok(CancelReason::CustomerCanceled),
+            "no_coverage" => Ok(CancelReason::NoCoverage),
+            "duplicate" => Ok(CancelReason::Duplicate),
+            "other" => Ok(CancelReason::Other),
+            _ => Err(AppError::BadRequest),
+        }
+    }
+}
+
+// 注文一覧の絞り込み条件
+#[derive(Clone, Debug, Default)]
+pub struct OrderFilter {
+    // 空の場合はステータスで絞り込まない
+    pub statuses: Vec<OrderStatus>,
+    pub area: Option<AreaId>,
+    // Some(false) でトラック未割り当て、Some(true) で割り当て済みのみ
+    pub assigned: Option<bool>,
+}
 
 #[derive(FromRow, Clone, Debug)]
 pub struct Order {
@@ -9,8 +116,92 @@ pub struct Order {
     pub tow_truck_id: Option<i32>,
     pub status: String,
     pub node_id: i32,
+    pub destination_node_id: Option<i32>,
     pub car_value: f64,
+    pub contact_phone: Option<String>,
+    pub trucks_required: i32,
+    pub cancel_reason: Option<String>,
     pub order_time: DateTime<Utc>,
+    // 完了時刻の正は completed_orders.completed_time (一覧・詳細の取得では結合して埋める)
     pub completed_time: Option<DateTime<Utc>>,
     pub area_id: i32,
+    // 配車時点でのトラックから注文地点までの到着予定時間(秒)
+    pub eta_seconds: Option<i32>,
+}
+
+#[derive(FromRow, Clone, Debug)]
+pub struct CompletedOrder {
+    pub id: i32,
+    pub order_id: i32,
+    pub tow_truck_id: i32,
+    pub completed_time: DateTime<Utc>,
+}
+
+// 完了済み注文の一覧用に、注文側の情報を結合したもの
+#[derive(FromRow, Clone, Debug)]
+pub struct CompletedOrderDetail {
+    pub id: i32,
+    pub order_id: i32,
+    pub tow_truck_id: i32,
+    pub client_id: i32,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: DateTime<Utc>,
+}
+
+// トラックごとの配車・完了の履歴
+#[derive(FromRow, Clone, Debug)]
+pub struct OrderEvent {
+    pub tow_truck_id: i32,
+    // "dispatched" または "completed"
+    pub event_type: String,
+    pub occurred_at: DateTime<Utc>,
+}
+
+// 一括完了処理における注文ごとの結果
+#[derive(Clone, Debug)]
+pub enum CompletionOutcome {
+    Completed(CompletedOrder, Order),
+    AlreadyCompleted,
+    NotDispatched,
+    // 担当トラックが注文と別のエリアに所属している
+    AreaMismatch,
+    NotFound,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_STATUSES: [OrderStatus; 4] = [
+        OrderStatus::Pending,
+        OrderStatus::Dispatched,
+        OrderStatus::Completed,
+        OrderStatus::Canceled,
+    ];
+
+    #[test]
+    fn status_serializes_as_its_lowercase_name() {
+        assert_eq!(
+            serde_json::to_string(&OrderStatus::Dispatched).unwrap(),
+            "\"dispatched\""
+        );
+        for status in ALL_STATUSES {
+            assert_eq!(
+                serde_json::to_value(status).unwrap(),
+                serde_json::Value::from(status.as_str())
+            );
+            assert_eq!(status.as_str().parse::<OrderStatus>().unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn parses_status_lists_and_rejects_unknown_values() {
+        assert_eq!(
+            OrderStatus::parse_list(" pending, dispatched ,").unwrap(),
+            vec![OrderStatus::Pending, OrderStatus::Dispatched]
+        );
+        assert!(OrderStatus::parse_list("").unwrap().is_empty());
+        assert!(OrderStatus::parse_list("pending,Dispatched").is_err());
+    }
 }
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use sqlx::FromRow;
 
 #[derive(FromRow, Clone, Debug)]
@@ -8,4 +9,17 @@ pub struct TowTruck {
     pub status: String,
     pub area_id: i32,
     pub node_id: i32,
+    // 車種と年式 (未登録のトラックは None)
+    pub model: Option<String>,
+    pub year: Option<i32>,
+    // 論理削除済みのトラックも含めて一覧を取得した場合にのみ埋める
+    #[sqlx(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+// 位置情報の履歴の1件 (locations の1行)
+#[derive(FromRow, Clone, Debug)]
+pub struct LocationRecord {
+    pub node_id: i32,
+    pub timestamp: DateTime<Utc>,
 }
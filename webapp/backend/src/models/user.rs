@@ -1,18 +1,58 @@
+use crate::errors::AppError;
 use sqlx::FromRow;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Client,
+    Dispatcher,
+    Driver,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Client => "client",
+            Role::Dispatcher => "dispatcher",
+            Role::Driver => "driver",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(Role::Client),
+            "dispatcher" => Ok(Role::Dispatcher),
+            "driver" => Ok(Role::Driver),
+            "admin" => Ok(Role::Admin),
+            _ => Err(AppError::BadRequest),
+        }
+    }
+}
 
 #[derive(FromRow, Clone, Debug)]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub password: String,
+    // SELECT * の列を受けるために持つが、画像名は find_profile_image_name_by_user_id で別に引く
+    #[allow(dead_code)]
     pub profile_image: String,
     pub role: String,
 }
 
 #[derive(FromRow, Clone, Debug)]
 pub struct Session {
+    // SELECT * の列を受けるために持つが、セッションはトークンで引くため参照しない
+    #[allow(dead_code)]
     pub id: i32,
     pub user_id: i32,
+    #[allow(dead_code)]
     pub session_token: String,
     pub is_valid: bool,
 }
@@ -23,3 +63,34 @@ pub struct Dispatcher {
     pub user_id: i32,
     pub area_id: i32,
 }
+
+#[derive(FromRow, Clone, Debug)]
+pub struct DispatcherProfile {
+    pub id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub area_id: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn role_round_trips_through_its_string_form() {
+        for role in [Role::Client, Role::Dispatcher, Role::Driver, Role::Admin] {
+            assert_eq!(role.as_str().parse::<Role>().unwrap(), role);
+        }
+    }
+
+    #[test]
+    fn rejects_roles_outside_the_allowlist() {
+        for role in ["", "superuser", "Admin", " driver"] {
+            assert!(
+                matches!(role.parse::<Role>(), Err(AppError::BadRequest)),
+                "{}",
+                role
+            );
+        }
+    }
+}
@@ -1,60 +1,119 @@
 use crate::errors::AppError;
-use crate::models::user::{Dispatcher, User};
+use crate::infrastructure::circuit_breaker::CircuitBreaker;
+use crate::infrastructure::db::DbPool;
+use crate::models::user::{Dispatcher, DispatcherProfile, User};
 use crate::{domains::auth_service::AuthRepository, models::user::Session};
-use sqlx::mysql::MySqlPool;
 use std::collections::HashMap;
+use std::sync::Arc;
 #[derive(Debug)]
 pub struct AuthRepositoryImpl {
-    pool: MySqlPool,
+    pool: DbPool,
+    circuit_breaker: Arc<CircuitBreaker>,
 }
 impl AuthRepositoryImpl {
-    pub fn new(pool: MySqlPool) -> Self {
-        AuthRepositoryImpl { pool }
+    pub fn new(pool: impl Into<DbPool>, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        AuthRepositoryImpl {
+            pool: pool.into(),
+            circuit_breaker,
+        }
     }
 }
 impl AuthRepository for AuthRepositoryImpl {
     // 既存の find_user_by_id メソッド
     async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError> {
-        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
-            .bind(id)
-            .fetch_optional(&self.pool)
-            .await?;
-        Ok(user)
+        self.circuit_breaker
+            .call(async {
+                let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+                Ok(user)
+            })
+            .await
     }
     // 追加: 複数のユーザーを一度に取得するメソッド
     async fn find_users_by_ids(&self, ids: &[i32]) -> Result<Vec<User>, AppError> {
-        if ids.is_empty() {
-            return Ok(vec![]); // 空のIDリストに対しては空の結果を返す
-        }
-        // プレースホルダーの生成
-        let query_placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-        // クエリ文字列を作成
-        let query = format!("SELECT * FROM users WHERE id IN ({})", query_placeholders);
-        // クエリを実行し、IDリストをバインド
-        let mut query_builder = sqlx::query_as::<_, User>(&query);
-        for id in ids {
-            query_builder = query_builder.bind(id);
-        }
-        // クエリの実行
-        let users = query_builder.fetch_all(&self.pool).await?;
+        self.circuit_breaker
+            .call(async {
+                if ids.is_empty() {
+                    return Ok(vec![]); // 空のIDリストに対しては空の結果を返す
+                }
+                // プレースホルダーの生成
+                let query_placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+                // クエリ文字列を作成
+                let query = format!("SELECT * FROM users WHERE id IN ({})", query_placeholders);
+                // クエリを実行し、IDリストをバインド
+                let mut query_builder = sqlx::query_as::<_, User>(&query);
+                for id in ids {
+                    query_builder = query_builder.bind(id);
+                }
+                // クエリの実行
+                let users = query_builder.fetch_all(&self.pool).await?;
+                Ok(users)
+            })
+            .await
+    }
+    // find_users_by_ids は DB の返した順に並ぶため、指定したIDの順に並べ直す
+    // 存在しないIDの位置には None を入れる
+    // DB への問い合わせは find_users_by_ids が遮断器を通して行うため、ここでは二重に通さない
+    async fn find_users_by_ids_in_order(&self, ids: &[i32]) -> Result<Vec<Option<User>>, AppError> {
+        let user_map: HashMap<i32, User> = self
+            .find_users_by_ids(ids)
+            .await?
+            .into_iter()
+            .map(|user| (user.id, user))
+            .collect();
+        let users = ids.iter().map(|id| user_map.get(id).cloned()).collect();
         Ok(users)
     }
+    // LIKE の特殊文字 (%, _, \) はエスケープし、入力をそのまま前方一致の文字列として扱う
+    async fn search_users_by_username(
+        &self,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<User>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let escaped_prefix = prefix
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                let users = sqlx::query_as::<_, User>(
+                    "SELECT * FROM users WHERE username LIKE ? ORDER BY username ASC, id ASC LIMIT ?",
+                )
+                .bind(format!("{}%", escaped_prefix))
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(users)
+            })
+            .await
+    }
     async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError> {
-        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
-            .bind(username)
-            .fetch_optional(&self.pool)
-            .await?;
-        Ok(user)
+        self.circuit_breaker
+            .call(async {
+                let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
+                    .bind(username)
+                    .fetch_optional(&self.pool)
+                    .await?;
+                Ok(user)
+            })
+            .await
     }
     async fn find_profile_image_name_by_user_id(
         &self,
         user_id: i32,
     ) -> Result<Option<String>, AppError> {
-        let profile_image_name = sqlx::query_scalar("SELECT profile_image FROM users WHERE id = ?")
-            .bind(user_id)
-            .fetch_optional(&self.pool)
-            .await?;
-        Ok(profile_image_name)
+        self.circuit_breaker
+            .call(async {
+                let profile_image_name =
+                    sqlx::query_scalar("SELECT profile_image FROM users WHERE id = ?")
+                        .bind(user_id)
+                        .fetch_optional(&self.pool)
+                        .await?;
+                Ok(profile_image_name)
+            })
+            .await
     }
     async fn create_user(
         &self,
@@ -62,90 +121,338 @@ impl AuthRepository for AuthRepositoryImpl {
         password: &str,
         role: &str,
     ) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO users (username, password, role) VALUES (?, ?, ?)")
-            .bind(username)
-            .bind(password)
-            .bind(role)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        self.circuit_breaker
+            .call(async {
+                sqlx::query("INSERT INTO users (username, password, role) VALUES (?, ?, ?)")
+                    .bind(username)
+                    .bind(password)
+                    .bind(role)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            })
+            .await
     }
     async fn create_session(&self, user_id: i32, session_token: &str) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO sessions (user_id, session_token) VALUES (?, ?)")
-            .bind(user_id)
-            .bind(session_token)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        self.circuit_breaker
+            .call(async {
+                sqlx::query("INSERT INTO sessions (user_id, session_token) VALUES (?, ?)")
+                    .bind(user_id)
+                    .bind(session_token)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            })
+            .await
     }
     async fn delete_session(&self, session_token: &str) -> Result<(), AppError> {
-        sqlx::query("DELETE FROM sessions WHERE session_token = ?")
-            .bind(session_token)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        self.circuit_breaker
+            .call(async {
+                sqlx::query("DELETE FROM sessions WHERE session_token = ?")
+                    .bind(session_token)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            })
+            .await
+    }
+    async fn count_valid_sessions(&self, user_id: i32) -> Result<i64, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let count = sqlx::query_scalar(
+                    "SELECT COUNT(*) FROM sessions WHERE user_id = ? AND expires_at > NOW()",
+                )
+                .bind(user_id)
+                .fetch_one(&self.pool)
+                .await?;
+                Ok(count)
+            })
+            .await
     }
     async fn find_session_by_session_token(
         &self,
         session_token: &str,
     ) -> Result<Session, AppError> {
-        let session =
-            sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE session_token = ?")
+        self.circuit_breaker
+            .call(async {
+                let session = sqlx::query_as::<_, Session>(
+                    "SELECT * FROM sessions WHERE session_token = ? AND expires_at > NOW()",
+                )
                 .bind(session_token)
                 .fetch_one(&self.pool)
                 .await?;
-        Ok(session)
+                Ok(session)
+            })
+            .await
     }
     async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError> {
-        let dispatcher = sqlx::query_as::<_, Dispatcher>("SELECT * FROM dispatchers WHERE id = ?")
-            .bind(id)
-            .fetch_optional(&self.pool)
-            .await?;
-        Ok(dispatcher)
+        self.circuit_breaker
+            .call(async {
+                let dispatcher =
+                    sqlx::query_as::<_, Dispatcher>("SELECT * FROM dispatchers WHERE id = ?")
+                        .bind(id)
+                        .fetch_optional(&self.pool)
+                        .await?;
+                Ok(dispatcher)
+            })
+            .await
     }
     // 追加: 複数のディスパッチャーを一度に取得するメソッド
     async fn find_dispatchers_by_ids(&self, ids: &[i32]) -> Result<Vec<Dispatcher>, AppError> {
-        if ids.is_empty() {
-            return Ok(vec![]); // 空のIDリストに対しては空の結果を返す
-        }
-        // プレースホルダーの生成
-        let query_placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-        // クエリ文字列を作成
-        let query = format!(
-            "SELECT * FROM dispatchers WHERE id IN ({})",
-            query_placeholders
-        );
-        // クエリを実行し、IDリストをバインド
-        let mut query_builder = sqlx::query_as::<_, Dispatcher>(&query);
-        for id in ids {
-            query_builder = query_builder.bind(id);
-        }
-        // クエリの実行
-        let dispatchers = query_builder.fetch_all(&self.pool).await?;
-        Ok(dispatchers)
+        self.circuit_breaker
+            .call(async {
+                if ids.is_empty() {
+                    return Ok(vec![]); // 空のIDリストに対しては空の結果を返す
+                }
+                // プレースホルダーの生成
+                let query_placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+                // クエリ文字列を作成
+                let query = format!(
+                    "SELECT * FROM dispatchers WHERE id IN ({})",
+                    query_placeholders
+                );
+                // クエリを実行し、IDリストをバインド
+                let mut query_builder = sqlx::query_as::<_, Dispatcher>(&query);
+                for id in ids {
+                    query_builder = query_builder.bind(id);
+                }
+                // クエリの実行
+                let dispatchers = query_builder.fetch_all(&self.pool).await?;
+                Ok(dispatchers)
+            })
+            .await
     }
 
     async fn find_dispatcher_by_user_id(
         &self,
         user_id: i32,
     ) -> Result<Option<Dispatcher>, AppError> {
-        let dispatcher =
-            sqlx::query_as::<_, Dispatcher>("SELECT * FROM dispatchers WHERE user_id = ?")
-                .bind(user_id)
-                .fetch_optional(&self.pool)
-                .await?;
-        Ok(dispatcher)
+        self.circuit_breaker
+            .call(async {
+                let dispatcher =
+                    sqlx::query_as::<_, Dispatcher>("SELECT * FROM dispatchers WHERE user_id = ?")
+                        .bind(user_id)
+                        .fetch_optional(&self.pool)
+                        .await?;
+                Ok(dispatcher)
+            })
+            .await
     }
+    // ディスパッチャーの行は、dispatcher ロールを持つ既存ユーザーに対してのみ1件作成する
     async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO dispatchers (user_id, area_id) VALUES (?, ?)")
-            .bind(user_id)
-            .bind(area_id)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+                let role: Option<String> =
+                    sqlx::query_scalar("SELECT role FROM users WHERE id = ? FOR UPDATE")
+                        .bind(user_id)
+                        .fetch_optional(&mut tx)
+                        .await?;
+                match role.as_deref() {
+                    Some("dispatcher") => {}
+                    Some(_) => return Err(AppError::BadRequest),
+                    None => return Err(AppError::NotFound),
+                }
+                let existing: Option<i32> =
+                    sqlx::query_scalar("SELECT id FROM dispatchers WHERE user_id = ?")
+                        .bind(user_id)
+                        .fetch_optional(&mut tx)
+                        .await?;
+                if existing.is_some() {
+                    return Err(AppError::Conflict);
+                }
+                sqlx::query("INSERT INTO dispatchers (user_id, area_id) VALUES (?, ?)")
+                    .bind(user_id)
+                    .bind(area_id)
+                    .execute(&mut tx)
+                    .await?;
+                tx.commit().await?;
+                Ok(())
+            })
+            .await
+    }
+
+    // 既にディスパッチャーのユーザーは Conflict、管理者は BadRequest として拒否する
+    // 作成したディスパッチャーのIDを返す
+    async fn promote_to_dispatcher(&self, user_id: i32, area_id: i32) -> Result<i32, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+                let role: Option<String> =
+                    sqlx::query_scalar("SELECT role FROM users WHERE id = ? FOR UPDATE")
+                        .bind(user_id)
+                        .fetch_optional(&mut tx)
+                        .await?;
+                match role.as_deref() {
+                    Some("dispatcher") => return Err(AppError::Conflict),
+                    Some("admin") => return Err(AppError::BadRequest),
+                    Some(_) => {}
+                    None => return Err(AppError::NotFound),
+                }
+                let area_exists: Option<i32> =
+                    sqlx::query_scalar("SELECT id FROM areas WHERE id = ?")
+                        .bind(area_id)
+                        .fetch_optional(&mut tx)
+                        .await?;
+                if area_exists.is_none() {
+                    return Err(AppError::BadRequest);
+                }
+
+                sqlx::query("UPDATE users SET role = 'dispatcher' WHERE id = ?")
+                    .bind(user_id)
+                    .execute(&mut tx)
+                    .await?;
+                let result =
+                    sqlx::query("INSERT INTO dispatchers (user_id, area_id) VALUES (?, ?)")
+                        .bind(user_id)
+                        .bind(area_id)
+                        .execute(&mut tx)
+                        .await?;
+                tx.commit().await?;
+
+                Ok(result.last_insert_id() as i32)
+            })
+            .await
+    }
+
+    async fn find_dispatcher_profile_by_id(
+        &self,
+        id: i32,
+    ) -> Result<Option<DispatcherProfile>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let dispatcher = sqlx::query_as::<_, DispatcherProfile>(
+                    "SELECT
+                        d.id, d.user_id, u.username, d.area_id
+                    FROM
+                        dispatchers d
+                    JOIN
+                        users u
+                    ON
+                        d.user_id = u.id
+                    WHERE
+                        d.id = ?",
+                )
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+                Ok(dispatcher)
+            })
+            .await
     }
 
     async fn find_user_by_ids(&self, ids: &[i32]) -> Result<Vec<User>, AppError> {
-        todo!()
+        self.find_users_by_ids(ids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsupport::{circuit_breaker, seed_area, seed_session, seed_user, test_pool};
+
+    #[actix_rt::test]
+    async fn create_dispatcher_requires_an_existing_dispatcher_user() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = AuthRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let client_id = seed_user(&pool, "client").await;
+        let dispatcher_user_id = seed_user(&pool, "dispatcher").await;
+
+        assert!(matches!(
+            repository.create_dispatcher(-1, area_id).await,
+            Err(AppError::NotFound)
+        ));
+        assert!(matches!(
+            repository.create_dispatcher(client_id, area_id).await,
+            Err(AppError::BadRequest)
+        ));
+        repository
+            .create_dispatcher(dispatcher_user_id, area_id)
+            .await
+            .unwrap();
+        assert!(matches!(
+            repository
+                .create_dispatcher(dispatcher_user_id, area_id)
+                .await,
+            Err(AppError::Conflict)
+        ));
+
+        let dispatcher_areas: Vec<(i32, i32)> =
+            sqlx::query_as("SELECT user_id, area_id FROM dispatchers WHERE user_id IN (?, ?)")
+                .bind(client_id)
+                .bind(dispatcher_user_id)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(dispatcher_areas, vec![(dispatcher_user_id, area_id)]);
+    }
+
+    #[actix_rt::test]
+    async fn promote_to_dispatcher_changes_the_role_and_creates_the_row() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = AuthRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let client_id = seed_user(&pool, "client").await;
+
+        let dispatcher_id = repository
+            .promote_to_dispatcher(client_id, area_id)
+            .await
+            .unwrap();
+
+        let role: String = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+            .bind(client_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(role, "dispatcher");
+        let dispatcher = repository
+            .find_dispatcher_by_id(dispatcher_id)
+            .await
+            .unwrap()
+            .expect("the dispatcher row exists");
+        assert_eq!(dispatcher.user_id, client_id);
+        assert!(matches!(
+            repository.promote_to_dispatcher(client_id, area_id).await,
+            Err(AppError::Conflict)
+        ));
+        assert!(matches!(
+            repository.promote_to_dispatcher(-1, area_id).await,
+            Err(AppError::NotFound)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn count_valid_sessions_ignores_expired_and_other_users_sessions() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = AuthRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let user_id = seed_user(&pool, "client").await;
+        let other_user_id = seed_user(&pool, "client").await;
+        seed_session(&pool, user_id).await;
+        seed_session(&pool, user_id).await;
+        let expired_token = seed_session(&pool, user_id).await;
+        seed_session(&pool, other_user_id).await;
+        sqlx::query(
+            "UPDATE sessions SET expires_at = NOW() - INTERVAL 1 MINUTE WHERE session_token = ?",
+        )
+        .bind(&expired_token)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(repository.count_valid_sessions(user_id).await.unwrap(), 2);
+        assert_eq!(
+            repository
+                .count_valid_sessions(other_user_id)
+                .await
+                .unwrap(),
+            1
+        );
     }
 }
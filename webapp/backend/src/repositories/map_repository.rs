@@ -1,97 +1,196 @@
-use sqlx::MySqlPool;
+use std::env;
+use std::sync::Arc;
 
 use crate::{
     domains::map_service::MapRepository,
-    models::graph::{Edge, Node},
+    infrastructure::{
+        circuit_breaker::CircuitBreaker,
+        db::{DbPool, DbTransaction},
+    },
+    models::{
+        area::AreaId,
+        graph::{Edge, Node},
+    },
 };
 
 #[derive(Debug)]
 pub struct MapRepositoryImpl {
-    pool: MySqlPool,
+    pool: DbPool,
+    circuit_breaker: Arc<CircuitBreaker>,
+    import_chunk_size: usize,
 }
 
 impl MapRepositoryImpl {
-    pub fn new(pool: MySqlPool) -> Self {
-        MapRepositoryImpl { pool }
+    pub fn new(pool: impl Into<DbPool>, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        MapRepositoryImpl {
+            pool: pool.into(),
+            circuit_breaker,
+            import_chunk_size: import_chunk_size_from_env(),
+        }
     }
+
+    // import_chunk_size 行ずつ複数行の INSERT を発行する
+    async fn insert_nodes(
+        &self,
+        tx: &mut DbTransaction,
+        nodes: &[Node],
+    ) -> Result<u64, sqlx::Error> {
+        let mut inserted = 0;
+
+        for chunk in nodes.chunks(self.import_chunk_size) {
+            let placeholders = chunk
+                .iter()
+                .map(|_| "(?, ?, ?, ?, ?)")
+                .collect::<Vec<_>>()
+                .join(",");
+            let sql = format!(
+                "INSERT INTO nodes (id, name, area_id, x, y) VALUES {}",
+                placeholders
+            );
+            let mut query = sqlx::query(&sql);
+            for node in chunk {
+                query = query
+                    .bind(node.id)
+                    .bind(&node.name)
+                    .bind(node.area_id)
+                    .bind(node.x)
+                    .bind(node.y);
+            }
+            inserted += query.execute(&mut *tx).await?.rows_affected();
+        }
+
+        Ok(inserted)
+    }
+
+    async fn insert_edges(
+        &self,
+        tx: &mut DbTransaction,
+        edges: &[Edge],
+    ) -> Result<u64, sqlx::Error> {
+        let mut inserted = 0;
+
+        for chunk in edges.chunks(self.import_chunk_size) {
+            let placeholders = chunk
+                .iter()
+                .map(|_| "(?, ?, ?)")
+                .collect::<Vec<_>>()
+                .join(",");
+            let sql = format!(
+                "INSERT INTO edges (node_a_id, node_b_id, weight) VALUES {}",
+                placeholders
+            );
+            let mut query = sqlx::query(&sql);
+            for edge in chunk {
+                query = query
+                    .bind(edge.node_a_id)
+                    .bind(edge.node_b_id)
+                    .bind(edge.weight);
+            }
+            inserted += query.execute(&mut *tx).await?.rows_affected();
+        }
+
+        Ok(inserted)
+    }
+}
+
+// 環境変数から一括 INSERT 1回あたりの行数を取得し、デフォルトを1000行に設定
+fn import_chunk_size_from_env() -> usize {
+    let chunk_size: usize = env::var("MAP_IMPORT_CHUNK_SIZE")
+        .unwrap_or_else(|_| "1000".to_string())
+        .parse()
+        .expect("MAP_IMPORT_CHUNK_SIZE must be a valid number");
+
+    chunk_size.max(1)
 }
 
 impl MapRepository for MapRepositoryImpl {
-    async fn get_all_nodes(&self, area_id: Option<i32>) -> Result<Vec<Node>, sqlx::Error> {
-        let where_clause = match area_id {
-            Some(_) => "WHERE area_id = ?",
-            None => "",
-        };
+    async fn get_all_nodes(&self, area_id: Option<AreaId>) -> Result<Vec<Node>, sqlx::Error> {
+        self.circuit_breaker
+            .call(async {
+                let where_clause = match area_id {
+                    Some(_) => "WHERE area_id = ?",
+                    None => "",
+                };
 
-        let sql = format!(
-            "SELECT
-                * 
-            FROM
-                nodes
-            {}
-            ORDER BY
-                id",
-            where_clause
-        );
+                let sql = format!(
+                    "SELECT
+                        * 
+                    FROM
+                        nodes
+                    {}
+                    ORDER BY
+                        id",
+                    where_clause
+                );
 
-        let nodes = match area_id {
-            Some(area_id) => {
-                sqlx::query_as::<_, Node>(&sql)
-                    .bind(area_id)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            None => {
-                sqlx::query_as::<_, Node>(&sql)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-        };
+                let nodes = match area_id {
+                    Some(area_id) => {
+                        sqlx::query_as::<_, Node>(&sql)
+                            .bind(area_id)
+                            .fetch_all(&self.pool)
+                            .await?
+                    }
+                    None => {
+                        sqlx::query_as::<_, Node>(&sql)
+                            .fetch_all(&self.pool)
+                            .await?
+                    }
+                };
 
-        Ok(nodes)
+                Ok(nodes)
+            })
+            .await
     }
 
-    async fn get_all_edges(&self, area_id: Option<i32>) -> Result<Vec<Edge>, sqlx::Error> {
-        let where_clause = match area_id {
-            Some(_) => "JOIN nodes n ON e.node_a_id = n.id WHERE n.area_id = ?",
-            None => "",
-        };
+    async fn get_all_edges(&self, area_id: Option<AreaId>) -> Result<Vec<Edge>, sqlx::Error> {
+        self.circuit_breaker
+            .call(async {
+                let where_clause = match area_id {
+                    Some(_) => "JOIN nodes n ON e.node_a_id = n.id WHERE n.area_id = ?",
+                    None => "",
+                };
 
-        let sql = format!(
-            "SELECT
-                e.node_a_id,
-                e.node_b_id,
-                e.weight
-            FROM
-                edges e
-            {}",
-            where_clause
-        );
+                let sql = format!(
+                    "SELECT
+                        e.node_a_id,
+                        e.node_b_id,
+                        e.weight
+                    FROM
+                        edges e
+                    {}",
+                    where_clause
+                );
 
-        let edges = match area_id {
-            Some(area_id) => {
-                sqlx::query_as::<_, Edge>(&sql)
-                    .bind(area_id)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            None => {
-                sqlx::query_as::<_, Edge>(&sql)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-        };
+                let edges = match area_id {
+                    Some(area_id) => {
+                        sqlx::query_as::<_, Edge>(&sql)
+                            .bind(area_id)
+                            .fetch_all(&self.pool)
+                            .await?
+                    }
+                    None => {
+                        sqlx::query_as::<_, Edge>(&sql)
+                            .fetch_all(&self.pool)
+                            .await?
+                    }
+                };
 
-        Ok(edges)
+                Ok(edges)
+            })
+            .await
     }
 
-    async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<i32, sqlx::Error> {
-        let area_id = sqlx::query_scalar("SELECT area_id FROM nodes WHERE id = ?")
-            .bind(node_id)
-            .fetch_one(&self.pool)
-            .await?;
+    async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<AreaId, sqlx::Error> {
+        self.circuit_breaker
+            .call(async {
+                let area_id = sqlx::query_scalar("SELECT area_id FROM nodes WHERE id = ?")
+                    .bind(node_id)
+                    .fetch_one(&self.pool)
+                    .await?;
 
-        Ok(area_id)
+                Ok(area_id)
+            })
+            .await
     }
 
     async fn update_edge(
@@ -100,15 +199,165 @@ impl MapRepository for MapRepositoryImpl {
         node_b_id: i32,
         weight: i32,
     ) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE edges SET weight = ? WHERE (node_a_id = ? AND node_b_id = ?) OR (node_a_id = ? AND node_b_id = ?)")
-            .bind(weight)
-            .bind(node_a_id)
-            .bind(node_b_id)
-            .bind(node_b_id)
-            .bind(node_a_id)
-            .execute(&self.pool)
-            .await?;
-
-        Ok(())
+        self.circuit_breaker
+            .call(async {
+                sqlx::query("UPDATE edges SET weight = ? WHERE (node_a_id = ? AND node_b_id = ?) OR (node_a_id = ? AND node_b_id = ?)")
+                    .bind(weight)
+                    .bind(node_a_id)
+                    .bind(node_b_id)
+                    .bind(node_b_id)
+                    .bind(node_a_id)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn get_depot_node_ids(&self, area_id: AreaId) -> Result<Vec<i32>, sqlx::Error> {
+        self.circuit_breaker
+            .call(async {
+                let node_ids = sqlx::query_scalar(
+                    "SELECT node_id FROM depots WHERE area_id = ? ORDER BY node_id",
+                )
+                .bind(area_id)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(node_ids)
+            })
+            .await
+    }
+
+    // 隣接関係は片方向だけ登録されていても双方向として扱う
+    async fn get_adjacent_area_ids(&self, area_id: AreaId) -> Result<Vec<AreaId>, sqlx::Error> {
+        self.circuit_breaker
+            .call(async {
+                let area_ids = sqlx::query_scalar(
+                    "SELECT adjacent_area_id FROM area_adjacencies WHERE area_id = ?
+                    UNION
+                    SELECT area_id FROM area_adjacencies WHERE adjacent_area_id = ?
+                    ORDER BY 1",
+                )
+                .bind(area_id)
+                .bind(area_id)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(area_ids)
+            })
+            .await
+    }
+
+    // 辺は追加したノードを参照するため、ノードを先に取り込む
+    // 途中で失敗した場合にノードだけが残らないよう、ノードと辺を1つのトランザクションで取り込む
+    async fn insert_map(&self, nodes: &[Node], edges: &[Edge]) -> Result<(u64, u64), sqlx::Error> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+
+                let nodes_inserted = self.insert_nodes(&mut tx, nodes).await?;
+                let edges_inserted = self.insert_edges(&mut tx, edges).await?;
+
+                tx.commit().await?;
+
+                Ok((nodes_inserted, edges_inserted))
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsupport::{circuit_breaker, seed_area, test_pool};
+    use sqlx::mysql::MySqlPool;
+
+    // 取り込むノードのIDは明示するため、既存のノードと重ならない範囲から選ぶ
+    async fn unused_node_id_base(pool: &MySqlPool) -> i32 {
+        let max_id: i32 = sqlx::query_scalar("SELECT COALESCE(MAX(id), 0) FROM nodes")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        max_id + 1000
+    }
+
+    fn line_map(area_id: i32, base: i32, len: i32) -> (Vec<Node>, Vec<Edge>) {
+        let nodes = (0..len)
+            .map(|i| Node {
+                id: base + i,
+                name: format!("imported{}", i),
+                area_id,
+                x: i,
+                y: 0,
+            })
+            .collect();
+        let edges = (0..len - 1)
+            .map(|i| Edge {
+                node_a_id: base + i,
+                node_b_id: base + i + 1,
+                weight: 1,
+            })
+            .collect();
+        (nodes, edges)
+    }
+
+    #[actix_rt::test]
+    async fn imports_batches_larger_than_the_chunk_size() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = MapRepositoryImpl {
+            pool: pool.clone().into(),
+            circuit_breaker: circuit_breaker(),
+            import_chunk_size: 2,
+        };
+        let area_id = seed_area(&pool).await;
+        let (nodes, edges) = line_map(area_id, unused_node_id_base(&pool).await, 5);
+
+        let inserted = repository.insert_map(&nodes, &edges).await.unwrap();
+
+        assert_eq!(inserted, (5, 4));
+        assert_eq!(
+            repository
+                .get_all_nodes(Some(AreaId(area_id)))
+                .await
+                .unwrap()
+                .len(),
+            5
+        );
+        assert_eq!(
+            repository
+                .get_all_edges(Some(AreaId(area_id)))
+                .await
+                .unwrap()
+                .len(),
+            4
+        );
+    }
+
+    #[actix_rt::test]
+    async fn failed_edge_chunk_rolls_back_the_imported_nodes() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = MapRepositoryImpl {
+            pool: pool.clone().into(),
+            circuit_breaker: circuit_breaker(),
+            import_chunk_size: 2,
+        };
+        let area_id = seed_area(&pool).await;
+        let (nodes, mut edges) = line_map(area_id, unused_node_id_base(&pool).await, 5);
+        // 最後のチャンクで (node_a_id, node_b_id) の一意制約に違反させる
+        edges.push(edges[0].clone());
+
+        assert!(repository.insert_map(&nodes, &edges).await.is_err());
+
+        assert!(repository
+            .get_all_nodes(Some(AreaId(area_id)))
+            .await
+            .unwrap()
+            .is_empty());
     }
 }
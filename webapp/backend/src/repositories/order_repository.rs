@@ -1,46 +1,246 @@
 use crate::domains::order_service::OrderRepository;
 use crate::errors::AppError;
-use crate::models::order::Order;
+use crate::infrastructure::circuit_breaker::CircuitBreaker;
+use crate::infrastructure::db::{DbPool, DbTransaction};
+use crate::models::area::{AreaId, AreaStats};
+use crate::models::order::{
+    CancelReason, CompletedOrder, CompletedOrderDetail, CompletionOutcome, Order, OrderEvent,
+    OrderFilter, OrderStatus,
+};
+use crate::utils::page_offset;
 use chrono::{DateTime, Utc};
-use sqlx::mysql::MySqlPool;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct OrderRepositoryImpl {
-    pool: MySqlPool,
+    pool: DbPool,
+    circuit_breaker: Arc<CircuitBreaker>,
 }
 
 impl OrderRepositoryImpl {
-    pub fn new(pool: MySqlPool) -> Self {
-        OrderRepositoryImpl { pool }
+    pub fn new(pool: impl Into<DbPool>, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        OrderRepositoryImpl {
+            pool: pool.into(),
+            circuit_breaker,
+        }
     }
-}
 
-impl OrderRepository for OrderRepositoryImpl {
-    async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError> {
-        let order = sqlx::query_as::<_, Order>(
-            "SELECT 
-                *
-            FROM
-                orders 
-            WHERE
-                id = ?",
+    // 完了させるトラックが注文と同じエリアに所属しているかを確認する
+    async fn tow_truck_in_area(
+        tx: &mut DbTransaction,
+        tow_truck_id: i32,
+        area_id: i32,
+    ) -> Result<bool, AppError> {
+        let tow_truck_area_id: Option<i32> =
+            sqlx::query_scalar("SELECT area_id FROM tow_trucks WHERE id = ? FOR UPDATE")
+                .bind(tow_truck_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        Ok(tow_truck_area_id == Some(area_id))
+    }
+
+    // 完了時刻は completed_orders を正とする
+    // 配車時に completed_orders の行が作られている場合があるため upsert する
+    async fn record_completion(
+        tx: &mut DbTransaction,
+        order_id: i32,
+        tow_truck_id: i32,
+        completed_time: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)
+            ON DUPLICATE KEY UPDATE tow_truck_id = VALUES(tow_truck_id), completed_time = VALUES(completed_time)",
         )
-        .bind(id)
-        .fetch_one(&self.pool)
+        .bind(order_id)
+        .bind(tow_truck_id)
+        .bind(completed_time)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(order)
+        Ok(())
     }
 
-    async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError> {
-        sqlx::query("UPDATE orders SET status = ? WHERE id = ?")
-            .bind(status)
-            .bind(order_id)
-            .execute(&self.pool)
-            .await?;
+    // トラックが order_id 以外の配車済みの注文を担当しているかを確認する
+    // 複数台で対応中の注文に補助として割り当てられている場合も担当中とみなす
+    async fn has_other_active_order(
+        tx: &mut DbTransaction,
+        order_id: i32,
+        tow_truck_id: i32,
+    ) -> Result<bool, AppError> {
+        let active_order_count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM orders
+            WHERE status = 'dispatched' AND id <> ?
+            AND (tow_truck_id = ? OR id IN (SELECT order_id FROM order_tow_trucks WHERE tow_truck_id = ?))",
+        )
+        .bind(order_id)
+        .bind(tow_truck_id)
+        .bind(tow_truck_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Ok(active_order_count > 0)
+    }
+
+    // 複数台で対応した注文の完了時に、割り当てたトラックをすべて空きに戻す
+    async fn release_assigned_tow_trucks(
+        tx: &mut DbTransaction,
+        order_id: i32,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE tow_trucks SET status = 'available'
+            WHERE id IN (SELECT tow_truck_id FROM order_tow_trucks WHERE order_id = ?)",
+        )
+        .bind(order_id)
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(())
+    }
+
+    // 注文を担当する全トラック (主担当と複数台対応の割り当て) について履歴を残す
+    async fn record_order_event(
+        tx: &mut DbTransaction,
+        order_id: i32,
+        event_type: &str,
+        occurred_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO order_events (order_id, tow_truck_id, event_type, occurred_at)
+            SELECT id, tow_truck_id, ?, ? FROM orders WHERE id = ? AND tow_truck_id IS NOT NULL
+            UNION
+            SELECT order_id, tow_truck_id, ?, ? FROM order_tow_trucks WHERE order_id = ?",
+        )
+        .bind(event_type)
+        .bind(occurred_at)
+        .bind(order_id)
+        .bind(event_type)
+        .bind(occurred_at)
+        .bind(order_id)
+        .execute(&mut *tx)
+        .await?;
 
         Ok(())
     }
+}
+
+impl OrderRepository for OrderRepositoryImpl {
+    async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError> {
+        self.circuit_breaker
+            .call(async {
+                // 完了時刻は completed_orders を正とし、完了済みの注文についてのみ結合する
+                let order = sqlx::query_as::<_, Order>(
+                    "SELECT
+                        o.id,
+                        o.client_id,
+                        o.dispatcher_id,
+                        o.tow_truck_id,
+                        o.status,
+                        o.node_id,
+                        o.destination_node_id,
+                        o.car_value,
+                        o.contact_phone,
+                        o.trucks_required,
+                        o.cancel_reason,
+                        o.order_time,
+                        co.completed_time,
+                        o.area_id,
+                        o.eta_seconds
+                    FROM
+                        orders o
+                    LEFT JOIN
+                        completed_orders co
+                    ON
+                        co.order_id = o.id AND o.status = 'completed'
+                    WHERE
+                        o.id = ?",
+                )
+                .bind(id)
+                .fetch_one(&self.pool)
+                .await?;
+
+                Ok(order)
+            })
+            .await
+    }
+
+    async fn update_order_status(
+        &self,
+        order_id: i32,
+        status: OrderStatus,
+    ) -> Result<(), AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+
+                // 同じ注文への状態変更を直列化するため、行ロックを取ってから現在の状態を確認する
+                let current: Option<(String, i32, Option<i32>)> = sqlx::query_as(
+                    "SELECT status, area_id, tow_truck_id FROM orders WHERE id = ? FOR UPDATE",
+                )
+                .bind(order_id)
+                .fetch_optional(&mut tx)
+                .await?;
+                let (current_status, area_id, tow_truck_id) = match current {
+                    Some((current_status, area_id, tow_truck_id)) => (
+                        current_status
+                            .parse::<OrderStatus>()
+                            .map_err(|_| AppError::InternalServerError)?,
+                        area_id,
+                        tow_truck_id,
+                    ),
+                    None => return Err(AppError::NotFound),
+                };
+                // 配車されていない注文の完了は、状態の競合ではなく不正な要求として扱う
+                if status == OrderStatus::Completed
+                    && current_status != OrderStatus::Dispatched
+                    && current_status != OrderStatus::Completed
+                {
+                    return Err(AppError::BadRequest);
+                }
+                if !current_status.can_transition_to(status) {
+                    return Err(AppError::Conflict);
+                }
+                // エリアの異なるトラックによる完了は記録しない
+                if status == OrderStatus::Completed {
+                    let tow_truck_id = match tow_truck_id {
+                        Some(tow_truck_id) => tow_truck_id,
+                        None => return Err(AppError::Conflict),
+                    };
+                    if !Self::tow_truck_in_area(&mut tx, tow_truck_id, area_id).await? {
+                        return Err(AppError::Conflict);
+                    }
+                    // 一括完了 (bulk_complete) と同じく、完了時刻を記録して主担当と補助のトラックを空きに戻す
+                    let completed_time = Utc::now();
+                    Self::record_completion(&mut tx, order_id, tow_truck_id, completed_time)
+                        .await?;
+                    sqlx::query("UPDATE orders SET status = ?, completed_time = ? WHERE id = ?")
+                        .bind(status.as_str())
+                        .bind(completed_time)
+                        .bind(order_id)
+                        .execute(&mut tx)
+                        .await?;
+                    sqlx::query("UPDATE tow_trucks SET status = 'available' WHERE id = ?")
+                        .bind(tow_truck_id)
+                        .execute(&mut tx)
+                        .await?;
+                    Self::record_order_event(&mut tx, order_id, "completed", completed_time)
+                        .await?;
+                    Self::release_assigned_tow_trucks(&mut tx, order_id).await?;
+                } else {
+                    sqlx::query("UPDATE orders SET status = ? WHERE id = ?")
+                        .bind(status.as_str())
+                        .bind(order_id)
+                        .execute(&mut tx)
+                        .await?;
+                }
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+            .await
+    }
 
     async fn get_paginated_orders(
         &self,
@@ -48,145 +248,2308 @@ impl OrderRepository for OrderRepositoryImpl {
         page_size: i32,
         sort_by: Option<String>,
         sort_order: Option<String>,
-        status: Option<String>,
-        area: Option<i32>,
+        filter: OrderFilter,
     ) -> Result<Vec<Order>, AppError> {
-        let offset = page * page_size;
-        let order_clause = format!(
-            "ORDER BY {} {}",
-            match sort_by.as_deref() {
-                Some("car_value") => "o.car_value",
-                Some("status") => "o.status",
-                Some("order_time") => "o.order_time",
-                _ => "o.order_time",
-            },
-            match sort_order.as_deref() {
-                Some("DESC") => "DESC",
-                Some("desc") => "DESC",
-                _ => "ASC",
-            }
-        );
+        self.circuit_breaker
+            .call(async {
+                let offset = page_offset(page, page_size)?;
+                let order_clause = match sort_by.as_deref() {
+                    // 配車の優先度順 (sort_order は無視する)
+                    // car_value の高い順、同じ値なら受付の早い順。car_value が 0 以下の注文
+                    // (金額未入力の旧データ) は金額の大小では比べられないため、最も優先度を低くして受付順に並べる
+                    Some("priority") => {
+                        "ORDER BY o.car_value > 0 DESC, o.car_value DESC, o.order_time ASC"
+                            .to_string()
+                    }
+                    // 完了時刻順。未完了の注文 (completed_time が NULL) は昇順・降順どちらでも末尾に並べる
+                    Some("completed_time") => format!(
+                        "ORDER BY co.completed_time IS NULL, co.completed_time {}",
+                        match sort_order.as_deref() {
+                            Some("DESC") => "DESC",
+                            Some("desc") => "DESC",
+                            _ => "ASC",
+                        }
+                    ),
+                    // car_value や status は同じ値の注文が多く、ページをまたぐと順序が揺れるため
+                    // o.id を第2キーにして並びを一意に定める
+                    sort_by => format!(
+                        "ORDER BY {} {}{}",
+                        match sort_by {
+                            Some("car_value") => "o.car_value",
+                            Some("status") => "o.status",
+                            Some("order_time") => "o.order_time",
+                            _ => "o.order_time",
+                        },
+                        match sort_order.as_deref() {
+                            Some("DESC") => "DESC",
+                            Some("desc") => "DESC",
+                            _ => "ASC",
+                        },
+                        match sort_by {
+                            Some("car_value") | Some("status") => ", o.id ASC",
+                            _ => "",
+                        }
+                    ),
+                };
 
-        let where_clause = match (status.clone(), area) {
-            (Some(_), Some(_)) => "WHERE o.status = ? AND o.area_id = ?".to_string(),
-            (None, Some(_)) => "WHERE o.area_id = ?".to_string(),
-            (Some(_), None) => "WHERE o.status = ?".to_string(),
-            _ => "".to_string(),
-        };
-
-        let sql = format!(
-            "SELECT 
-                o.id, 
-                o.client_id, 
-                o.dispatcher_id, 
-                o.tow_truck_id, 
-                o.status, 
-                o.node_id, 
-                o.car_value, 
-                o.order_time, 
-                o.completed_time
-            FROM
-                orders o
-            {} 
-            {} 
-            LIMIT ? 
-            OFFSET ?",
-            where_clause, order_clause
-        );
+                let mut conditions = Vec::new();
+                if !filter.statuses.is_empty() {
+                    let status_placeholders = filter
+                        .statuses
+                        .iter()
+                        .map(|_| "?")
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    conditions.push(format!("o.status IN ({})", status_placeholders));
+                }
+                if filter.area.is_some() {
+                    conditions.push("o.area_id = ?".to_string());
+                }
+                match filter.assigned {
+                    Some(true) => conditions.push("o.tow_truck_id IS NOT NULL".to_string()),
+                    Some(false) => conditions.push("o.tow_truck_id IS NULL".to_string()),
+                    None => {}
+                }
+                let where_clause = match conditions.is_empty() {
+                    true => "".to_string(),
+                    false => format!("WHERE {}", conditions.join(" AND ")),
+                };
 
-        let orders = match (status, area) {
-            (Some(status), Some(area)) => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(status)
-                    .bind(area)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            (None, Some(area)) => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(area)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            (Some(status), None) => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(status)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            _ => {
-                sqlx::query_as::<_, Order>(&sql)
+                let sql = format!(
+                    "SELECT
+                        o.id,
+                        o.client_id,
+                        o.dispatcher_id,
+                        o.tow_truck_id,
+                        o.status,
+                        o.node_id,
+                        o.destination_node_id,
+                        o.car_value,
+                        o.contact_phone,
+                        o.trucks_required,
+                        o.cancel_reason,
+                        o.order_time,
+                        co.completed_time,
+                        o.area_id,
+                        o.eta_seconds
+                    FROM
+                        orders o
+                    LEFT JOIN
+                        completed_orders co
+                    ON
+                        co.order_id = o.id AND o.status = 'completed'
+                    {}
+                    {}
+                    LIMIT ?
+                    OFFSET ?",
+                    where_clause, order_clause
+                );
+
+                // プレースホルダの順に値をバインド
+                let mut query_builder = sqlx::query_as::<_, Order>(&sql);
+                for status in &filter.statuses {
+                    query_builder = query_builder.bind(status.as_str());
+                }
+                if let Some(area) = filter.area {
+                    query_builder = query_builder.bind(area);
+                }
+                let orders = query_builder
                     .bind(page_size)
                     .bind(offset)
                     .fetch_all(&self.pool)
-                    .await?
-            }
-        };
+                    .await?;
 
-        Ok(orders)
+                Ok(orders)
+            })
+            .await
     }
 
-    async fn create_order(
+    // エリアの取得と挿入を1つのトランザクションで行い、採番された注文IDを返す
+    // 途中で失敗した場合はロールバックされ、注文は作られない
+    async fn create_order_tx(
         &self,
         client_id: i32,
         node_id: i32,
+        destination_node_id: Option<i32>,
         car_value: f64,
-    ) -> Result<(), AppError> {
-        // node_id に対応する area_id を取得
-        let area_id: i32 = sqlx::query_scalar("SELECT area_id FROM nodes WHERE id = ?")
-            .bind(node_id)
-            .fetch_one(&self.pool)
-            .await?;
-        
-        // orders テーブルに新しいレコードを挿入
-        sqlx::query("INSERT INTO orders (client_id, node_id, area_id, status, car_value) VALUES (?, ?, ?, 'pending', ?)")
-            .bind(client_id)
-            .bind(node_id)
-            .bind(area_id)
-            .bind(car_value)
-            .execute(&self.pool)
-            .await?;
-    
-        Ok(())
+        contact_phone: Option<&str>,
+        trucks_required: i32,
+    ) -> Result<i32, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+
+                // node_id に対応する area_id を取得
+                let area_id: i32 = sqlx::query_scalar("SELECT area_id FROM nodes WHERE id = ?")
+                    .bind(node_id)
+                    .fetch_one(&mut tx)
+                    .await?;
+
+                // orders テーブルに新しいレコードを挿入
+                let result = sqlx::query("INSERT INTO orders (client_id, node_id, destination_node_id, area_id, status, car_value, contact_phone, trucks_required) VALUES (?, ?, ?, ?, 'pending', ?, ?, ?)")
+                    .bind(client_id)
+                    .bind(node_id)
+                    .bind(destination_node_id)
+                    .bind(area_id)
+                    .bind(car_value)
+                    .bind(contact_phone)
+                    .bind(trucks_required)
+                    .execute(&mut tx)
+                    .await?;
+
+                tx.commit().await?;
+
+                Ok(result.last_insert_id() as i32)
+            })
+            .await
     }
 
-    async fn update_order_dispatched(
+    // /order/dispatcher
+    // 1台のトラックが同時に運べるのは1台の車だけなので、担当中の注文があるトラックへの配車は拒否する
+    // トラックの行ロックを取ってから件数を確認し、同じトラックへの同時配車を直列化する
+    // 隣接エリアから配車したトラックは注文のエリアに所属を移し、完了時のエリアの確認を通れるようにする
+    async fn dispatch_order(
         &self,
-        id: i32,
+        order_id: i32,
         dispatcher_id: i32,
         tow_truck_id: i32,
+        order_time: DateTime<Utc>,
+        eta_seconds: Option<i32>,
     ) -> Result<(), AppError> {
-        sqlx::query(
-            "UPDATE orders SET dispatcher_id = ?, tow_truck_id = ?, status = 'dispatched' WHERE id = ?",
-        )
-        .bind(dispatcher_id)
-        .bind(tow_truck_id)
-        .bind(id)
-        .execute(&self.pool)
-        .await?;
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
 
-        Ok(())
+                let locked_tow_truck_id: Option<i32> =
+                    sqlx::query_scalar("SELECT id FROM tow_trucks WHERE id = ? FOR UPDATE")
+                        .bind(tow_truck_id)
+                        .fetch_optional(&mut tx)
+                        .await?;
+                if locked_tow_truck_id.is_none() {
+                    return Err(AppError::NotFound);
+                }
+
+                if Self::has_other_active_order(&mut tx, order_id, tow_truck_id).await? {
+                    return Err(AppError::Conflict);
+                }
+
+                sqlx::query("INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)")
+                    .bind(order_id)
+                    .bind(tow_truck_id)
+                    .bind(order_time)
+                    .execute(&mut tx)
+                    .await
+                    .map_err(|_| AppError::BadRequest)?;
+
+                sqlx::query(
+                    "UPDATE orders SET dispatcher_id = ?, tow_truck_id = ?, eta_seconds = ?, status = 'dispatched' WHERE id = ?",
+                )
+                .bind(dispatcher_id)
+                .bind(tow_truck_id)
+                .bind(eta_seconds)
+                .bind(order_id)
+                .execute(&mut tx)
+                .await?;
+                Self::record_order_event(&mut tx, order_id, "dispatched", Utc::now()).await?;
+
+                sqlx::query(
+                    "UPDATE tow_trucks SET status = 'busy', area_id = (SELECT area_id FROM orders WHERE id = ?)
+                    WHERE id = ?",
+                )
+                .bind(order_id)
+                .bind(tow_truck_id)
+                .execute(&mut tx)
+                .await?;
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+            .await
     }
-// /order/dispatcher
-    async fn create_completed_order(
+
+    async fn bulk_complete_orders(
         &self,
-        order_id: i32,
-        tow_truck_id: i32,
+        order_ids: &[i32],
         completed_time: DateTime<Utc>,
+    ) -> Result<Vec<(i32, CompletionOutcome)>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+                let mut results = Vec::with_capacity(order_ids.len());
+
+                for &order_id in order_ids {
+                    let order =
+                        sqlx::query_as::<_, Order>("SELECT * FROM orders WHERE id = ? FOR UPDATE")
+                            .bind(order_id)
+                            .fetch_optional(&mut tx)
+                            .await?;
+
+                    let order = match order {
+                        Some(order) => order,
+                        None => {
+                            results.push((order_id, CompletionOutcome::NotFound));
+                            continue;
+                        }
+                    };
+                    if order.status == "completed" {
+                        results.push((order_id, CompletionOutcome::AlreadyCompleted));
+                        continue;
+                    }
+                    let tow_truck_id = match (order.status.as_str(), order.tow_truck_id) {
+                        ("dispatched", Some(tow_truck_id)) => tow_truck_id,
+                        _ => {
+                            results.push((order_id, CompletionOutcome::NotDispatched));
+                            continue;
+                        }
+                    };
+                    if !Self::tow_truck_in_area(&mut tx, tow_truck_id, order.area_id).await? {
+                        results.push((order_id, CompletionOutcome::AreaMismatch));
+                        continue;
+                    }
+
+                    Self::record_completion(&mut tx, order_id, tow_truck_id, completed_time)
+                        .await?;
+
+                    sqlx::query(
+                        "UPDATE orders SET status = 'completed', completed_time = ? WHERE id = ?",
+                    )
+                    .bind(completed_time)
+                    .bind(order_id)
+                    .execute(&mut tx)
+                    .await?;
+
+                    sqlx::query("UPDATE tow_trucks SET status = 'available' WHERE id = ?")
+                        .bind(tow_truck_id)
+                        .execute(&mut tx)
+                        .await?;
+                    Self::record_order_event(&mut tx, order_id, "completed", completed_time)
+                        .await?;
+                    Self::release_assigned_tow_trucks(&mut tx, order_id).await?;
+
+                    let completed_order = sqlx::query_as::<_, CompletedOrder>(
+                        "SELECT * FROM completed_orders WHERE order_id = ?",
+                    )
+                    .bind(order_id)
+                    .fetch_one(&mut tx)
+                    .await?;
+
+                    results.push((
+                        order_id,
+                        CompletionOutcome::Completed(completed_order, order),
+                    ));
+                }
+
+                tx.commit().await?;
+
+                Ok(results)
+            })
+            .await
+    }
+
+    async fn completion_durations(
+        &self,
+        area: AreaId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<i64>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                // 完了時刻は completed_orders を正とし、完了済みの注文だけを集計する
+                let durations = sqlx::query_scalar(
+                    "SELECT
+                        TIMESTAMPDIFF(SECOND, o.order_time, co.completed_time)
+                    FROM
+                        orders o
+                    JOIN
+                        completed_orders co
+                    ON
+                        co.order_id = o.id
+                    WHERE
+                        o.area_id = ?
+                    AND
+                        o.order_time >= ?
+                    AND
+                        o.order_time < ?
+                    AND
+                        o.status = 'completed'",
+                )
+                .bind(area)
+                .bind(from)
+                .bind(to)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(durations)
+            })
+            .await
+    }
+
+    // エリアごとの集計をサブクエリでまとめ、1往復で取得する
+    async fn area_stats(&self) -> Result<Vec<AreaStats>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let stats = sqlx::query_as::<_, AreaStats>(
+                    "SELECT
+                        a.id AS area_id,
+                        (
+                            SELECT COUNT(*) FROM orders o
+                            WHERE o.area_id = a.id AND o.status = 'pending'
+                        ) AS pending_order_count,
+                        (
+                            SELECT COUNT(*) FROM tow_trucks tt
+                            WHERE tt.area_id = a.id AND tt.status = 'available' AND tt.deleted_at IS NULL
+                        ) AS available_tow_truck_count,
+                        (
+                            SELECT CAST(AVG(TIMESTAMPDIFF(SECOND, o.order_time, co.completed_time)) AS DOUBLE)
+                            FROM orders o
+                            JOIN completed_orders co ON co.order_id = o.id
+                            WHERE o.area_id = a.id AND o.status = 'completed'
+                        ) AS average_completion_seconds
+                    FROM
+                        areas a
+                    ORDER BY
+                        a.id ASC",
+                )
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(stats)
+            })
+            .await
+    }
+
+    async fn find_active_order_ids_by_tow_truck_ids(
+        &self,
+        tow_truck_ids: &[i32],
+    ) -> Result<Vec<(i32, i32)>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                if tow_truck_ids.is_empty() {
+                    return Ok(vec![]);
+                }
+                let query_placeholders = tow_truck_ids
+                    .iter()
+                    .map(|_| "?")
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let query = format!(
+                    "SELECT
+                        tow_truck_id, id
+                    FROM
+                        orders
+                    WHERE
+                        tow_truck_id IN ({})
+                    AND
+                        status NOT IN ('completed', 'canceled')",
+                    query_placeholders
+                );
+                let mut query_builder = sqlx::query_as::<_, (i32, i32)>(&query);
+                for id in tow_truck_ids {
+                    query_builder = query_builder.bind(id);
+                }
+                let active_orders = query_builder.fetch_all(&self.pool).await?;
+                Ok(active_orders)
+            })
+            .await
+    }
+
+    async fn reopen_order(&self, order_id: i32) -> Result<(), AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+
+                let order =
+                    sqlx::query_as::<_, Order>("SELECT * FROM orders WHERE id = ? FOR UPDATE")
+                        .bind(order_id)
+                        .fetch_optional(&mut tx)
+                        .await?;
+                let order = match order {
+                    Some(order) => order,
+                    None => return Err(AppError::NotFound),
+                };
+                let tow_truck_id = match (order.status.as_str(), order.tow_truck_id) {
+                    ("completed", Some(tow_truck_id)) => tow_truck_id,
+                    _ => return Err(AppError::BadRequest),
+                };
+
+                // 完了後に別の注文へ配車されたトラックがいる場合は、担当を二重にしないよう戻さない
+                // 複数台で対応した注文は補助のトラックも含めて確認し、ロックはIDの昇順で取る
+                let mut tow_truck_ids: Vec<i32> = sqlx::query_scalar(
+                    "SELECT tow_truck_id FROM order_tow_trucks WHERE order_id = ?",
+                )
+                .bind(order_id)
+                .fetch_all(&mut tx)
+                .await?;
+                tow_truck_ids.push(tow_truck_id);
+                tow_truck_ids.sort_unstable();
+                tow_truck_ids.dedup();
+                for &assigned_tow_truck_id in &tow_truck_ids {
+                    sqlx::query("SELECT id FROM tow_trucks WHERE id = ? FOR UPDATE")
+                        .bind(assigned_tow_truck_id)
+                        .execute(&mut tx)
+                        .await?;
+                    if Self::has_other_active_order(&mut tx, order_id, assigned_tow_truck_id)
+                        .await?
+                    {
+                        return Err(AppError::Conflict);
+                    }
+                }
+
+                sqlx::query("DELETE FROM completed_orders WHERE order_id = ?")
+                    .bind(order_id)
+                    .execute(&mut tx)
+                    .await?;
+
+                sqlx::query(
+                    "UPDATE orders SET status = 'dispatched', completed_time = NULL WHERE id = ?",
+                )
+                .bind(order_id)
+                .execute(&mut tx)
+                .await?;
+                Self::record_order_event(&mut tx, order_id, "dispatched", Utc::now()).await?;
+
+                for assigned_tow_truck_id in tow_truck_ids {
+                    sqlx::query("UPDATE tow_trucks SET status = 'busy' WHERE id = ?")
+                        .bind(assigned_tow_truck_id)
+                        .execute(&mut tx)
+                        .await?;
+                }
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn cancel_order(&self, order_id: i32, reason: CancelReason) -> Result<(), AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+
+                let status: Option<String> =
+                    sqlx::query_scalar("SELECT status FROM orders WHERE id = ? FOR UPDATE")
+                        .bind(order_id)
+                        .fetch_optional(&mut tx)
+                        .await?;
+                let status = match status {
+                    Some(status) => status
+                        .parse::<OrderStatus>()
+                        .map_err(|_| AppError::InternalServerError)?,
+                    None => return Err(AppError::NotFound),
+                };
+                if !status.can_transition_to(OrderStatus::Canceled) {
+                    return Err(AppError::Conflict);
+                }
+
+                sqlx::query(
+                    "UPDATE orders SET status = 'canceled', cancel_reason = ? WHERE id = ?",
+                )
+                .bind(reason.as_str())
+                .bind(order_id)
+                .execute(&mut tx)
+                .await?;
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    // 注文の行ロックを取ってから一括でキャンセルする
+    // pending の注文にはトラックが割り当てられていないため、トラックの状態は変えない
+    async fn cancel_area_pending(
+        &self,
+        area: AreaId,
+        reason: CancelReason,
+    ) -> Result<Vec<i32>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+
+                let order_ids: Vec<i32> = sqlx::query_scalar(
+                    "SELECT id FROM orders WHERE area_id = ? AND status = 'pending' FOR UPDATE",
+                )
+                .bind(area)
+                .fetch_all(&mut tx)
+                .await?;
+                if order_ids.is_empty() {
+                    tx.commit().await?;
+                    return Ok(order_ids);
+                }
+
+                let placeholders = order_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+                let sql = format!(
+                    "UPDATE orders SET status = 'canceled', cancel_reason = ? WHERE id IN ({})",
+                    placeholders
+                );
+                let mut query = sqlx::query(&sql).bind(reason.as_str());
+                for id in &order_ids {
+                    query = query.bind(id);
+                }
+                query.execute(&mut tx).await?;
+
+                tx.commit().await?;
+
+                Ok(order_ids)
+            })
+            .await
+    }
+
+    // 配車後に地点を変えると担当トラックとエリアが食い違うため、pending の注文のみ付け替える
+    async fn relocate_order(&self, order_id: i32, node_id: i32) -> Result<(), AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+
+                let status: Option<String> =
+                    sqlx::query_scalar("SELECT status FROM orders WHERE id = ? FOR UPDATE")
+                        .bind(order_id)
+                        .fetch_optional(&mut tx)
+                        .await?;
+                match status.as_deref() {
+                    Some("pending") => {}
+                    Some(_) => return Err(AppError::Conflict),
+                    None => return Err(AppError::NotFound),
+                }
+
+                let area_id: Option<i32> =
+                    sqlx::query_scalar("SELECT area_id FROM nodes WHERE id = ?")
+                        .bind(node_id)
+                        .fetch_optional(&mut tx)
+                        .await?;
+                let area_id = match area_id {
+                    Some(area_id) => area_id,
+                    None => return Err(AppError::BadRequest),
+                };
+
+                sqlx::query("UPDATE orders SET node_id = ?, area_id = ? WHERE id = ?")
+                    .bind(node_id)
+                    .bind(area_id)
+                    .bind(order_id)
+                    .execute(&mut tx)
+                    .await?;
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    // completed_orders には配車時に作られた行も含まれるため、完了済みの注文に限定する
+    async fn get_paginated_completed_orders(
+        &self,
+        page: i32,
+        page_size: i32,
+    ) -> Result<Vec<CompletedOrderDetail>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let offset = page_offset(page, page_size)?;
+                let completed_orders = sqlx::query_as::<_, CompletedOrderDetail>(
+                    "SELECT
+                        co.id,
+                        co.order_id,
+                        co.tow_truck_id,
+                        o.client_id,
+                        o.car_value,
+                        o.order_time,
+                        co.completed_time
+                    FROM
+                        completed_orders co
+                    JOIN
+                        orders o
+                    ON
+                        co.order_id = o.id
+                    WHERE
+                        o.status = 'completed'
+                    ORDER BY
+                        co.completed_time DESC, co.id DESC
+                    LIMIT ? OFFSET ?",
+                )
+                .bind(page_size)
+                .bind(offset)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(completed_orders)
+            })
+            .await
+    }
+
+    async fn dispatch_counts_by_dispatcher(
+        &self,
+        area: AreaId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<HashMap<i32, i64>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                // 受付時刻ではなく配車した時刻 (order_events の dispatched) で期間を区切る
+                // 複数台での配車や再配車で同じ注文の履歴が複数あっても1件として数える
+                let counts: Vec<(i32, i64)> = sqlx::query_as(
+                    "SELECT
+                        o.dispatcher_id, COUNT(DISTINCT o.id)
+                    FROM
+                        orders o
+                    JOIN
+                        order_events e
+                    ON
+                        e.order_id = o.id AND e.event_type = 'dispatched'
+                    WHERE
+                        o.area_id = ?
+                    AND
+                        e.occurred_at >= ?
+                    AND
+                        e.occurred_at < ?
+                    AND
+                        o.dispatcher_id IS NOT NULL
+                    GROUP BY
+                        o.dispatcher_id",
+                )
+                .bind(area)
+                .bind(from)
+                .bind(to)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(counts.into_iter().collect())
+            })
+            .await
+    }
+
+    // 同じクライアントが同じ地点から window 以内に重ねて出した pending の注文を取り消す
+    // 地点ごとに最も早い注文を残し、その受付時刻から window 以内の後続の注文を canceled にする
+    // 残した注文のIDと、それに統合して取り消した注文のIDの組を返す
+    async fn cancel_duplicate_pending_orders(
+        &self,
+        client_id: i32,
+        window: chrono::Duration,
+    ) -> Result<Vec<(i32, Vec<i32>)>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+
+                let pending_orders: Vec<(i32, i32, DateTime<Utc>)> = sqlx::query_as(
+                    "SELECT
+                        id, node_id, order_time
+                    FROM
+                        orders
+                    WHERE
+                        client_id = ?
+                    AND
+                        status = 'pending'
+                    ORDER BY
+                        order_time ASC, id ASC
+                    FOR UPDATE",
+                )
+                .bind(client_id)
+                .fetch_all(&mut tx)
+                .await?;
+
+                // 地点ごとに、現在残している注文のIDと受付時刻を保持する
+                let mut kept_by_node: HashMap<i32, (i32, DateTime<Utc>)> = HashMap::new();
+                let mut groups: Vec<(i32, Vec<i32>)> = Vec::new();
+                let mut canceled_ids = Vec::new();
+                for (order_id, node_id, order_time) in pending_orders {
+                    match kept_by_node.get(&node_id) {
+                        Some(&(kept_id, kept_time)) if order_time - kept_time <= window => {
+                            canceled_ids.push(order_id);
+                            if let Some((_, canceled)) = groups.iter_mut().find(|(id, _)| *id == kept_id) {
+                                canceled.push(order_id);
+                            }
+                        }
+                        _ => {
+                            kept_by_node.insert(node_id, (order_id, order_time));
+                            groups.push((order_id, Vec::new()));
+                        }
+                    }
+                }
+
+                if !canceled_ids.is_empty() {
+                    let placeholders = canceled_ids
+                        .iter()
+                        .map(|_| "?")
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    let sql = format!(
+                        "UPDATE orders SET status = 'canceled', cancel_reason = 'duplicate' WHERE id IN ({})",
+                        placeholders
+                    );
+                    let mut query = sqlx::query(&sql);
+                    for id in &canceled_ids {
+                        query = query.bind(id);
+                    }
+                    query.execute(&mut tx).await?;
+                }
+
+                tx.commit().await?;
+
+                // 重複のなかった注文は結果に含めない
+                Ok(groups
+                    .into_iter()
+                    .filter(|(_, canceled)| !canceled.is_empty())
+                    .collect())
+            })
+            .await
+    }
+
+    // 複数台のトラックをまとめて配車する。1台でも空いていないか、他の注文を担当中であれば
+    // 何も変更せずに Conflict を返す
+    // 先頭のトラックを注文の担当トラック (orders.tow_truck_id) とし、全台を order_tow_trucks に記録する
+    async fn dispatch_order_with_tow_trucks(
+        &self,
+        order_id: i32,
+        dispatcher_id: i32,
+        tow_truck_ids: &[i32],
+        order_time: DateTime<Utc>,
+        eta_seconds: Option<i32>,
     ) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)")
+        self.circuit_breaker
+            .call(async {
+                let primary_tow_truck_id = match tow_truck_ids.first() {
+                    Some(&tow_truck_id) => tow_truck_id,
+                    None => return Err(AppError::BadRequest),
+                };
+
+                let mut tx = self.pool.begin().await?;
+
+                let placeholders = tow_truck_ids
+                    .iter()
+                    .map(|_| "?")
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let sql = format!(
+                    "SELECT COUNT(*) FROM tow_trucks WHERE id IN ({}) AND status = 'available' FOR UPDATE",
+                    placeholders
+                );
+                let mut query = sqlx::query_scalar::<_, i64>(&sql);
+                for id in tow_truck_ids {
+                    query = query.bind(id);
+                }
+                let available_count = query.fetch_one(&mut tx).await?;
+                if available_count != tow_truck_ids.len() as i64 {
+                    return Err(AppError::Conflict);
+                }
+                for &tow_truck_id in tow_truck_ids {
+                    if Self::has_other_active_order(&mut tx, order_id, tow_truck_id).await? {
+                        return Err(AppError::Conflict);
+                    }
+                }
+
+                sqlx::query("INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)")
+                    .bind(order_id)
+                    .bind(primary_tow_truck_id)
+                    .bind(order_time)
+                    .execute(&mut tx)
+                    .await
+                    .map_err(|_| AppError::BadRequest)?;
+
+                sqlx::query(
+                    "UPDATE orders SET dispatcher_id = ?, tow_truck_id = ?, eta_seconds = ?, status = 'dispatched' WHERE id = ?",
+                )
+                .bind(dispatcher_id)
+                .bind(primary_tow_truck_id)
+                .bind(eta_seconds)
+                .bind(order_id)
+                .execute(&mut tx)
+                .await?;
+
+                for &tow_truck_id in tow_truck_ids {
+                    sqlx::query("INSERT INTO order_tow_trucks (order_id, tow_truck_id) VALUES (?, ?)")
+                        .bind(order_id)
+                        .bind(tow_truck_id)
+                        .execute(&mut tx)
+                        .await?;
+                }
+                Self::record_order_event(&mut tx, order_id, "dispatched", Utc::now()).await?;
+
+                let sql = format!(
+                    "UPDATE tow_trucks SET status = 'busy' WHERE id IN ({})",
+                    placeholders
+                );
+                let mut query = sqlx::query(&sql);
+                for id in tow_truck_ids {
+                    query = query.bind(id);
+                }
+                query.execute(&mut tx).await?;
+
+                tx.commit().await?;
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn overdue_dispatched(
+        &self,
+        area: AreaId,
+        max_age: chrono::Duration,
+    ) -> Result<Vec<Order>, AppError> {
+        // 基準時刻を表せないほど長い max_age は panic させずに不正な要求として扱う
+        let threshold = Utc::now()
+            .checked_sub_signed(max_age)
+            .ok_or(AppError::BadRequest)?;
+        self.circuit_breaker
+            .call(async {
+                let orders = sqlx::query_as::<_, Order>(
+                    "SELECT
+                        o.id,
+                        o.client_id,
+                        o.dispatcher_id,
+                        o.tow_truck_id,
+                        o.status,
+                        o.node_id,
+                        o.destination_node_id,
+                        o.car_value,
+                        o.contact_phone,
+                        o.trucks_required,
+                        o.cancel_reason,
+                        o.order_time,
+                        NULL AS completed_time,
+                        o.area_id,
+                        o.eta_seconds
+                    FROM
+                        orders o
+                    WHERE
+                        o.area_id = ?
+                    AND
+                        o.status = 'dispatched'
+                    AND
+                        o.order_time < ?
+                    ORDER BY
+                        o.order_time ASC, o.id ASC",
+                )
+                .bind(area)
+                .bind(threshold)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(orders)
+            })
+            .await
+    }
+
+    async fn pending_in_area(&self, area: AreaId) -> Result<Vec<Order>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let orders = sqlx::query_as::<_, Order>(
+                    "SELECT
+                        o.id,
+                        o.client_id,
+                        o.dispatcher_id,
+                        o.tow_truck_id,
+                        o.status,
+                        o.node_id,
+                        o.destination_node_id,
+                        o.car_value,
+                        o.contact_phone,
+                        o.trucks_required,
+                        o.cancel_reason,
+                        o.order_time,
+                        NULL AS completed_time,
+                        o.area_id,
+                        o.eta_seconds
+                    FROM
+                        orders o
+                    WHERE
+                        o.area_id = ?
+                    AND
+                        o.status = 'pending'
+                    ORDER BY
+                        o.order_time ASC, o.id ASC",
+                )
+                .bind(area)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(orders)
+            })
+            .await
+    }
+
+    // 期間の開始時点で busy だったかを判定できるよう、終了時刻より前の履歴をすべて返す
+    async fn order_events_before(
+        &self,
+        area: AreaId,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<OrderEvent>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let events = sqlx::query_as::<_, OrderEvent>(
+                    "SELECT
+                        e.tow_truck_id,
+                        e.event_type,
+                        e.occurred_at
+                    FROM
+                        order_events e
+                    JOIN
+                        tow_trucks t
+                    ON
+                        t.id = e.tow_truck_id
+                    WHERE
+                        t.area_id = ?
+                    AND
+                        e.occurred_at < ?
+                    ORDER BY
+                        e.tow_truck_id ASC, e.occurred_at ASC, e.id ASC",
+                )
+                .bind(area)
+                .bind(to)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(events)
+            })
+            .await
+    }
+
+    async fn pending_at_nodes(&self, node_ids: &[i32]) -> Result<Vec<Order>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                if node_ids.is_empty() {
+                    return Ok(vec![]);
+                }
+                let placeholders = node_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+                let sql = format!(
+                    "SELECT
+                        o.id,
+                        o.client_id,
+                        o.dispatcher_id,
+                        o.tow_truck_id,
+                        o.status,
+                        o.node_id,
+                        o.destination_node_id,
+                        o.car_value,
+                        o.contact_phone,
+                        o.trucks_required,
+                        o.cancel_reason,
+                        o.order_time,
+                        NULL AS completed_time,
+                        o.area_id,
+                        o.eta_seconds
+                    FROM
+                        orders o
+                    WHERE
+                        o.status = 'pending'
+                    AND
+                        o.node_id IN ({})",
+                    placeholders
+                );
+                let mut query = sqlx::query_as::<_, Order>(&sql);
+                for node_id in node_ids {
+                    query = query.bind(node_id);
+                }
+                let orders = query.fetch_all(&self.pool).await?;
+
+                Ok(orders)
+            })
+            .await
+    }
+
+    async fn pending_in_area_after(
+        &self,
+        area: AreaId,
+        after: Option<(DateTime<Utc>, i32)>,
+        limit: i32,
+    ) -> Result<Vec<Order>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                // (order_time, id) の組で前のページの続きから取得する
+                let after_clause = match after {
+                    Some(_) => "AND (o.order_time, o.id) > (?, ?)",
+                    None => "",
+                };
+                let sql = format!(
+                    "SELECT
+                        o.id,
+                        o.client_id,
+                        o.dispatcher_id,
+                        o.tow_truck_id,
+                        o.status,
+                        o.node_id,
+                        o.destination_node_id,
+                        o.car_value,
+                        o.contact_phone,
+                        o.trucks_required,
+                        o.cancel_reason,
+                        o.order_time,
+                        NULL AS completed_time,
+                        o.area_id,
+                        o.eta_seconds
+                    FROM
+                        orders o
+                    WHERE
+                        o.area_id = ?
+                    AND
+                        o.status = 'pending'
+                    {}
+                    ORDER BY
+                        o.order_time ASC, o.id ASC
+                    LIMIT ?",
+                    after_clause
+                );
+                let mut query = sqlx::query_as::<_, Order>(&sql).bind(area);
+                if let Some((after_time, after_id)) = after {
+                    query = query.bind(after_time).bind(after_id);
+                }
+                let orders = query.bind(limit).fetch_all(&self.pool).await?;
+
+                Ok(orders)
+            })
+            .await
+    }
+
+    async fn recent_orders(
+        &self,
+        area: AreaId,
+        window: chrono::Duration,
+    ) -> Result<Vec<Order>, AppError> {
+        // 基準時刻を表せないほど長い window は panic させずに不正な要求として扱う
+        let since = Utc::now()
+            .checked_sub_signed(window)
+            .ok_or(AppError::BadRequest)?;
+        self.circuit_breaker
+            .call(async {
+                let orders = sqlx::query_as::<_, Order>(
+                    "SELECT
+                        o.id,
+                        o.client_id,
+                        o.dispatcher_id,
+                        o.tow_truck_id,
+                        o.status,
+                        o.node_id,
+                        o.destination_node_id,
+                        o.car_value,
+                        o.contact_phone,
+                        o.trucks_required,
+                        o.cancel_reason,
+                        o.order_time,
+                        co.completed_time,
+                        o.area_id,
+                        o.eta_seconds
+                    FROM
+                        orders o
+                    LEFT JOIN
+                        completed_orders co
+                    ON
+                        co.order_id = o.id AND o.status = 'completed'
+                    WHERE
+                        o.area_id = ?
+                    AND
+                        o.order_time >= ?
+                    ORDER BY
+                        o.order_time DESC, o.id DESC",
+                )
+                .bind(area)
+                .bind(since)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(orders)
+            })
+            .await
+    }
+
+    // クライアントごとに注文日時が最も新しい注文 (同時刻なら注文IDが大きいもの) をウィンドウ関数で1往復で取得する
+    async fn latest_order_per_client(
+        &self,
+        client_ids: &[i32],
+    ) -> Result<HashMap<i32, Order>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                if client_ids.is_empty() {
+                    return Ok(HashMap::new());
+                }
+                let query_placeholders = client_ids
+                    .iter()
+                    .map(|_| "?")
+                    .collect::<Vec<&str>>()
+                    .join(",");
+                let query = format!(
+                    "SELECT
+                        id,
+                        client_id,
+                        dispatcher_id,
+                        tow_truck_id,
+                        status,
+                        node_id,
+                        destination_node_id,
+                        car_value,
+                        contact_phone,
+                        trucks_required,
+                        cancel_reason,
+                        order_time,
+                        completed_time,
+                        area_id,
+                        eta_seconds
+                    FROM (
+                        SELECT
+                            o.id,
+                            o.client_id,
+                            o.dispatcher_id,
+                            o.tow_truck_id,
+                            o.status,
+                            o.node_id,
+                            o.destination_node_id,
+                            o.car_value,
+                            o.contact_phone,
+                            o.trucks_required,
+                            o.cancel_reason,
+                            o.order_time,
+                            co.completed_time,
+                            o.area_id,
+                            o.eta_seconds,
+                            ROW_NUMBER() OVER (
+                                PARTITION BY o.client_id ORDER BY o.order_time DESC, o.id DESC
+                            ) AS rn
+                        FROM
+                            orders o
+                        LEFT JOIN
+                            completed_orders co
+                        ON
+                            co.order_id = o.id AND o.status = 'completed'
+                        WHERE
+                            o.client_id IN ({})
+                    ) latest
+                    WHERE
+                        rn = 1",
+                    query_placeholders
+                );
+                let mut query_builder = sqlx::query_as::<_, Order>(&query);
+                for client_id in client_ids {
+                    query_builder = query_builder.bind(client_id);
+                }
+                let orders = query_builder.fetch_all(&self.pool).await?;
+
+                Ok(orders
+                    .into_iter()
+                    .map(|order| (order.client_id, order))
+                    .collect())
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsupport::{
+        circuit_breaker, seed_area, seed_dispatch, seed_dispatcher, seed_node, seed_order,
+        seed_truck, seed_user, test_pool,
+    };
+    use sqlx::mysql::MySqlPool;
+
+    async fn order_status(pool: &MySqlPool, order_id: i32) -> String {
+        sqlx::query_scalar("SELECT status FROM orders WHERE id = ?")
             .bind(order_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn tow_truck_status(pool: &MySqlPool, tow_truck_id: i32) -> String {
+        sqlx::query_scalar("SELECT status FROM tow_trucks WHERE id = ?")
             .bind(tow_truck_id)
-            .bind(completed_time)
-            .execute(&self.pool)
-            .await?;
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
 
-        Ok(())
+    #[actix_rt::test]
+    async fn cancel_area_pending_cancels_only_pending_orders_of_the_area() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let other_area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let other_node_id = seed_node(&pool, other_area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let pending_ids = vec![
+            seed_order(&pool, client_id, node_id).await,
+            seed_order(&pool, client_id, node_id).await,
+        ];
+        let dispatched_id = seed_order(&pool, client_id, node_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        seed_dispatch(&pool, dispatched_id, tow_truck_id).await;
+        let other_area_pending_id = seed_order(&pool, client_id, other_node_id).await;
+
+        let mut canceled_ids = repository
+            .cancel_area_pending(AreaId(area_id), CancelReason::NoCoverage)
+            .await
+            .unwrap();
+        canceled_ids.sort();
+
+        assert_eq!(canceled_ids, pending_ids);
+        for order_id in &pending_ids {
+            let (status, cancel_reason): (String, Option<String>) =
+                sqlx::query_as("SELECT status, cancel_reason FROM orders WHERE id = ?")
+                    .bind(order_id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(status, "canceled");
+            assert_eq!(
+                cancel_reason.as_deref(),
+                Some(CancelReason::NoCoverage.as_str())
+            );
+        }
+        for (order_id, expected) in [
+            (dispatched_id, "dispatched"),
+            (other_area_pending_id, "pending"),
+        ] {
+            let status: String = sqlx::query_scalar("SELECT status FROM orders WHERE id = ?")
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(status, expected);
+        }
+        // 配車済みの注文を担当するトラックはそのまま
+        let tow_truck_status: String =
+            sqlx::query_scalar("SELECT status FROM tow_trucks WHERE id = ?")
+                .bind(tow_truck_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(tow_truck_status, "busy");
+    }
+
+    #[actix_rt::test]
+    async fn truck_dispatched_from_another_area_moves_and_passes_the_completion_check() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let adjacent_area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let adjacent_node_id = seed_node(&pool, adjacent_area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, adjacent_area_id, adjacent_node_id).await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+
+        repository
+            .dispatch_order(order_id, dispatcher_id, tow_truck_id, Utc::now(), None)
+            .await
+            .unwrap();
+
+        let tow_truck_area_id: i32 =
+            sqlx::query_scalar("SELECT area_id FROM tow_trucks WHERE id = ?")
+                .bind(tow_truck_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(tow_truck_area_id, area_id);
+        let outcomes = repository
+            .bulk_complete_orders(&[order_id], Utc::now())
+            .await
+            .unwrap();
+        assert!(matches!(outcomes[0].1, CompletionOutcome::Completed(..)));
+        assert_eq!(order_status(&pool, order_id).await, "completed");
+        assert_eq!(tow_truck_status(&pool, tow_truck_id).await, "available");
+    }
+
+    #[actix_rt::test]
+    async fn bulk_complete_reports_an_outcome_for_every_requested_id() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let completed_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, completed_id, tow_truck_id).await;
+        repository
+            .bulk_complete_orders(&[completed_id], Utc::now())
+            .await
+            .unwrap();
+        // 同じトラックで2件目を完了できることも確かめる (completed_orders.tow_truck_id は UNIQUE ではない)
+        let dispatched_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, dispatched_id, tow_truck_id).await;
+        let pending_id = seed_order(&pool, client_id, node_id).await;
+        let missing_id = i32::MAX;
+
+        let outcomes = repository
+            .bulk_complete_orders(
+                &[dispatched_id, completed_id, pending_id, missing_id],
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+
+        let ids: Vec<i32> = outcomes.iter().map(|(order_id, _)| *order_id).collect();
+        assert_eq!(
+            ids,
+            vec![dispatched_id, completed_id, pending_id, missing_id]
+        );
+        match &outcomes[0].1 {
+            CompletionOutcome::Completed(completed_order, _) => {
+                assert_eq!(completed_order.order_id, dispatched_id);
+                assert_eq!(completed_order.tow_truck_id, tow_truck_id);
+            }
+            _ => panic!("expected the dispatched order to be completed"),
+        }
+        assert!(matches!(outcomes[1].1, CompletionOutcome::AlreadyCompleted));
+        assert!(matches!(outcomes[2].1, CompletionOutcome::NotDispatched));
+        assert!(matches!(outcomes[3].1, CompletionOutcome::NotFound));
+        assert_eq!(order_status(&pool, dispatched_id).await, "completed");
+        assert_eq!(order_status(&pool, pending_id).await, "pending");
+        assert_eq!(tow_truck_status(&pool, tow_truck_id).await, "available");
+        let completed_rows: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM completed_orders WHERE tow_truck_id = ?")
+                .bind(tow_truck_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(completed_rows, 2);
+    }
+
+    #[actix_rt::test]
+    async fn paginated_orders_match_any_of_the_requested_statuses() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let pending_order_id = seed_order(&pool, client_id, node_id).await;
+        let dispatched_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, dispatched_order_id, tow_truck_id).await;
+        let canceled_order_id = seed_order(&pool, client_id, node_id).await;
+        sqlx::query("UPDATE orders SET status = 'canceled' WHERE id = ?")
+            .bind(canceled_order_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let orders = repository
+            .get_paginated_orders(
+                0,
+                10,
+                None,
+                None,
+                OrderFilter {
+                    statuses: vec![OrderStatus::Pending, OrderStatus::Dispatched],
+                    area: Some(AreaId(area_id)),
+                    assigned: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        let mut order_ids: Vec<i32> = orders.iter().map(|order| order.id).collect();
+        order_ids.sort();
+        assert_eq!(order_ids, vec![pending_order_id, dispatched_order_id]);
+    }
+
+    #[actix_rt::test]
+    async fn racing_status_updates_on_one_order_leave_exactly_one_success() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+
+        // pending からはどちらの遷移も許可されているが、先に確定した方の後では他方が不正な遷移になる
+        let (dispatched, canceled) = futures::join!(
+            repository.update_order_status(order_id, OrderStatus::Dispatched),
+            repository.update_order_status(order_id, OrderStatus::Canceled),
+        );
+
+        let (expected_status, loser) = match (dispatched, canceled) {
+            (Ok(()), Err(e)) => ("dispatched", e),
+            (Err(e), Ok(())) => ("canceled", e),
+            (dispatched, canceled) => panic!(
+                "exactly one update must succeed: {:?} / {:?}",
+                dispatched, canceled
+            ),
+        };
+        assert!(matches!(loser, AppError::Conflict));
+        assert_eq!(order_status(&pool, order_id).await, expected_status);
+    }
+
+    #[actix_rt::test]
+    async fn invalid_status_transitions_are_rejected() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let other_area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let other_node_id = seed_node(&pool, other_area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let moved_tow_truck_id = seed_truck(&pool, driver_id, other_area_id, other_node_id).await;
+
+        // 配車されていない注文は完了できない
+        let pending_order_id = seed_order(&pool, client_id, node_id).await;
+        assert!(matches!(
+            repository
+                .update_order_status(pending_order_id, OrderStatus::Completed)
+                .await,
+            Err(AppError::BadRequest)
+        ));
+        assert_eq!(order_status(&pool, pending_order_id).await, "pending");
+
+        // 他のエリアに移ったトラックでは完了できない
+        let moved_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, moved_order_id, moved_tow_truck_id).await;
+        assert!(matches!(
+            repository
+                .update_order_status(moved_order_id, OrderStatus::Completed)
+                .await,
+            Err(AppError::Conflict)
+        ));
+        assert_eq!(order_status(&pool, moved_order_id).await, "dispatched");
+
+        // 配車済みの注文は完了でき、完了後は取り消しも再度の配車もできない
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, order_id, tow_truck_id).await;
+        repository
+            .update_order_status(order_id, OrderStatus::Completed)
+            .await
+            .unwrap();
+        for status in [
+            OrderStatus::Canceled,
+            OrderStatus::Dispatched,
+            OrderStatus::Pending,
+        ] {
+            assert!(matches!(
+                repository.update_order_status(order_id, status).await,
+                Err(AppError::Conflict)
+            ));
+        }
+        assert_eq!(order_status(&pool, order_id).await, "completed");
+
+        // 取り消した注文は配車できない
+        let canceled_order_id = seed_order(&pool, client_id, node_id).await;
+        repository
+            .update_order_status(canceled_order_id, OrderStatus::Canceled)
+            .await
+            .unwrap();
+        assert!(matches!(
+            repository
+                .update_order_status(canceled_order_id, OrderStatus::Dispatched)
+                .await,
+            Err(AppError::Conflict)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn paginated_orders_filter_on_whether_a_tow_truck_is_assigned() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let unassigned_order_id = seed_order(&pool, client_id, node_id).await;
+        let assigned_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, assigned_order_id, tow_truck_id).await;
+
+        for (assigned, expected_order_ids) in [
+            (Some(false), vec![unassigned_order_id]),
+            (Some(true), vec![assigned_order_id]),
+            (None, vec![unassigned_order_id, assigned_order_id]),
+        ] {
+            let orders = repository
+                .get_paginated_orders(
+                    0,
+                    10,
+                    Some("order_time".to_string()),
+                    None,
+                    OrderFilter {
+                        statuses: Vec::new(),
+                        area: Some(AreaId(area_id)),
+                        assigned,
+                    },
+                )
+                .await
+                .unwrap();
+            let mut order_ids: Vec<i32> = orders.iter().map(|order| order.id).collect();
+            order_ids.sort();
+            assert_eq!(order_ids, expected_order_ids);
+        }
+    }
+
+    // 複数台で対応した注文を完了済みの状態で作る (先頭のトラックが主担当)
+    async fn seed_completed_order(pool: &MySqlPool, order_id: i32, tow_truck_ids: &[i32]) {
+        seed_dispatch(pool, order_id, tow_truck_ids[0]).await;
+        for &tow_truck_id in tow_truck_ids {
+            sqlx::query("INSERT INTO order_tow_trucks (order_id, tow_truck_id) VALUES (?, ?)")
+                .bind(order_id)
+                .bind(tow_truck_id)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, NOW())",
+        )
+        .bind(order_id)
+        .bind(tow_truck_ids[0])
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query("UPDATE orders SET status = 'completed' WHERE id = ?")
+            .bind(order_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "UPDATE tow_trucks SET status = 'available'
+            WHERE id IN (SELECT tow_truck_id FROM order_tow_trucks WHERE order_id = ?)",
+        )
+        .bind(order_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn reopen_returns_the_order_and_all_its_tow_trucks_to_dispatched() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let primary_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let extra_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        seed_completed_order(&pool, order_id, &[primary_tow_truck_id, extra_tow_truck_id]).await;
+
+        repository.reopen_order(order_id).await.unwrap();
+
+        assert_eq!(order_status(&pool, order_id).await, "dispatched");
+        assert_eq!(tow_truck_status(&pool, primary_tow_truck_id).await, "busy");
+        assert_eq!(tow_truck_status(&pool, extra_tow_truck_id).await, "busy");
+        let completion_count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM completed_orders WHERE order_id = ?")
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(completion_count, 0);
+        // 完了していない注文は戻せない
+        assert!(matches!(
+            repository.reopen_order(order_id).await,
+            Err(AppError::BadRequest)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn reopen_is_rejected_when_a_tow_truck_has_taken_another_order() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let primary_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let extra_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        seed_completed_order(&pool, order_id, &[primary_tow_truck_id, extra_tow_truck_id]).await;
+        // 完了後に補助のトラックが別の注文へ配車された
+        let next_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, next_order_id, extra_tow_truck_id).await;
+
+        assert!(matches!(
+            repository.reopen_order(order_id).await,
+            Err(AppError::Conflict)
+        ));
+
+        assert_eq!(order_status(&pool, order_id).await, "completed");
+        assert_eq!(
+            tow_truck_status(&pool, primary_tow_truck_id).await,
+            "available"
+        );
+        assert_eq!(order_status(&pool, next_order_id).await, "dispatched");
+    }
+
+    #[actix_rt::test]
+    async fn priority_sort_puts_zero_value_orders_last_by_order_time() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let now = Utc::now();
+        // (car_value, 受付からの経過分)
+        let seeds = [(0.0, 30), (500.0, 10), (0.0, 40), (2000.0, 5), (500.0, 20)];
+        let mut order_ids = Vec::new();
+        for (car_value, minutes_ago) in seeds {
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            sqlx::query("UPDATE orders SET car_value = ?, order_time = ? WHERE id = ?")
+                .bind(car_value)
+                .bind(now - chrono::Duration::minutes(minutes_ago))
+                .bind(order_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            order_ids.push(order_id);
+        }
+
+        let orders = repository
+            .get_paginated_orders(
+                0,
+                10,
+                Some("priority".to_string()),
+                None,
+                OrderFilter {
+                    area: Some(AreaId(area_id)),
+                    ..OrderFilter::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // 金額の高い順、同じ金額なら受付の早い順で、金額 0 の注文は最後に受付の早い順で並ぶ
+        let sorted_ids: Vec<i32> = orders.iter().map(|order| order.id).collect();
+        assert_eq!(
+            sorted_ids,
+            vec![
+                order_ids[3],
+                order_ids[4],
+                order_ids[1],
+                order_ids[2],
+                order_ids[0]
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn dispatching_a_second_order_to_a_busy_truck_is_rejected() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let first_order_id = seed_order(&pool, client_id, node_id).await;
+        let second_order_id = seed_order(&pool, client_id, node_id).await;
+
+        repository
+            .dispatch_order(
+                first_order_id,
+                dispatcher_id,
+                tow_truck_id,
+                Utc::now(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            repository
+                .dispatch_order(
+                    second_order_id,
+                    dispatcher_id,
+                    tow_truck_id,
+                    Utc::now(),
+                    None
+                )
+                .await,
+            Err(AppError::Conflict)
+        ));
+
+        assert_eq!(order_status(&pool, second_order_id).await, "pending");
+        let active_order_ids = repository
+            .find_active_order_ids_by_tow_truck_ids(&[tow_truck_id])
+            .await
+            .unwrap();
+        assert_eq!(active_order_ids, vec![(tow_truck_id, first_order_id)]);
+    }
+
+    #[actix_rt::test]
+    async fn a_truck_assisting_a_multi_truck_order_cannot_take_another_order() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let primary_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let assisting_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let multi_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, multi_order_id, primary_tow_truck_id).await;
+        sqlx::query("INSERT INTO order_tow_trucks (order_id, tow_truck_id) VALUES (?, ?), (?, ?)")
+            .bind(multi_order_id)
+            .bind(primary_tow_truck_id)
+            .bind(multi_order_id)
+            .bind(assisting_tow_truck_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        // 状態の更新が漏れて available のままでも、担当中の注文があれば配車しない
+        let order_id = seed_order(&pool, client_id, node_id).await;
+
+        assert!(matches!(
+            repository
+                .dispatch_order(
+                    order_id,
+                    dispatcher_id,
+                    assisting_tow_truck_id,
+                    Utc::now(),
+                    None
+                )
+                .await,
+            Err(AppError::Conflict)
+        ));
+        assert_eq!(order_status(&pool, order_id).await, "pending");
+    }
+
+    #[actix_rt::test]
+    async fn orders_sort_by_completion_time_descending_with_incomplete_orders_last() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let pending_order_id = seed_order(&pool, client_id, node_id).await;
+        let now = Utc::now();
+        // 受付順とは逆の順に完了させる
+        let mut completed_order_ids = Vec::new();
+        for minutes_ago in [10, 30, 20] {
+            let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+            let order_id = seed_order(&pool, client_id, node_id).await;
+            seed_dispatch(&pool, order_id, tow_truck_id).await;
+            repository
+                .update_order_status(order_id, OrderStatus::Completed)
+                .await
+                .unwrap();
+            sqlx::query("UPDATE completed_orders SET completed_time = ? WHERE order_id = ?")
+                .bind(now - chrono::Duration::minutes(minutes_ago))
+                .bind(order_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            completed_order_ids.push(order_id);
+        }
+
+        let orders = repository
+            .get_paginated_orders(
+                0,
+                10,
+                Some("completed_time".to_string()),
+                Some("DESC".to_string()),
+                OrderFilter {
+                    area: Some(AreaId(area_id)),
+                    ..OrderFilter::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let sorted_ids: Vec<i32> = orders.iter().map(|order| order.id).collect();
+        assert_eq!(
+            sorted_ids,
+            vec![
+                completed_order_ids[0],
+                completed_order_ids[2],
+                completed_order_ids[1],
+                pending_order_id
+            ]
+        );
+        assert!(orders[..3]
+            .iter()
+            .all(|order| order.completed_time.is_some()));
+        assert_eq!(orders[3].completed_time, None);
+    }
+
+    #[actix_rt::test]
+    async fn multi_truck_dispatch_rejects_a_truck_that_has_another_active_order() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let dispatcher_id = seed_dispatcher(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let free_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let engaged_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let other_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, other_order_id, engaged_tow_truck_id).await;
+        // 状態の更新が漏れて available のままになっている
+        sqlx::query("UPDATE tow_trucks SET status = 'available' WHERE id = ?")
+            .bind(engaged_tow_truck_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let order_id = seed_order(&pool, client_id, node_id).await;
+
+        assert!(matches!(
+            repository
+                .dispatch_order_with_tow_trucks(
+                    order_id,
+                    dispatcher_id,
+                    &[free_tow_truck_id, engaged_tow_truck_id],
+                    Utc::now(),
+                    Some(60),
+                )
+                .await,
+            Err(AppError::Conflict)
+        ));
+
+        assert_eq!(order_status(&pool, order_id).await, "pending");
+        assert_eq!(
+            tow_truck_status(&pool, free_tow_truck_id).await,
+            "available"
+        );
+        let assigned_count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM order_tow_trucks WHERE order_id = ?")
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(assigned_count, 0);
+
+        repository
+            .dispatch_order_with_tow_trucks(
+                order_id,
+                dispatcher_id,
+                &[free_tow_truck_id],
+                Utc::now(),
+                Some(60),
+            )
+            .await
+            .unwrap();
+        let eta_seconds: Option<i32> =
+            sqlx::query_scalar("SELECT eta_seconds FROM orders WHERE id = ?")
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(eta_seconds, Some(60));
+    }
+
+    // 注文の (ノードID, エリアID)
+    async fn order_location(pool: &MySqlPool, order_id: i32) -> (i32, i32) {
+        sqlx::query_as("SELECT node_id, area_id FROM orders WHERE id = ?")
+            .bind(order_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn relocate_order_moves_a_pending_order_and_rederives_its_area() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let other_area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let same_area_node_id = seed_node(&pool, area_id).await;
+        let other_area_node_id = seed_node(&pool, other_area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+
+        repository
+            .relocate_order(order_id, same_area_node_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            order_location(&pool, order_id).await,
+            (same_area_node_id, area_id)
+        );
+
+        // 別のエリアのノードに付け替えると、注文のエリアも新しいノードのエリアになる
+        repository
+            .relocate_order(order_id, other_area_node_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            order_location(&pool, order_id).await,
+            (other_area_node_id, other_area_id)
+        );
+        assert_eq!(order_status(&pool, order_id).await, "pending");
+    }
+
+    #[actix_rt::test]
+    async fn relocate_order_rejects_dispatched_orders_and_unknown_nodes() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let other_node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let dispatched_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, dispatched_order_id, tow_truck_id).await;
+        let pending_order_id = seed_order(&pool, client_id, node_id).await;
+        // どのノードにも使われていない ID
+        let unknown_node_id = i32::MAX;
+
+        assert!(matches!(
+            repository
+                .relocate_order(dispatched_order_id, other_node_id)
+                .await,
+            Err(AppError::Conflict)
+        ));
+        assert!(matches!(
+            repository
+                .relocate_order(pending_order_id, unknown_node_id)
+                .await,
+            Err(AppError::BadRequest)
+        ));
+
+        // どちらの場合も注文は元の地点のまま変わらない
+        for order_id in [dispatched_order_id, pending_order_id] {
+            assert_eq!(order_location(&pool, order_id).await, (node_id, area_id));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn completing_an_order_releases_the_primary_and_assisting_tow_trucks() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let primary_driver_id = seed_user(&pool, "driver").await;
+        let assisting_driver_id = seed_user(&pool, "driver").await;
+        let primary_tow_truck_id = seed_truck(&pool, primary_driver_id, area_id, node_id).await;
+        let assisting_tow_truck_id = seed_truck(&pool, assisting_driver_id, area_id, node_id).await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, order_id, primary_tow_truck_id).await;
+        seed_dispatch(&pool, order_id, assisting_tow_truck_id).await;
+        sqlx::query("UPDATE orders SET tow_truck_id = ? WHERE id = ?")
+            .bind(primary_tow_truck_id)
+            .bind(order_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO order_tow_trucks (order_id, tow_truck_id) VALUES (?, ?)")
+            .bind(order_id)
+            .bind(assisting_tow_truck_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        repository
+            .update_order_status(order_id, OrderStatus::Completed)
+            .await
+            .unwrap();
+
+        for tow_truck_id in [primary_tow_truck_id, assisting_tow_truck_id] {
+            assert_eq!(tow_truck_status(&pool, tow_truck_id).await, "available");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn completing_an_order_stamps_the_completion_time() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, order_id, tow_truck_id).await;
+
+        let before = Utc::now() - chrono::Duration::seconds(1);
+        repository
+            .update_order_status(order_id, OrderStatus::Completed)
+            .await
+            .unwrap();
+        let after = Utc::now() + chrono::Duration::seconds(1);
+
+        // 注文の完了時刻と completed_orders の行は同じ時刻で記録される
+        let order_completed_time: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT completed_time FROM orders WHERE id = ?")
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let (completed_tow_truck_id, completed_time): (i32, DateTime<Utc>) = sqlx::query_as(
+            "SELECT tow_truck_id, completed_time FROM completed_orders WHERE order_id = ?",
+        )
+        .bind(order_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(completed_tow_truck_id, tow_truck_id);
+        assert_eq!(order_completed_time, Some(completed_time));
+        assert!(before <= completed_time && completed_time <= after);
+        let order = repository.find_order_by_id(order_id).await.unwrap();
+        assert_eq!(order.completed_time, Some(completed_time));
+    }
+
+    #[actix_rt::test]
+    async fn orders_with_equal_sort_keys_page_in_a_stable_order() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        // car_value も status もすべて同じ注文
+        let mut order_ids = Vec::new();
+        for _ in 0..5 {
+            order_ids.push(seed_order(&pool, client_id, node_id).await);
+        }
+
+        for sort_by in ["car_value", "status"] {
+            for sort_order in ["ASC", "DESC"] {
+                // 2件ずつのページを2回読み、どちらも ID の昇順で重複も欠けもなく並ぶ
+                for _ in 0..2 {
+                    let mut paged_ids = Vec::new();
+                    for page in 0..3 {
+                        let orders = repository
+                            .get_paginated_orders(
+                                page,
+                                2,
+                                Some(sort_by.to_string()),
+                                Some(sort_order.to_string()),
+                                OrderFilter {
+                                    area: Some(AreaId(area_id)),
+                                    ..OrderFilter::default()
+                                },
+                            )
+                            .await
+                            .unwrap();
+                        paged_ids.extend(orders.iter().map(|order| order.id));
+                    }
+                    assert_eq!(paged_ids, order_ids, "sort_by={} {}", sort_by, sort_order);
+                }
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn area_stats_average_only_completed_orders() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let now = Utc::now();
+        // 受付から1時間で完了した注文と、配車時に completed_orders の行が作られただけの配車中の注文
+        let completed_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let completed_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_completed_order(&pool, completed_order_id, &[completed_tow_truck_id]).await;
+        let dispatched_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let dispatched_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, dispatched_order_id, dispatched_tow_truck_id).await;
+        sqlx::query(
+            "INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)",
+        )
+        .bind(dispatched_order_id)
+        .bind(dispatched_tow_truck_id)
+        .bind(now)
+        .execute(&pool)
+        .await
+        .unwrap();
+        for (order_id, order_time, completed_time) in [
+            (
+                completed_order_id,
+                now - chrono::Duration::hours(2),
+                now - chrono::Duration::hours(1),
+            ),
+            (
+                dispatched_order_id,
+                now - chrono::Duration::minutes(10),
+                now,
+            ),
+        ] {
+            sqlx::query("UPDATE orders SET order_time = ? WHERE id = ?")
+                .bind(order_time)
+                .bind(order_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            sqlx::query("UPDATE completed_orders SET completed_time = ? WHERE order_id = ?")
+                .bind(completed_time)
+                .bind(order_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let stats = repository.area_stats().await.unwrap();
+
+        let area_stats = stats
+            .iter()
+            .find(|stats| stats.area_id == AreaId(area_id))
+            .unwrap();
+        assert_eq!(area_stats.average_completion_seconds, Some(3600.0));
+    }
+
+    #[actix_rt::test]
+    async fn latest_order_per_client_returns_the_newest_order_of_each_client() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let now = Utc::now();
+        let mut client_ids = Vec::new();
+        let mut expected = HashMap::new();
+        // 受付時刻を登録順とは逆にして、ID ではなく受付時刻で最新を選ぶことを確かめる
+        for minutes_ago_list in [[5, 30, 60], [90, 15, 45]] {
+            let client_id = seed_user(&pool, "client").await;
+            let mut orders = Vec::new();
+            for minutes_ago in minutes_ago_list {
+                let order_id = seed_order(&pool, client_id, node_id).await;
+                sqlx::query("UPDATE orders SET order_time = ? WHERE id = ?")
+                    .bind(now - chrono::Duration::minutes(minutes_ago))
+                    .bind(order_id)
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+                orders.push((minutes_ago, order_id));
+            }
+            client_ids.push(client_id);
+            expected.insert(client_id, orders.iter().min().unwrap().1);
+        }
+        // 注文のないクライアントは結果に含まれない
+        let client_without_orders_id = seed_user(&pool, "client").await;
+        client_ids.push(client_without_orders_id);
+
+        let latest = repository
+            .latest_order_per_client(&client_ids)
+            .await
+            .unwrap();
+
+        let latest_ids: HashMap<i32, i32> = latest
+            .iter()
+            .map(|(&client_id, order)| (client_id, order.id))
+            .collect();
+        assert_eq!(latest_ids, expected);
+        assert!(repository
+            .latest_order_per_client(&[])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn create_order_tx_returns_a_usable_id_and_leaves_nothing_on_failure() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+
+        let order_id = repository
+            .create_order_tx(client_id, node_id, None, 500000.0, Some("090-0000-0000"), 1)
+            .await
+            .unwrap();
+
+        let order = repository.find_order_by_id(order_id).await.unwrap();
+        assert_eq!(order.client_id, client_id);
+        assert_eq!((order.node_id, order.area_id), (node_id, area_id));
+        assert_eq!(order.status, "pending");
+
+        // 存在しないノード (エリアの取得で失敗) と存在しないクライアント (挿入で失敗) はどちらも注文を作らない
+        assert!(repository
+            .create_order_tx(client_id, i32::MAX, None, 500000.0, None, 1)
+            .await
+            .is_err());
+        assert!(repository
+            .create_order_tx(-1, node_id, None, 500000.0, None, 1)
+            .await
+            .is_err());
+        let order_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM orders WHERE node_id = ?")
+            .bind(node_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(order_count, 1);
+    }
+
+    #[actix_rt::test]
+    async fn paginated_order_queries_reject_negative_pages() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = OrderRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        let filter = OrderFilter {
+            area: Some(AreaId(area_id)),
+            ..OrderFilter::default()
+        };
+
+        for (page, page_size) in [(-1, 10), (0, 0), (0, -1)] {
+            assert!(matches!(
+                repository
+                    .get_paginated_orders(page, page_size, None, None, filter.clone())
+                    .await,
+                Err(AppError::BadRequest)
+            ));
+            assert!(matches!(
+                repository
+                    .get_paginated_completed_orders(page, page_size)
+                    .await,
+                Err(AppError::BadRequest)
+            ));
+        }
+        let orders = repository
+            .get_paginated_orders(0, 10, None, None, filter)
+            .await
+            .unwrap();
+        assert_eq!(
+            orders.iter().map(|order| order.id).collect::<Vec<i32>>(),
+            vec![order_id]
+        );
     }
 }
@@ -1,34 +1,45 @@
 use crate::domains::tow_truck_service::TowTruckRepository;
 use crate::errors::AppError;
-use crate::models::tow_truck::TowTruck;
-use sqlx::mysql::MySqlPool;
+use crate::infrastructure::circuit_breaker::CircuitBreaker;
+use crate::infrastructure::db::DbPool;
+use crate::models::area::AreaId;
+use crate::models::tow_truck::{LocationRecord, TowTruck};
+use crate::utils::page_offset;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct TowTruckRepositoryImpl {
-    pool: MySqlPool,
+    pool: DbPool,
+    circuit_breaker: Arc<CircuitBreaker>,
 }
 
 impl TowTruckRepositoryImpl {
-    pub fn new(pool: MySqlPool) -> Self {
-        TowTruckRepositoryImpl { pool }
+    pub fn new(pool: impl Into<DbPool>, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        TowTruckRepositoryImpl {
+            pool: pool.into(),
+            circuit_breaker,
+        }
     }
-}
-impl TowTruckRepository for TowTruckRepositoryImpl {
-    async fn get_paginated_tow_trucks(
+
+    // pagination は (page_size, offset)。None の場合は全件を返す
+    async fn fetch_tow_trucks(
         &self,
-        page: i32,
-        page_size: i32,
+        pagination: Option<(i32, i64)>,
         status: Option<String>,
-        area_id: Option<i32>,
+        area_id: Option<AreaId>,
+        include_deleted: bool,
+        model: Option<String>,
     ) -> Result<Vec<TowTruck>, AppError> {
         let where_clause = match (status, area_id) {
             (Some(status), Some(area_id)) => format!(
                 "WHERE tt.status = '{}' AND tt.area_id = {} AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-                status, area_id
+                status, area_id.0
             ),
             (None, Some(area_id)) => format!(
                 "WHERE tt.area_id = {} AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-                area_id
+                area_id.0
             ),
             (Some(status), None) => format!(
                 "WHERE tt.status = '{}' AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
@@ -37,13 +48,20 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
             (None, None) => "WHERE l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)"
                 .to_string(),
         };
-        let limit_clause = match page_size {
-            -1 => "".to_string(),
-            _ => format!("LIMIT {}", page_size),
+        // 管理者が明示した場合のみ、論理削除済みのトラックも一覧に含める
+        let deleted_clause = match include_deleted {
+            true => "",
+            false => "AND tt.deleted_at IS NULL",
+        };
+        let model_clause = match model {
+            Some(_) => "AND tt.model = ?",
+            None => "",
         };
-        let offset_clause = match page_size {
-            -1 => "".to_string(),
-            page_size => format!("OFFSET {}", page * page_size),
+        let (limit_clause, offset_clause) = match pagination {
+            Some((page_size, offset)) => {
+                (format!("LIMIT {}", page_size), format!("OFFSET {}", offset))
+            }
+            None => ("".to_string(), "".to_string()),
         };
         let query = format!(
             "SELECT
@@ -52,7 +70,10 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
                 u.username AS driver_username,
                 tt.status,
                 tt.area_id,
-                l.node_id
+                tt.model,
+                tt.year,
+                l.node_id,
+                tt.deleted_at
             FROM
                 tow_trucks tt
             JOIN
@@ -64,85 +85,820 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
             ON
                 tt.id = l.tow_truck_id
             {}
+            {}
+            {}
             ORDER BY
                 tt.id ASC
             {}
             {}",
-            where_clause, limit_clause, offset_clause
+            where_clause, deleted_clause, model_clause, limit_clause, offset_clause
         );
-        let tow_trucks = sqlx::query_as::<_, TowTruck>(&query)
-            .fetch_all(&self.pool)
-            .await?;
+        let mut query_builder = sqlx::query_as::<_, TowTruck>(&query);
+        if let Some(model) = model {
+            query_builder = query_builder.bind(model);
+        }
+        let tow_trucks = query_builder.fetch_all(&self.pool).await?;
         Ok(tow_trucks)
     }
-    async fn update_location(&self, tow_truck_id: i32, node_id: i32) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO locations (tow_truck_id, node_id) VALUES (?, ?)")
-            .bind(tow_truck_id)
-            .bind(node_id)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
-    }
-    async fn update_status(&self, tow_truck_id: i32, status: &str) -> Result<(), AppError> {
-        sqlx::query("UPDATE tow_trucks SET status = ? WHERE id = ?")
-            .bind(status)
-            .bind(tow_truck_id)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+}
+impl TowTruckRepository for TowTruckRepositoryImpl {
+    async fn get_paginated_tow_trucks(
+        &self,
+        page: i32,
+        page_size: i32,
+        status: Option<String>,
+        area_id: Option<AreaId>,
+        include_deleted: bool,
+        model: Option<String>,
+    ) -> Result<Vec<TowTruck>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let offset = page_offset(page, page_size)?;
+                self.fetch_tow_trucks(
+                    Some((page_size, offset)),
+                    status,
+                    area_id,
+                    include_deleted,
+                    model,
+                )
+                .await
+            })
+            .await
+    }
+    async fn get_all_tow_trucks(
+        &self,
+        status: Option<String>,
+        area_id: Option<AreaId>,
+        include_deleted: bool,
+        model: Option<String>,
+    ) -> Result<Vec<TowTruck>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                self.fetch_tow_trucks(None, status, area_id, include_deleted, model)
+                    .await
+            })
+            .await
+    }
+    // 同じトラックの位置情報が前後して届いた場合に、古い位置で新しい位置を上書きしないよう
+    // 送信時刻が前回記録した位置の送信時刻 (location_sent_at) より新しいときだけ記録する
+    // (記録しなかった場合は false を返す)。送信時刻はトラック側の時計なので、
+    // サーバーの時計で更新する生存報告の時刻 (last_updated_at) とは比べない
+    async fn update_location(
+        &self,
+        tow_truck_id: i32,
+        node_id: i32,
+        timestamp: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let mut tx = self.pool.begin().await?;
+                let result = sqlx::query(
+                    "UPDATE tow_trucks SET location_sent_at = ?, last_updated_at = NOW()
+                    WHERE id = ? AND (location_sent_at IS NULL OR location_sent_at < ?)",
+                )
+                .bind(timestamp)
+                .bind(tow_truck_id)
+                .bind(timestamp)
+                .execute(&mut tx)
+                .await?;
+                if result.rows_affected() == 0 {
+                    return Ok(false);
+                }
+                sqlx::query(
+                    "INSERT INTO locations (tow_truck_id, node_id, timestamp) VALUES (?, ?, ?)",
+                )
+                .bind(tow_truck_id)
+                .bind(node_id)
+                .bind(timestamp)
+                .execute(&mut tx)
+                .await?;
+                tx.commit().await?;
+                Ok(true)
+            })
+            .await
+    }
+    // 位置は変えずに last_updated_at だけを更新する
+    async fn heartbeat(&self, tow_truck_id: i32) -> Result<(), AppError> {
+        self.circuit_breaker
+            .call(async {
+                sqlx::query("UPDATE tow_trucks SET last_updated_at = NOW() WHERE id = ?")
+                    .bind(tow_truck_id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            })
+            .await
+    }
+    // 既に同じステータスの場合は更新せず false を返す
+    async fn update_status(&self, tow_truck_id: i32, status: &str) -> Result<bool, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let result =
+                    sqlx::query("UPDATE tow_trucks SET status = ? WHERE id = ? AND status <> ?")
+                        .bind(status)
+                        .bind(tow_truck_id)
+                        .bind(status)
+                        .execute(&self.pool)
+                        .await?;
+                Ok(result.rows_affected() > 0)
+            })
+            .await
+    }
+    // busy なのに担当中の注文 (複数台対応の割り当てを含む) が1件もないトラックを available に戻し、そのIDを返す
+    // area_id が None の場合は全エリアを対象にする
+    // 対象の行をロックしてから更新するため、確認と更新の間に配車されたトラックを戻すことはない
+    async fn free_busy_without_active_order(
+        &self,
+        area_id: Option<AreaId>,
+    ) -> Result<Vec<i32>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let area_clause = match area_id {
+                    Some(_) => "AND tt.area_id = ?",
+                    None => "",
+                };
+                let sql = format!(
+                    "SELECT
+                        tt.id
+                    FROM
+                        tow_trucks tt
+                    WHERE
+                        tt.status = 'busy'
+                    {}
+                    AND NOT EXISTS (
+                        SELECT 1 FROM orders o
+                        WHERE o.status NOT IN ('completed', 'canceled')
+                        AND (
+                            o.tow_truck_id = tt.id
+                            OR o.id IN (SELECT order_id FROM order_tow_trucks WHERE tow_truck_id = tt.id)
+                        )
+                    )
+                    FOR UPDATE",
+                    area_clause
+                );
+
+                let mut tx = self.pool.begin().await?;
+                let mut query = sqlx::query_scalar::<_, i32>(&sql);
+                if let Some(area_id) = area_id {
+                    query = query.bind(area_id);
+                }
+                let tow_truck_ids = query.fetch_all(&mut tx).await?;
+
+                if !tow_truck_ids.is_empty() {
+                    let placeholders = tow_truck_ids
+                        .iter()
+                        .map(|_| "?")
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    let sql = format!(
+                        "UPDATE tow_trucks SET status = 'available' WHERE id IN ({})",
+                        placeholders
+                    );
+                    let mut query = sqlx::query(&sql);
+                    for id in &tow_truck_ids {
+                        query = query.bind(id);
+                    }
+                    query.execute(&mut tx).await?;
+                }
+
+                tx.commit().await?;
+
+                Ok(tow_truck_ids)
+            })
+            .await
+    }
+    async fn location_history(
+        &self,
+        tow_truck_id: i32,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<LocationRecord>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let records = sqlx::query_as::<_, LocationRecord>(
+                    "SELECT node_id, timestamp FROM locations
+                    WHERE tow_truck_id = ? AND timestamp BETWEEN ? AND ?
+                    ORDER BY timestamp ASC, id ASC",
+                )
+                .bind(tow_truck_id)
+                .bind(from)
+                .bind(to)
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(records)
+            })
+            .await
     }
     async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError> {
-        let tow_truck = sqlx::query_as::<_, TowTruck>(
-            "SELECT
-                tt.id, tt.driver_id, u.username AS driver_username, tt.status, l.node_id, tt.area_id
-            FROM
-                tow_trucks tt
-            JOIN
-                users u
-            ON
-                tt.driver_id = u.id
-            JOIN
-                locations l
-            ON
-                tt.id = l.tow_truck_id
-            WHERE
-                tt.id = ?
-            AND
-                l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await?;
-        Ok(tow_truck)
+        self.circuit_breaker
+            .call(async {
+                let tow_truck = sqlx::query_as::<_, TowTruck>(
+                    "SELECT
+                        tt.id, tt.driver_id, u.username AS driver_username, tt.status, l.node_id, tt.area_id, tt.model, tt.year
+                    FROM
+                        tow_trucks tt
+                    JOIN
+                        users u
+                    ON
+                        tt.driver_id = u.id
+                    JOIN
+                        locations l
+                    ON
+                        tt.id = l.tow_truck_id
+                    WHERE
+                        tt.id = ?
+                    AND
+                        l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
+                )
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+                Ok(tow_truck)
+            })
+            .await
     }
     // 新規追加: 複数のトウ・トラックを一度に取得するメソッド
     async fn find_tow_truck_by_ids(&self, ids: &[i32]) -> Result<Vec<TowTruck>, AppError> {
-        if ids.is_empty() {
-            return Ok(vec![]); // 空のIDリストに対しては空の結果を返す
+        self.circuit_breaker
+            .call(async {
+                if ids.is_empty() {
+                    return Ok(vec![]); // 空のIDリストに対しては空の結果を返す
+                }
+                // プレースホルダの生成
+                let query_placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+                // クエリ文字列を作成
+                let query = format!(
+                    "SELECT
+                        tt.id, tt.driver_id, u.username AS driver_username, tt.status, l.node_id, tt.area_id, tt.model, tt.year
+                    FROM
+                        tow_trucks tt
+                    JOIN
+                        users u ON tt.driver_id = u.id
+                    JOIN
+                        locations l ON tt.id = l.tow_truck_id
+                    WHERE
+                        tt.id IN ({})",
+                    query_placeholders
+                );
+                // クエリを実行し、IDリストをバインド
+                let mut query_builder = sqlx::query_as::<_, TowTruck>(&query);
+                for id in ids {
+                    query_builder = query_builder.bind(id);
+                }
+                // クエリの実行
+                let tow_trucks = query_builder.fetch_all(&self.pool).await?;
+                Ok(tow_trucks)
+            })
+            .await
+    }
+    async fn available_in_area(&self, area_id: AreaId) -> Result<Vec<TowTruck>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let tow_trucks = sqlx::query_as::<_, TowTruck>(
+                    "SELECT
+                        tt.id,
+                        tt.driver_id,
+                        u.username AS driver_username,
+                        tt.status,
+                        tt.area_id,
+                        tt.model,
+                        tt.year,
+                        l.node_id
+                    FROM
+                        tow_trucks tt
+                    JOIN
+                        users u
+                    ON
+                        tt.driver_id = u.id
+                    JOIN
+                        locations l
+                    ON
+                        tt.id = l.tow_truck_id
+                    WHERE
+                        tt.status = 'available'
+                    AND
+                        tt.area_id = ?
+                    AND
+                        tt.deleted_at IS NULL
+                    AND
+                        l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)
+                    ORDER BY
+                        tt.id ASC",
+                )
+                .bind(area_id)
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(tow_trucks)
+            })
+            .await
+    }
+    async fn stale_busy(
+        &self,
+        area_id: Option<AreaId>,
+        stale_after_seconds: i64,
+    ) -> Result<Vec<TowTruck>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let area_clause = match area_id {
+                    Some(_) => "AND tt.area_id = ?",
+                    None => "",
+                };
+                let query = format!(
+                    "SELECT
+                        tt.id,
+                        tt.driver_id,
+                        u.username AS driver_username,
+                        tt.status,
+                        tt.area_id,
+                        tt.model,
+                        tt.year,
+                        l.node_id
+                    FROM
+                        tow_trucks tt
+                    JOIN
+                        users u
+                    ON
+                        tt.driver_id = u.id
+                    JOIN
+                        locations l
+                    ON
+                        tt.id = l.tow_truck_id
+                    WHERE
+                        tt.status = 'busy'
+                    AND
+                        tt.last_updated_at < NOW() - INTERVAL ? SECOND
+                    {}
+                    AND
+                        l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)
+                    ORDER BY
+                        tt.id ASC",
+                    area_clause
+                );
+                let mut query_builder = sqlx::query_as::<_, TowTruck>(&query).bind(stale_after_seconds);
+                if let Some(area_id) = area_id {
+                    query_builder = query_builder.bind(area_id);
+                }
+                let tow_trucks = query_builder.fetch_all(&self.pool).await?;
+                Ok(tow_trucks)
+            })
+            .await
+    }
+    // 論理削除済みのトラックは台数に含めない
+    async fn count_by_status_and_area(
+        &self,
+        area_id: AreaId,
+    ) -> Result<HashMap<String, i64>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let counts: Vec<(String, i64)> = sqlx::query_as(
+                    "SELECT
+                        status, COUNT(*)
+                    FROM
+                        tow_trucks
+                    WHERE
+                        area_id = ?
+                    AND
+                        deleted_at IS NULL
+                    GROUP BY
+                        status",
+                )
+                .bind(area_id)
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(counts.into_iter().collect())
+            })
+            .await
+    }
+    async fn find_by_driver_username(&self, username: &str) -> Result<Vec<TowTruck>, AppError> {
+        self.circuit_breaker
+            .call(async {
+                let tow_trucks = sqlx::query_as::<_, TowTruck>(
+                    "SELECT
+                        tt.id,
+                        tt.driver_id,
+                        u.username AS driver_username,
+                        tt.status,
+                        tt.area_id,
+                        tt.model,
+                        tt.year,
+                        l.node_id
+                    FROM
+                        tow_trucks tt
+                    JOIN
+                        users u
+                    ON
+                        tt.driver_id = u.id
+                    JOIN
+                        locations l
+                    ON
+                        tt.id = l.tow_truck_id
+                    WHERE
+                        u.username = ?
+                    AND
+                        tt.deleted_at IS NULL
+                    AND
+                        l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)
+                    ORDER BY
+                        tt.id ASC",
+                )
+                .bind(username)
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(tow_trucks)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsupport::{
+        circuit_breaker, seed_area, seed_dispatch, seed_node, seed_order, seed_truck, seed_user,
+        test_pool,
+    };
+    use sqlx::mysql::MySqlPool;
+
+    async fn tow_truck_status(pool: &MySqlPool, tow_truck_id: i32) -> String {
+        sqlx::query_scalar("SELECT status FROM tow_trucks WHERE id = ?")
+            .bind(tow_truck_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn frees_only_busy_tow_trucks_without_an_active_order() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let other_area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let other_node_id = seed_node(&pool, other_area_id).await;
+        let client_id = seed_user(&pool, "client").await;
+
+        // 担当中の注文があるトラック
+        let working_id =
+            seed_truck(&pool, seed_user(&pool, "driver").await, area_id, node_id).await;
+        let order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, order_id, working_id).await;
+        // 担当していた注文が完了済みのまま busy で残ったトラック
+        let orphaned_id =
+            seed_truck(&pool, seed_user(&pool, "driver").await, area_id, node_id).await;
+        let completed_order_id = seed_order(&pool, client_id, node_id).await;
+        seed_dispatch(&pool, completed_order_id, orphaned_id).await;
+        sqlx::query("UPDATE orders SET status = 'completed' WHERE id = ?")
+            .bind(completed_order_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        // 別のエリアで busy のまま残ったトラック
+        let other_area_orphaned_id = seed_truck(
+            &pool,
+            seed_user(&pool, "driver").await,
+            other_area_id,
+            other_node_id,
+        )
+        .await;
+        sqlx::query("UPDATE tow_trucks SET status = 'busy' WHERE id = ?")
+            .bind(other_area_orphaned_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let freed = repository
+            .free_busy_without_active_order(Some(AreaId(area_id)))
+            .await
+            .unwrap();
+
+        assert_eq!(freed, vec![orphaned_id]);
+        assert_eq!(tow_truck_status(&pool, orphaned_id).await, "available");
+        assert_eq!(tow_truck_status(&pool, working_id).await, "busy");
+        assert_eq!(
+            tow_truck_status(&pool, other_area_orphaned_id).await,
+            "busy"
+        );
+
+        // エリアを指定しない場合 (定期的な自己修復) は全エリアが対象になる
+        let freed = repository
+            .free_busy_without_active_order(None)
+            .await
+            .unwrap();
+
+        assert!(freed.contains(&other_area_orphaned_id));
+        assert!(!freed.contains(&working_id));
+        assert_eq!(
+            tow_truck_status(&pool, other_area_orphaned_id).await,
+            "available"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn older_location_arriving_after_a_newer_one_is_discarded() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let start_node_id = seed_node(&pool, area_id).await;
+        let newer_node_id = seed_node(&pool, area_id).await;
+        let older_node_id = seed_node(&pool, area_id).await;
+        let latest_node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, start_node_id).await;
+        // トラック側の時計はサーバーより1時間遅れているものとし、同じ秒の中で前後して届く
+        let sent_at = Utc::now() - chrono::Duration::hours(1);
+
+        assert!(repository
+            .update_location(
+                tow_truck_id,
+                newer_node_id,
+                sent_at + chrono::Duration::milliseconds(500)
+            )
+            .await
+            .unwrap());
+        // 間にサーバーの時計で記録する生存報告が入っても、位置情報の順序の判定には影響しない
+        repository.heartbeat(tow_truck_id).await.unwrap();
+        assert!(!repository
+            .update_location(tow_truck_id, older_node_id, sent_at)
+            .await
+            .unwrap());
+        assert!(repository
+            .update_location(
+                tow_truck_id,
+                latest_node_id,
+                sent_at + chrono::Duration::milliseconds(800)
+            )
+            .await
+            .unwrap());
+
+        let node_ids: Vec<i32> = repository
+            .location_history(
+                tow_truck_id,
+                sent_at - chrono::Duration::seconds(1),
+                sent_at + chrono::Duration::seconds(1),
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| record.node_id)
+            .collect();
+        assert_eq!(node_ids, vec![newer_node_id, latest_node_id]);
+    }
+
+    #[actix_rt::test]
+    async fn each_location_update_appends_a_history_row() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let mut node_ids = Vec::new();
+        for _ in 0..3 {
+            node_ids.push(seed_node(&pool, area_id).await);
         }
-        // プレースホルダの生成
-        let query_placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-        // クエリ文字列を作成
-        let query = format!(
-            "SELECT
-                tt.id, tt.driver_id, u.username AS driver_username, tt.status, l.node_id, tt.area_id
-            FROM
-                tow_trucks tt
-            JOIN
-                users u ON tt.driver_id = u.id
-            JOIN
-                locations l ON tt.id = l.tow_truck_id
-            WHERE
-                tt.id IN ({})",
-            query_placeholders
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_ids[0]).await;
+        let from = Utc::now() - chrono::Duration::hours(2);
+
+        // 同じノードへの更新も上書きせずに1件として残す
+        let updates = [node_ids[1], node_ids[2], node_ids[2]];
+        for (i, &node_id) in updates.iter().enumerate() {
+            let sent_at = from + chrono::Duration::minutes(i as i64 + 1);
+            assert!(repository
+                .update_location(tow_truck_id, node_id, sent_at)
+                .await
+                .unwrap());
+        }
+
+        let history = repository
+            .location_history(tow_truck_id, from, from + chrono::Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|record| record.node_id)
+                .collect::<Vec<_>>(),
+            updates
         );
-        // クエリを実行し、IDリストをバインド
-        let mut query_builder = sqlx::query_as::<_, TowTruck>(&query);
-        for id in ids {
-            query_builder = query_builder.bind(id);
+        assert!(history
+            .windows(2)
+            .all(|pair| pair[0].timestamp < pair[1].timestamp));
+    }
+
+    #[actix_rt::test]
+    async fn available_in_area_binds_the_area_id_to_the_area_column() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let other_area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let other_node_id = seed_node(&pool, other_area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        seed_truck(&pool, driver_id, other_area_id, other_node_id).await;
+
+        let tow_trucks = repository
+            .available_in_area(AreaId::from(area_id))
+            .await
+            .unwrap();
+
+        let tow_truck_ids: Vec<i32> = tow_trucks.iter().map(|tow_truck| tow_truck.id).collect();
+        assert_eq!(tow_truck_ids, vec![tow_truck_id]);
+        assert!(tow_trucks
+            .iter()
+            .all(|tow_truck| tow_truck.area_id == area_id));
+    }
+
+    #[actix_rt::test]
+    async fn available_in_area_excludes_busy_and_deleted_tow_trucks() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let available_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let busy_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let deleted_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        repository
+            .update_status(busy_tow_truck_id, "busy")
+            .await
+            .unwrap();
+        sqlx::query("UPDATE tow_trucks SET deleted_at = NOW() WHERE id = ?")
+            .bind(deleted_tow_truck_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let tow_trucks = repository.available_in_area(AreaId(area_id)).await.unwrap();
+
+        let tow_truck_ids: Vec<i32> = tow_trucks.iter().map(|tow_truck| tow_truck.id).collect();
+        assert_eq!(tow_truck_ids, vec![available_tow_truck_id]);
+        assert_eq!(tow_trucks[0].node_id, node_id);
+    }
+
+    #[actix_rt::test]
+    async fn find_by_driver_username_returns_the_drivers_active_tow_trucks() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let driver_id = seed_user(&pool, "driver").await;
+        let other_driver_id = seed_user(&pool, "driver").await;
+        let tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        let deleted_tow_truck_id = seed_truck(&pool, driver_id, area_id, node_id).await;
+        seed_truck(&pool, other_driver_id, area_id, node_id).await;
+        sqlx::query("UPDATE tow_trucks SET deleted_at = NOW() WHERE id = ?")
+            .bind(deleted_tow_truck_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let username: String = sqlx::query_scalar("SELECT username FROM users WHERE id = ?")
+            .bind(driver_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        let tow_trucks = repository.find_by_driver_username(&username).await.unwrap();
+
+        let found: Vec<(i32, i32, Option<String>)> = tow_trucks
+            .into_iter()
+            .map(|tow_truck| (tow_truck.id, tow_truck.driver_id, tow_truck.driver_username))
+            .collect();
+        assert_eq!(found, vec![(tow_truck_id, driver_id, Some(username))]);
+        assert!(repository
+            .find_by_driver_username("no_such_driver")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn tow_trucks_are_filtered_by_model() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let flatbed_id =
+            seed_truck(&pool, seed_user(&pool, "driver").await, area_id, node_id).await;
+        let other_flatbed_id =
+            seed_truck(&pool, seed_user(&pool, "driver").await, area_id, node_id).await;
+        let wrecker_id =
+            seed_truck(&pool, seed_user(&pool, "driver").await, area_id, node_id).await;
+        for (tow_truck_id, model, year) in [
+            (flatbed_id, "flatbed", Some(2018)),
+            (other_flatbed_id, "flatbed", None),
+            (wrecker_id, "wrecker", Some(2021)),
+        ] {
+            sqlx::query("UPDATE tow_trucks SET model = ?, year = ? WHERE id = ?")
+                .bind(model)
+                .bind(year)
+                .bind(tow_truck_id)
+                .execute(&pool)
+                .await
+                .unwrap();
         }
-        // クエリの実行
-        let tow_trucks = query_builder.fetch_all(&self.pool).await?;
-        Ok(tow_trucks)
+
+        let flatbeds = repository
+            .get_all_tow_trucks(
+                None,
+                Some(AreaId(area_id)),
+                false,
+                Some("flatbed".to_string()),
+            )
+            .await
+            .unwrap();
+        let all = repository
+            .get_all_tow_trucks(None, Some(AreaId(area_id)), false, None)
+            .await
+            .unwrap();
+
+        let found: Vec<(i32, Option<String>, Option<i32>)> = flatbeds
+            .into_iter()
+            .map(|tow_truck| (tow_truck.id, tow_truck.model, tow_truck.year))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (flatbed_id, Some("flatbed".to_string()), Some(2018)),
+                (other_flatbed_id, Some("flatbed".to_string()), None),
+            ]
+        );
+        // モデルを指定しなければ絞り込まない
+        assert_eq!(all.len(), 3);
+        assert!(repository
+            .get_all_tow_trucks(
+                None,
+                Some(AreaId(area_id)),
+                false,
+                Some("no_such_model".to_string()),
+            )
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn paginated_tow_trucks_reject_negative_pages_while_the_full_listing_returns_all() {
+        let Some(pool) = test_pool().await else {
+            return;
+        };
+        let repository = TowTruckRepositoryImpl::new(pool.clone(), circuit_breaker());
+        let area_id = seed_area(&pool).await;
+        let node_id = seed_node(&pool, area_id).await;
+        let mut tow_truck_ids = Vec::new();
+        for _ in 0..3 {
+            let driver_id = seed_user(&pool, "driver").await;
+            tow_truck_ids.push(seed_truck(&pool, driver_id, area_id, node_id).await);
+        }
+
+        for (page, page_size) in [(-1, 10), (0, 0), (0, -1)] {
+            assert!(matches!(
+                repository
+                    .get_paginated_tow_trucks(
+                        page,
+                        page_size,
+                        None,
+                        Some(AreaId(area_id)),
+                        false,
+                        None
+                    )
+                    .await,
+                Err(AppError::BadRequest)
+            ));
+        }
+        let second_page = repository
+            .get_paginated_tow_trucks(1, 2, None, Some(AreaId(area_id)), false, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            second_page
+                .iter()
+                .map(|tow_truck| tow_truck.id)
+                .collect::<Vec<i32>>(),
+            vec![tow_truck_ids[2]]
+        );
+        // 全件の取得はページングの引数を持たない専用のメソッドで行う
+        let all = repository
+            .get_all_tow_trucks(None, Some(AreaId(area_id)), false, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            all.iter()
+                .map(|tow_truck| tow_truck.id)
+                .collect::<Vec<i32>>(),
+            tow_truck_ids
+        );
     }
 }
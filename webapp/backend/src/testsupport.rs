@@ -0,0 +1,285 @@
+// DB を使うテストのための接続とデータの準備
+// TEST_DATABASE_URL には init.sql とマイグレーションを適用したデータベースを指定する
+// 各テストは seed_area で作った新しいエリアの中だけでデータを作るため、既存のデータや並行して動く他のテストと干渉しない
+use crate::infrastructure::circuit_breaker::CircuitBreaker;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use sqlx::mysql::{
+    MySql, MySqlPool, MySqlPoolOptions, MySqlQueryResult, MySqlRow, MySqlStatement, MySqlTypeInfo,
+};
+use sqlx::{Describe, Either, Execute, Executor, Transaction};
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// TEST_DATABASE_URL が未設定の場合は None を返す (呼び出し側のテストは何もせずに終える)
+pub async fn test_pool() -> Option<MySqlPool> {
+    let database_url = env::var("TEST_DATABASE_URL").ok()?;
+    let pool = MySqlPoolOptions::new()
+        .max_connections(5)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to TEST_DATABASE_URL");
+
+    Some(pool)
+}
+
+pub async fn seed_area(pool: &MySqlPool) -> i32 {
+    sqlx::query("INSERT INTO areas (name) VALUES (?)")
+        .bind(format!("test_area_{}", rand::random::<u32>()))
+        .execute(pool)
+        .await
+        .expect("Failed to seed area")
+        .last_insert_id() as i32
+}
+
+// 他のテストと重ならないよう、ユーザー名には乱数を付ける
+pub async fn seed_user(pool: &MySqlPool, role: &str) -> i32 {
+    sqlx::query("INSERT INTO users (username, role) VALUES (?, ?)")
+        .bind(format!("test_{}_{}", role, rand::random::<u32>()))
+        .bind(role)
+        .execute(pool)
+        .await
+        .expect("Failed to seed user")
+        .last_insert_id() as i32
+}
+
+pub async fn seed_node(pool: &MySqlPool, area_id: i32) -> i32 {
+    sqlx::query("INSERT INTO nodes (name, area_id, x, y) VALUES (?, ?, 0, 0)")
+        .bind(format!("test_node_{}", rand::random::<u32>()))
+        .bind(area_id)
+        .execute(pool)
+        .await
+        .expect("Failed to seed node")
+        .last_insert_id() as i32
+}
+
+pub async fn seed_edge(pool: &MySqlPool, node_a_id: i32, node_b_id: i32, weight: i32) -> i32 {
+    sqlx::query("INSERT INTO edges (node_a_id, node_b_id, weight) VALUES (?, ?, ?)")
+        .bind(node_a_id)
+        .bind(node_b_id)
+        .bind(weight)
+        .execute(pool)
+        .await
+        .expect("Failed to seed edge")
+        .last_insert_id() as i32
+}
+
+// トラックの現在地は locations の最新の行なので、位置も1件登録する
+pub async fn seed_truck(pool: &MySqlPool, driver_id: i32, area_id: i32, node_id: i32) -> i32 {
+    let tow_truck_id = sqlx::query("INSERT INTO tow_trucks (driver_id, area_id) VALUES (?, ?)")
+        .bind(driver_id)
+        .bind(area_id)
+        .execute(pool)
+        .await
+        .expect("Failed to seed tow truck")
+        .last_insert_id() as i32;
+    sqlx::query("INSERT INTO locations (tow_truck_id, node_id) VALUES (?, ?)")
+        .bind(tow_truck_id)
+        .bind(node_id)
+        .execute(pool)
+        .await
+        .expect("Failed to seed location");
+
+    tow_truck_id
+}
+
+// 未配車 (pending) の注文を作る。エリアは注文地点のノードのエリアにする
+pub async fn seed_order(pool: &MySqlPool, client_id: i32, node_id: i32) -> i32 {
+    sqlx::query(
+        "INSERT INTO orders (client_id, node_id, area_id, car_value)
+        SELECT ?, id, area_id, 1000000 FROM nodes WHERE id = ?",
+    )
+    .bind(client_id)
+    .bind(node_id)
+    .execute(pool)
+    .await
+    .expect("Failed to seed order")
+    .last_insert_id() as i32
+}
+
+// 配車担当者のユーザーと dispatchers の行を作り、dispatchers の ID を返す
+pub async fn seed_dispatcher(pool: &MySqlPool, area_id: i32) -> i32 {
+    let user_id = seed_user(pool, "dispatcher").await;
+    sqlx::query("INSERT INTO dispatchers (user_id, area_id) VALUES (?, ?)")
+        .bind(user_id)
+        .bind(area_id)
+        .execute(pool)
+        .await
+        .expect("Failed to seed dispatcher")
+        .last_insert_id() as i32
+}
+
+// 注文をトラックに配車した状態 (注文は dispatched、トラックは busy) にする
+pub async fn seed_dispatch(pool: &MySqlPool, order_id: i32, tow_truck_id: i32) {
+    sqlx::query("UPDATE orders SET status = 'dispatched', tow_truck_id = ? WHERE id = ?")
+        .bind(tow_truck_id)
+        .bind(order_id)
+        .execute(pool)
+        .await
+        .expect("Failed to dispatch seeded order");
+    sqlx::query("UPDATE tow_trucks SET status = 'busy' WHERE id = ?")
+        .bind(tow_truck_id)
+        .execute(pool)
+        .await
+        .expect("Failed to mark seeded tow truck busy");
+}
+
+// ユーザーのセッションを作り、Authorization ヘッダーに渡すトークンを返す
+pub async fn seed_session(pool: &MySqlPool, user_id: i32) -> String {
+    let session_token = format!("test_session_{}", rand::random::<u64>());
+    sqlx::query("INSERT INTO sessions (user_id, session_token) VALUES (?, ?)")
+        .bind(user_id)
+        .bind(&session_token)
+        .execute(pool)
+        .await
+        .expect("Failed to seed session");
+
+    session_token
+}
+
+// テストで作るリポジトリに渡す遮断器 (接続できる DB を使うため、遮断しない前提で既定値を使う)
+pub fn circuit_breaker() -> Arc<CircuitBreaker> {
+    Arc::new(CircuitBreaker::new(5, Duration::from_secs(10)))
+}
+
+// 発行したクエリの数を数える接続プール
+// テストではリポジトリがこの型を DbPool として持つため、サービスの呼び出しが何回 DB に問い合わせたかを確かめられる
+// begin() で始めたトランザクションの中のクエリも同じカウンタで数える
+#[derive(Debug, Clone)]
+pub struct CountingPool {
+    pool: MySqlPool,
+    queries: Arc<AtomicUsize>,
+}
+
+impl CountingPool {
+    pub fn query_count(&self) -> usize {
+        self.queries.load(Ordering::SeqCst)
+    }
+
+    pub async fn begin(&self) -> Result<CountingTransaction, sqlx::Error> {
+        Ok(CountingTransaction {
+            tx: self.pool.begin().await?,
+            queries: self.queries.clone(),
+        })
+    }
+}
+
+// CountingPool::begin() が返すトランザクション。発行したクエリを元のプールのカウンタに加える
+#[derive(Debug)]
+pub struct CountingTransaction {
+    tx: Transaction<'static, MySql>,
+    queries: Arc<AtomicUsize>,
+}
+
+impl CountingTransaction {
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        self.tx.commit().await
+    }
+
+    pub async fn rollback(self) -> Result<(), sqlx::Error> {
+        self.tx.rollback().await
+    }
+}
+
+impl From<MySqlPool> for CountingPool {
+    fn from(pool: MySqlPool) -> Self {
+        CountingPool {
+            pool,
+            queries: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+// execute / fetch_all などは fetch_many に、fetch_one は fetch_optional に集約されるため、この2つで数える
+impl<'p> Executor<'p> for &'_ CountingPool {
+    type Database = MySql;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<MySqlQueryResult, MySqlRow>, sqlx::Error>>
+    where
+        E: 'q + Execute<'q, MySql>,
+    {
+        self.queries.fetch_add(1, Ordering::SeqCst);
+        self.pool.fetch_many(query)
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<MySqlRow>, sqlx::Error>>
+    where
+        E: 'q + Execute<'q, MySql>,
+    {
+        self.queries.fetch_add(1, Ordering::SeqCst);
+        self.pool.fetch_optional(query)
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [MySqlTypeInfo],
+    ) -> BoxFuture<'e, Result<MySqlStatement<'q>, sqlx::Error>> {
+        self.pool.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'e, Result<Describe<MySql>, sqlx::Error>> {
+        self.pool.describe(sql)
+    }
+}
+
+// CountingPool と同じく fetch_many と fetch_optional で数える
+impl<'c> Executor<'c> for &'c mut CountingTransaction {
+    type Database = MySql;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<MySqlQueryResult, MySqlRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, MySql>,
+    {
+        self.queries.fetch_add(1, Ordering::SeqCst);
+        (&mut self.tx).fetch_many(query)
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<MySqlRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, MySql>,
+    {
+        self.queries.fetch_add(1, Ordering::SeqCst);
+        (&mut self.tx).fetch_optional(query)
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [MySqlTypeInfo],
+    ) -> BoxFuture<'e, Result<MySqlStatement<'q>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        (&mut self.tx).prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'e, Result<Describe<MySql>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        (&mut self.tx).describe(sql)
+    }
+}
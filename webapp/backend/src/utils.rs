@@ -3,6 +3,7 @@ use argon2::{
     Argon2,
 };
 use rand::Rng;
+use std::future::Future;
 
 use crate::errors::AppError;
 
@@ -43,3 +44,130 @@ pub fn verify_password(hashed_password: &str, input_password: &str) -> Result<bo
         Err(_) => Ok(false),
     }
 }
+
+// ページ番号とページサイズを検証し、OFFSET に渡す値を返す
+// 負の値や0件のページはそのまま SQL に渡すとエラーや意図しない結果になるため BadRequest とする
+// 全件の取得はページングとは別のメソッドで行う
+pub fn page_offset(page: i32, page_size: i32) -> Result<i64, AppError> {
+    if page < 0 || page_size <= 0 {
+        return Err(AppError::BadRequest);
+    }
+
+    Ok(i64::from(page) * i64::from(page_size))
+}
+
+// クライアントから受け取った期間の長さを検証し、to_duration で chrono::Duration に変換する
+// 範囲外の値は Duration の生成や時刻の計算で溢れて panic するため、0 から max までに制限する
+pub fn bounded_duration(
+    name: &str,
+    value: i64,
+    max: i64,
+    to_duration: fn(i64) -> chrono::Duration,
+) -> Result<chrono::Duration, AppError> {
+    if !(0..=max).contains(&value) {
+        return Err(AppError::ValidationError(format!(
+            "{} must be between 0 and {}",
+            name, max
+        )));
+    }
+
+    Ok(to_duration(value))
+}
+
+// IN 句が大きくなりすぎないよう、IDを重複を除いて chunk_size 件ずつに分けて取得し、結果をまとめて返す
+// IDが1件もない場合は問い合わせ自体を省く
+pub async fn fetch_in_chunks<T, F, Fut>(
+    ids: &[i32],
+    chunk_size: usize,
+    fetch: F,
+) -> Result<Vec<T>, AppError>
+where
+    F: Fn(Vec<i32>) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, AppError>>,
+{
+    let mut unique_ids = ids.to_vec();
+    unique_ids.sort_unstable();
+    unique_ids.dedup();
+
+    let mut results = Vec::new();
+    for chunk in unique_ids.chunks(chunk_size.max(1)) {
+        results.extend(fetch(chunk.to_vec()).await?);
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounded_duration_accepts_values_within_the_range() {
+        let duration = bounded_duration("window_seconds", 90, 3600, chrono::Duration::seconds);
+        assert_eq!(duration.unwrap(), chrono::Duration::seconds(90));
+        let duration = bounded_duration("window_minutes", 0, 60, chrono::Duration::minutes);
+        assert_eq!(duration.unwrap(), chrono::Duration::zero());
+    }
+
+    #[test]
+    fn bounded_duration_rejects_negative_and_oversized_values() {
+        for value in [-1, 3601, i64::MIN, i64::MAX] {
+            match bounded_duration("window_seconds", value, 3600, chrono::Duration::seconds) {
+                Err(AppError::ValidationError(message)) => {
+                    assert_eq!(message, "window_seconds must be between 0 and 3600")
+                }
+                other => panic!("expected a validation error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn page_offset_rejects_negative_pages_and_non_positive_page_sizes() {
+        assert_eq!(page_offset(0, 10).unwrap(), 0);
+        assert_eq!(page_offset(3, 10).unwrap(), 30);
+        // i32 同士の掛け算では溢れる大きさでも i64 で計算する
+        assert_eq!(
+            page_offset(i32::MAX, i32::MAX).unwrap(),
+            i64::from(i32::MAX) * i64::from(i32::MAX)
+        );
+        for (page, page_size) in [(-1, 10), (0, 0), (0, -1), (i32::MIN, 10)] {
+            assert!(matches!(
+                page_offset(page, page_size),
+                Err(AppError::BadRequest)
+            ));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn fetch_in_chunks_splits_deduplicated_ids_at_the_chunk_size() {
+        // 0..250 を重複させて渡しても、重複を除いた 250 件を 100, 100, 50 件に分けて問い合わせる
+        let ids: Vec<i32> = (0..250).chain(0..250).rev().collect();
+        let chunks = std::cell::RefCell::new(Vec::new());
+
+        let fetched = fetch_in_chunks(&ids, 100, |chunk| {
+            chunks.borrow_mut().push(chunk.clone());
+            async move { Ok::<_, AppError>(chunk) }
+        })
+        .await
+        .unwrap();
+
+        let chunk_sizes: Vec<usize> = chunks.borrow().iter().map(Vec::len).collect();
+        assert_eq!(chunk_sizes, vec![100, 100, 50]);
+        assert_eq!(fetched, (0..250).collect::<Vec<i32>>());
+    }
+
+    #[actix_rt::test]
+    async fn fetch_in_chunks_skips_the_query_without_ids() {
+        let calls = std::cell::Cell::new(0);
+
+        let fetched = fetch_in_chunks(&[], 100, |chunk| {
+            calls.set(calls.get() + 1);
+            async move { Ok::<_, AppError>(chunk) }
+        })
+        .await
+        .unwrap();
+
+        assert!(fetched.is_empty());
+        assert_eq!(calls.get(), 0);
+    }
+}
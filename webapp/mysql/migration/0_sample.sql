@@ -15,3 +15,84 @@ ALTER TABLE orders ADD INDEX index_orders_on_order_time(order_time);
 ALTER TABLE orders ADD INDEX index_orders_on_area_id_and_status(area_id, status);
 ALTER TABLE nodes ADD INDEX index_nodes_on_area_id(area_id);
 ALTER TABLE sessions ADD INDEX idx_session_token(session_token);
+
+-- 1台のトラックが複数の注文を完了できるように tow_truck_id の UNIQUE 制約を外す
+-- init.sql の UNIQUE のままだと、同じトラックの2件目の完了 (一括完了を含む) が重複キーで失敗する
+-- 外部キーのために索引は必要なので、UNIQUE ではない索引に置き換える
+ALTER TABLE completed_orders ADD INDEX index_completed_orders_on_tow_truck_id(tow_truck_id), DROP INDEX tow_truck_id;
+
+-- セッションの有効期限 (デフォルトで作成から1日)
+ALTER TABLE sessions ADD COLUMN expires_at DATETIME NOT NULL DEFAULT (CURRENT_TIMESTAMP + INTERVAL 1 DAY);
+ALTER TABLE sessions ADD INDEX index_sessions_on_expires_at(expires_at);
+
+-- 搬送先 (ガレージなど) のノード
+ALTER TABLE orders ADD COLUMN destination_node_id INT NULL;
+
+-- トラックが最後に生存報告 (位置更新・ハートビート) をした時刻
+ALTER TABLE tow_trucks ADD COLUMN last_updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+-- 完了後にトラックが戻る拠点 (エリアごとに複数可)
+CREATE TABLE IF NOT EXISTS depots (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    area_id INT NOT NULL,
+    node_id INT NOT NULL,
+    INDEX index_depots_on_area_id(area_id)
+);
+
+-- 顧客の連絡先電話番号
+ALTER TABLE orders ADD COLUMN contact_phone VARCHAR(16) NULL;
+
+-- 大型車両など、1件の注文に複数台のトラックが必要な場合の台数と割り当て
+ALTER TABLE orders ADD COLUMN trucks_required INT NOT NULL DEFAULT 1;
+CREATE TABLE IF NOT EXISTS order_tow_trucks (
+    order_id INT NOT NULL,
+    tow_truck_id INT NOT NULL,
+    PRIMARY KEY (order_id, tow_truck_id),
+    INDEX index_order_tow_trucks_on_tow_truck_id(tow_truck_id)
+);
+
+-- 配車・完了の履歴 (トラックごとの稼働率の集計に使う)
+CREATE TABLE IF NOT EXISTS order_events (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    order_id INT NOT NULL,
+    tow_truck_id INT NOT NULL,
+    event_type ENUM('dispatched', 'completed') NOT NULL,
+    occurred_at DATETIME NOT NULL,
+    INDEX index_order_events_on_tow_truck_id_and_occurred_at(tow_truck_id, occurred_at),
+    INDEX index_order_events_on_order_id(order_id)
+);
+
+-- 廃車などで運用から外したトラック (論理削除)
+ALTER TABLE tow_trucks ADD COLUMN deleted_at DATETIME NULL;
+
+-- 注文をキャンセルした理由 (customer_canceled, no_coverage, duplicate, other)
+ALTER TABLE orders ADD COLUMN cancel_reason VARCHAR(32) NULL;
+
+-- 配車する仕事との相性を判断するための車種と年式
+ALTER TABLE tow_trucks ADD COLUMN model VARCHAR(255) NULL;
+ALTER TABLE tow_trucks ADD COLUMN year INT NULL;
+
+-- 配車時点での到着予定時間(秒)。見積もりの精度を後から検証するために記録する
+ALTER TABLE orders ADD COLUMN eta_seconds INT NULL;
+
+-- 隣接するエリアの組 (向きは問わない)。エリア内に空きトラックがいない場合の配車に使う
+CREATE TABLE IF NOT EXISTS area_adjacencies (
+    area_id INT NOT NULL,
+    adjacent_area_id INT NOT NULL,
+    PRIMARY KEY (area_id, adjacent_area_id),
+    INDEX index_area_adjacencies_on_adjacent_area_id(adjacent_area_id)
+);
+
+-- locations は位置更新ごとに行を追加する履歴として使う (現在地は最新の行)。期間を指定した履歴の取得用
+ALTER TABLE locations ADD INDEX index_locations_on_tow_truck_id_and_timestamp(tow_truck_id, timestamp);
+
+-- 最後に記録した位置情報の送信時刻 (トラック側の時計)。遅れて届いた古い位置情報を捨てるために使う
+-- 同じ秒に送られた位置情報も順序を比べられるよう、送信時刻はマイクロ秒まで保持する
+ALTER TABLE tow_trucks ADD COLUMN location_sent_at DATETIME(6) NULL;
+UPDATE tow_trucks
+SET location_sent_at = (
+    SELECT MAX(timestamp)
+    FROM locations
+    WHERE locations.tow_truck_id = tow_trucks.id
+);
+ALTER TABLE locations MODIFY COLUMN timestamp DATETIME(6) DEFAULT CURRENT_TIMESTAMP(6);